@@ -0,0 +1,789 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use web_time::Instant;
+
+use crate::analysis::{Analysis, Candidate};
+use crate::board::{Board, PlayerColor, Point};
+use crate::game::{Game, GameResult, Move};
+#[cfg(feature = "nn")]
+use crate::nn::Network;
+
+// the usual UCT exploration constant, about sqrt(2)
+const EXPLORATION: f32 = 1.4;
+// how far PUCT trusts the network's move probabilities over what search has found
+#[cfg(feature = "nn")]
+const PRIOR_WEIGHT: f32 = 1.5;
+// the computer resigns once its best move wins less often than this...
+const RESIGN_BELOW: f32 = 0.05;
+// ...over at least this many playouts
+const RESIGN_AFTER: u32 = 200;
+// moves of expected play reported with each candidate in an analysis
+const PV_LENGTH: usize = 10;
+
+/// How long the computer thinks about each move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Budget {
+    Playouts(u32),
+    Time(Duration),
+}
+impl Default for Budget {
+    fn default() -> Self {
+        Budget::Playouts(2000)
+    }
+}
+
+impl Budget {
+    fn spent(self, playouts: u32, started: Instant) -> bool {
+        match self {
+            Budget::Playouts(limit) => playouts >= limit,
+            // always finish at least one, so there's a move to give
+            Budget::Time(limit) => playouts > 0 && started.elapsed() >= limit,
+        }
+    }
+}
+
+/// A computer opponent: the colour it plays, how hard it thinks and the
+/// ONNX network guiding it, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Computer {
+    pub color: PlayerColor,
+    pub budget: Budget,
+    pub network: Option<std::path::PathBuf>,
+}
+
+struct Node {
+    mv: Move,
+    // who played `mv`
+    color: PlayerColor,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // legal moves that don't have a child yet, in random order
+    untried: Vec<Move>,
+    visits: u32,
+    // playouts won by `color` through this node
+    wins: f32,
+    // Black's lead at the end of those playouts, added up
+    score: f32,
+}
+impl Node {
+    fn uct(&self, parent_visits_ln: f32) -> f32 {
+        let visits = self.visits as f32;
+        self.wins / visits + EXPLORATION * (parent_visits_ln / visits).sqrt()
+    }
+}
+
+/// Picks a move for the player to move with Monte Carlo tree search over
+/// random playouts. Gives up early, with the best move so far, once `stop` is set.
+pub fn best_move(game: &Game, budget: Budget, stop: &AtomicBool) -> Move {
+    let (nodes, _) = search(game, budget, stop);
+    let best = nodes[0].children.iter().max_by_key(|&&i| nodes[i].visits);
+    choose(best.map(|&i| (nodes[i].mv, nodes[i].visits, nodes[i].wins)))
+}
+
+/// Searches like `best_move`, but reports how every move it tried fared
+/// instead of picking one.
+pub fn analyze(game: &Game, budget: Budget, stop: &AtomicBool) -> Analysis {
+    let (nodes, ownership) = search(game, budget, stop);
+    let to_move = game.to_move();
+    let most_visited = |node: usize| {
+        nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|&i| nodes[i].visits)
+    };
+
+    let mut candidates = nodes[0]
+        .children
+        .iter()
+        .map(|&i| {
+            let node = &nodes[i];
+            let visits = node.visits as f32;
+            let lead = node.score / visits;
+            // the line of play search expects, most visited reply after most visited reply
+            let mut pv = vec![node.mv];
+            let mut next = most_visited(i);
+            while let Some(j) = next.filter(|_| pv.len() < PV_LENGTH) {
+                pv.push(nodes[j].mv);
+                next = most_visited(j);
+            }
+            Candidate {
+                mv: node.mv,
+                visits: node.visits,
+                winrate: node.wins / visits,
+                score_lead: Some(if to_move == PlayerColor::Black {
+                    lead
+                } else {
+                    -lead
+                }),
+                pv,
+            }
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.visits));
+    Analysis {
+        to_move,
+        visits: nodes[0].visits,
+        candidates,
+        ownership,
+    }
+}
+
+// grows a tree below the position; the position itself is nodes[0]. Also
+// gives who ended up owning each point, on average over the playouts.
+fn search(game: &Game, budget: Budget, stop: &AtomicBool) -> (Vec<Node>, Option<Vec<f32>>) {
+    let mut rng = rand::thread_rng();
+    let started = Instant::now();
+    let size = game.board().size();
+    let mut owned = vec![0.0; size * size];
+    let mut finished = 0;
+    let mut nodes = vec![Node {
+        mv: Move::Pass,
+        color: game.to_move().opponent(),
+        parent: None,
+        children: Vec::new(),
+        untried: candidate_moves(game, &mut rng),
+        visits: 0,
+        wins: 0.0,
+        score: 0.0,
+    }];
+
+    let mut playouts = 0;
+    while !budget.spent(playouts, started) && (playouts == 0 || !stop.load(Ordering::Relaxed)) {
+        playouts += 1;
+
+        // walk down through fully expanded nodes
+        let mut position = game.clone();
+        let mut node = 0;
+        while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+            let parent_visits_ln = (nodes[node].visits as f32).ln();
+            node = *nodes[node]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    nodes[a]
+                        .uct(parent_visits_ln)
+                        .total_cmp(&nodes[b].uct(parent_visits_ln))
+                })
+                .unwrap();
+            position
+                .play(nodes[node].mv)
+                .expect("moves in the tree were legal when they were added");
+        }
+
+        // add one new move below it
+        if let Some(mv) = nodes[node].untried.pop() {
+            let color = position.to_move();
+            position
+                .play(mv)
+                .expect("untried moves are checked before they are listed");
+            nodes.push(Node {
+                mv,
+                color,
+                parent: Some(node),
+                children: Vec::new(),
+                untried: candidate_moves(&position, &mut rng),
+                visits: 0,
+                wins: 0.0,
+                score: 0.0,
+            });
+            let child = nodes.len() - 1;
+            nodes[node].children.push(child);
+            node = child;
+        }
+
+        let (winner, lead) = match position.result() {
+            Some(result) => (result.winner(), black_lead(result)),
+            None => {
+                let end = playout(&position, &mut rng);
+                for (i, owned) in owned.iter_mut().enumerate() {
+                    match end.owner(i) {
+                        Some(PlayerColor::Black) => *owned += 1.0,
+                        Some(PlayerColor::White) => *owned -= 1.0,
+                        None => {}
+                    }
+                }
+                finished += 1;
+                let [black, white] = end.area();
+                let lead = black - white - game.settings.komi;
+                let winner = if lead > 0.0 {
+                    PlayerColor::Black
+                } else {
+                    PlayerColor::White
+                };
+                (winner, lead)
+            }
+        };
+        let mut next = Some(node);
+        while let Some(i) = next {
+            nodes[i].visits += 1;
+            nodes[i].score += lead;
+            if nodes[i].color == winner {
+                nodes[i].wins += 1.0;
+            }
+            next = nodes[i].parent;
+        }
+    }
+    let ownership =
+        (finished > 0).then(|| owned.iter().map(|owned| owned / finished as f32).collect());
+    (nodes, ownership)
+}
+
+// by how much Black finished ahead; nothing is known of the score of a resigned game
+fn black_lead(result: GameResult) -> f32 {
+    match result {
+        GameResult::Score {
+            winner: PlayerColor::Black,
+            margin,
+        } => margin,
+        GameResult::Score { margin, .. } => -margin,
+        _ => 0.0,
+    }
+}
+
+// the most visited move, unless even that one is hopeless
+fn choose(best: Option<(Move, u32, f32)>) -> Move {
+    match best {
+        Some((_, visits, wins))
+            if visits >= RESIGN_AFTER && wins / (visits as f32) < RESIGN_BELOW =>
+        {
+            Move::Resign
+        }
+        Some((mv, _, _)) => mv,
+        None => Move::Pass,
+    }
+}
+
+#[cfg(feature = "nn")]
+struct GuidedNode {
+    mv: Move,
+    color: PlayerColor,
+    parent: Option<usize>,
+    // empty until the network has looked at the position
+    children: Vec<usize>,
+    // the network's probability for `mv`
+    prior: f32,
+    visits: u32,
+    // the sum over visits of `color`'s chance of winning, from 0 to 1
+    wins: f32,
+}
+
+/// Like `best_move`, but the network's move probabilities decide which moves
+/// get searched (PUCT) and its value stands in for random playouts.
+#[cfg(feature = "nn")]
+pub fn best_move_guided(
+    game: &Game,
+    budget: Budget,
+    network: &Network,
+    stop: &AtomicBool,
+) -> Result<Move, crate::nn::NnError> {
+    let started = Instant::now();
+    let mut nodes = vec![GuidedNode {
+        mv: Move::Pass,
+        color: game.to_move().opponent(),
+        parent: None,
+        children: Vec::new(),
+        prior: 1.0,
+        visits: 0,
+        wins: 0.0,
+    }];
+
+    let mut playouts = 0;
+    while !budget.spent(playouts, started) && (playouts == 0 || !stop.load(Ordering::Relaxed)) {
+        playouts += 1;
+
+        let mut position = game.clone();
+        let mut node = 0;
+        while !nodes[node].children.is_empty() {
+            let visits_sqrt = (nodes[node].visits as f32).sqrt();
+            let score = |child: &GuidedNode| {
+                // moves not yet tried start out looking like losses
+                let value = if child.visits == 0 {
+                    0.0
+                } else {
+                    child.wins / child.visits as f32
+                };
+                value + PRIOR_WEIGHT * child.prior * visits_sqrt / (1 + child.visits) as f32
+            };
+            node = *nodes[node]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| score(&nodes[a]).total_cmp(&score(&nodes[b])))
+                .unwrap();
+            position
+                .play(nodes[node].mv)
+                .expect("moves in the tree were legal when they were added");
+        }
+
+        // the chance that the player to move at the leaf wins
+        let to_move = position.to_move();
+        let value = match position.result() {
+            Some(result) if result.winner() == to_move => 1.0,
+            Some(_) => 0.0,
+            None => {
+                let evaluation = network.evaluate(&position)?;
+                let size = position.board().size();
+                let moves = candidate_moves(&position, &mut rand::thread_rng());
+                let index = |mv: &Move| match *mv {
+                    Move::Play((x, y)) => y * size + x,
+                    _ => size * size,
+                };
+                // only legal moves share the probability
+                let total = moves
+                    .iter()
+                    .map(|mv| evaluation.policy[index(mv)])
+                    .sum::<f32>()
+                    .max(f32::MIN_POSITIVE);
+                for mv in moves {
+                    nodes.push(GuidedNode {
+                        mv,
+                        color: to_move,
+                        parent: Some(node),
+                        children: Vec::new(),
+                        prior: evaluation.policy[index(&mv)] / total,
+                        visits: 0,
+                        wins: 0.0,
+                    });
+                    let child = nodes.len() - 1;
+                    nodes[node].children.push(child);
+                }
+                (evaluation.value + 1.0) / 2.0
+            }
+        };
+
+        let mut next = Some(node);
+        while let Some(i) = next {
+            nodes[i].visits += 1;
+            nodes[i].wins += if nodes[i].color == to_move {
+                value
+            } else {
+                1.0 - value
+            };
+            next = nodes[i].parent;
+        }
+    }
+
+    let best = nodes[0].children.iter().max_by_key(|&&i| nodes[i].visits);
+    Ok(choose(
+        best.map(|&i| (nodes[i].mv, nodes[i].visits, nodes[i].wins)),
+    ))
+}
+
+/// Every legal move worth trying, passing included, shuffled. Filling in one's
+/// own eyes is left out.
+fn candidate_moves(game: &Game, rng: &mut impl Rng) -> Vec<Move> {
+    if game.is_over() {
+        return Vec::new();
+    }
+    let board = game.board();
+    let color = game.to_move();
+    let mut moves = vec![Move::Pass];
+    for y in 0..board.size() {
+        for x in 0..board.size() {
+            let point = (x, y);
+            if !is_eye(board, point, color) && game.check_play(point).is_ok() {
+                moves.push(Move::Play(point));
+            }
+        }
+    }
+    moves.shuffle(rng);
+    moves
+}
+
+// empty with only `color`'s stones next to it; good enough for random play
+fn is_eye(board: &Board, point: Point, color: PlayerColor) -> bool {
+    board.get(point).is_none() && board.neighbours(point).all(|n| board.get(n) == Some(color))
+}
+
+/// Plays random moves until neither side has anything left but to fill its
+/// own eyes, and gives the board as it ends up.
+fn playout(game: &Game, rng: &mut impl Rng) -> Playout {
+    let mut board = Playout::new(game.board());
+    let size = board.size;
+    let mut color = game.to_move();
+    let mut passes = 0;
+
+    // long enough for any sensible game; stops the rare endless capture loop
+    for _ in 0..size * size * 3 {
+        if passes >= 2 {
+            break;
+        }
+        match board.random_move(color, rng) {
+            Some(i) => {
+                board.play(i, color);
+                passes = 0;
+            }
+            None => {
+                board.ko = None;
+                passes += 1;
+            }
+        }
+        color = color.opponent();
+    }
+
+    board
+}
+
+/// The board as playouts see it. Each chain's stones are linked in a ring
+/// and the chain keeps a count of its pseudo-liberties (one per stone per
+/// empty neighbour, so shared liberties count more than once), which is
+/// enough to spot captures and suicide without a flood fill.
+struct Playout {
+    size: usize,
+    stones: Vec<Option<PlayerColor>>,
+    // the stone that stands for each stone's chain
+    chain: Vec<usize>,
+    // the next stone round the same chain
+    next: Vec<usize>,
+    // pseudo-liberties, kept on the chain's own stone
+    liberties: Vec<u32>,
+    empty: Vec<usize>,
+    // where each empty point is in `empty`
+    empty_at: Vec<usize>,
+    ko: Option<usize>,
+}
+impl Playout {
+    // the ko, if any, isn't carried over from the game
+    fn new(board: &Board) -> Self {
+        let size = board.size();
+        let points = size * size;
+        let mut playout = Playout {
+            size,
+            stones: vec![None; points],
+            chain: (0..points).collect(),
+            next: (0..points).collect(),
+            liberties: vec![0; points],
+            empty: (0..points).collect(),
+            empty_at: (0..points).collect(),
+            ko: None,
+        };
+        for ((x, y), color) in board.stones() {
+            playout.add_stone(y * size + x, color);
+        }
+        playout
+    }
+
+    fn neighbours(&self, i: usize) -> impl Iterator<Item = usize> {
+        let size = self.size;
+        let (x, y) = (i % size, i / size);
+        [
+            (x > 0).then(|| i - 1),
+            (x + 1 < size).then(|| i + 1),
+            (y > 0).then(|| i - size),
+            (y + 1 < size).then(|| i + size),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    fn is_eye(&self, i: usize, color: PlayerColor) -> bool {
+        self.neighbours(i).all(|n| self.stones[n] == Some(color))
+    }
+
+    fn is_legal(&self, i: usize, color: PlayerColor) -> bool {
+        if self.ko == Some(i) {
+            return false;
+        }
+        self.neighbours(i).any(|n| match self.stones[n] {
+            None => true,
+            Some(stone) => {
+                let chain = self.chain[n];
+                let touching = self
+                    .neighbours(i)
+                    .filter(|&m| self.stones[m].is_some() && self.chain[m] == chain)
+                    .count() as u32;
+                if stone == color {
+                    // the chain keeps a liberty somewhere else
+                    self.liberties[chain] > touching
+                } else {
+                    // this is the chain's last liberty, so it's captured
+                    self.liberties[chain] == touching
+                }
+            }
+        })
+    }
+
+    /// A random legal move for `color` that doesn't fill its own eye, if there is one.
+    fn random_move(&self, color: PlayerColor, rng: &mut impl Rng) -> Option<usize> {
+        let count = self.empty.len();
+        if count == 0 {
+            return None;
+        }
+        let start = rng.gen_range(0..count);
+        (0..count)
+            .map(|k| self.empty[(start + k) % count])
+            .find(|&i| !self.is_eye(i, color) && self.is_legal(i, color))
+    }
+
+    fn play(&mut self, i: usize, color: PlayerColor) {
+        self.add_stone(i, color);
+        let mut captured = 0;
+        let mut last_captured = i;
+        for n in self.neighbours(i) {
+            if self.stones[n] == Some(color.opponent()) && self.liberties[self.chain[n]] == 0 {
+                last_captured = n;
+                captured += self.remove_chain(self.chain[n]);
+            }
+        }
+        // `i` still stands for its chain, as `add_stone` merges others into it
+        let alone = self.next[i] == i;
+        self.ko = (captured == 1 && alone && self.liberties[i] == 1).then_some(last_captured);
+    }
+
+    // places a stone without taking anything
+    fn add_stone(&mut self, i: usize, color: PlayerColor) {
+        self.stones[i] = Some(color);
+        self.chain[i] = i;
+        self.next[i] = i;
+        self.liberties[i] = 0;
+        let last = self.empty.pop().unwrap();
+        if last != i {
+            let at = self.empty_at[i];
+            self.empty[at] = last;
+            self.empty_at[last] = at;
+        }
+
+        for n in self.neighbours(i) {
+            match self.stones[n] {
+                None => self.liberties[i] += 1,
+                Some(_) => self.liberties[self.chain[n]] -= 1,
+            }
+        }
+        for n in self.neighbours(i) {
+            let other = self.chain[n];
+            if self.stones[n] == Some(color) && other != i {
+                let mut stone = other;
+                loop {
+                    self.chain[stone] = i;
+                    stone = self.next[stone];
+                    if stone == other {
+                        break;
+                    }
+                }
+                // joins the two rings into one
+                self.next.swap(i, other);
+                self.liberties[i] += self.liberties[other];
+            }
+        }
+    }
+
+    fn remove_chain(&mut self, chain: usize) -> usize {
+        let mut removed = 0;
+        let mut stone = chain;
+        loop {
+            self.stones[stone] = None;
+            self.empty_at[stone] = self.empty.len();
+            self.empty.push(stone);
+            removed += 1;
+            stone = self.next[stone];
+            if stone == chain {
+                break;
+            }
+        }
+        // the ring is still intact, so go round again handing liberties back
+        loop {
+            for n in self.neighbours(stone) {
+                if self.stones[n].is_some() {
+                    self.liberties[self.chain[n]] += 1;
+                }
+            }
+            stone = self.next[stone];
+            if stone == chain {
+                break;
+            }
+        }
+        removed
+    }
+
+    // the stone on `i`, or the colour of every stone around it
+    fn owner(&self, i: usize) -> Option<PlayerColor> {
+        match self.stones[i] {
+            Some(color) => Some(color),
+            None => {
+                let mut colors = self.neighbours(i).map(|n| self.stones[n]);
+                let first = colors.next().flatten();
+                first.filter(|_| colors.all(|c| c == first))
+            }
+        }
+    }
+
+    /// Stones plus the empty points surrounded by one colour, without komi.
+    fn area(&self) -> [f32; 2] {
+        let mut area = [0.0, 0.0];
+        for i in 0..self.stones.len() {
+            if let Some(owner) = self.owner(i) {
+                area[owner.index()] += 1.0;
+            }
+        }
+        area
+    }
+}
+
+/// A search for the next move running on its own thread; the answer comes
+/// back through `poll`. Dropping it stops the search.
+pub struct Search {
+    // how many moves had been played when it started, to spot an answer
+    // that an undo has made stale
+    pub moves: usize,
+    stop: Arc<AtomicBool>,
+    result: Receiver<Move>,
+}
+impl Search {
+    pub fn start(game: &Game, budget: Budget) -> Search {
+        Search::spawn(game, move |game, stop| best_move(game, budget, stop))
+    }
+
+    /// Searches with `network`'s help, falling back to playouts if it fails.
+    #[cfg(feature = "nn")]
+    pub fn start_guided(game: &Game, budget: Budget, network: Arc<Network>) -> Search {
+        Search::spawn(game, move |game, stop| {
+            best_move_guided(game, budget, &network, stop).unwrap_or_else(|e| {
+                log::warn!("the network couldn't evaluate the position: {}", e);
+                best_move(game, budget, stop)
+            })
+        })
+    }
+
+    fn spawn(
+        game: &Game,
+        think: impl FnOnce(&Game, &AtomicBool) -> Move + Send + 'static,
+    ) -> Search {
+        let (sender, result) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let moves = game.moves().len();
+        let game = game.clone();
+
+        // browsers have no threads, so there it thinks on the spot
+        #[cfg(target_arch = "wasm32")]
+        let _ = sender.send(think(&game, &stop));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let _ = sender.send(think(&game, &stop));
+            });
+        }
+
+        Search {
+            moves,
+            stop,
+            result,
+        }
+    }
+
+    /// The chosen move, once the search has finished.
+    pub fn poll(&self) -> Option<Move> {
+        self.result.try_recv().ok()
+    }
+}
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameSettings;
+    use crate::sgf;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // the playout board holds the same stones as the game, and lets the player
+    // to move go exactly where the game does
+    fn assert_agree(game: &Game, playout: &Playout) {
+        let size = game.board().size();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * size + x;
+                assert_eq!(
+                    playout.stones[i],
+                    game.board().get((x, y)),
+                    "stone at {:?}",
+                    (x, y)
+                );
+                if playout.stones[i].is_none() {
+                    assert_eq!(
+                        playout.is_legal(i, game.to_move()),
+                        game.check_play((x, y)).is_ok(),
+                        "{} at {:?}",
+                        game.to_move().name(),
+                        (x, y)
+                    );
+                }
+            }
+        }
+    }
+
+    fn play_both(game: &mut Game, playout: &mut Playout, point: Point) {
+        let (x, y) = point;
+        playout.play(y * game.board().size() + x, game.to_move());
+        game.play(Move::Play(point)).unwrap();
+        assert_agree(game, playout);
+    }
+
+    #[test]
+    fn playouts_capture_like_the_game() {
+        let lines = [
+            // black takes a ko, white can't take back until a move elsewhere each
+            "ba ca ab bb bc db ii cc cb ia hh bb",
+            // two stones on the edge, then black can't play into the corner white left
+            "aa ca ba ab ff bb gg aa",
+            // a chain of three taken from the middle, which leaves an eye
+            "ed ee de fe ef dd ii fd aa ge bb ff cc eg dg df",
+        ];
+        for line in lines {
+            let mut game = Game::new(GameSettings {
+                size: 9,
+                ..GameSettings::default()
+            });
+            let mut playout = Playout::new(game.board());
+            for point in line.split(' ') {
+                let point = sgf::decode_point(point, 9).unwrap();
+                play_both(&mut game, &mut playout, point);
+            }
+        }
+    }
+
+    #[test]
+    fn playouts_follow_random_games() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            let mut game = Game::new(GameSettings {
+                size: 9,
+                ..GameSettings::default()
+            });
+            let mut playout = Playout::new(game.board());
+            for _ in 0..150 {
+                let legal = (0..81)
+                    .map(|i| (i % 9, i / 9))
+                    .filter(|&point| game.check_play(point).is_ok())
+                    .collect::<Vec<_>>();
+                let Some(&point) = legal.choose(&mut rng) else {
+                    break;
+                };
+                play_both(&mut game, &mut playout, point);
+            }
+        }
+    }
+
+    #[test]
+    fn search_takes_a_group_in_atari() {
+        // black and white each have a group with one liberty; whoever takes first wins
+        let text = "(;SZ[5]KM[0.5]PL[B]\
+            AB[ba][ab][bb][dc][ec][ad][bd][cd][dd][de]\
+            AW[ca][ea][cb][db][eb][ac][bc][cc])";
+        let game = Game::from_sgf(&sgf::parse(text).unwrap()[0]).unwrap();
+        let stop = AtomicBool::new(false);
+        assert_eq!(
+            best_move(&game, Budget::Playouts(2000), &stop),
+            Move::Play((3, 0))
+        );
+    }
+}
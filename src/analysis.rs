@@ -0,0 +1,582 @@
+//! Reviewing a game with an engine: either the built-in search, or an
+//! external program speaking GTP that understands KataGo's `kata-analyze`
+//! command. Every position of the game is looked at in turn, on a
+//! background thread, and each result comes back through `Analyzer::poll`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ai::Budget;
+use crate::board::{PlayerColor, COLUMN_LETTERS};
+use crate::game::{Game, Move};
+
+/// A move the engine considered, with what it thinks of it. Win rates and
+/// score leads are from the side of the player to move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub mv: Move,
+    pub visits: u32,
+    // from 0 to 1
+    pub winrate: f32,
+    // in points; not every engine estimates it
+    pub score_lead: Option<f32>,
+    // the line of play the engine expects, starting with `mv`
+    pub pv: Vec<Move>,
+}
+
+/// What the engine makes of one position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub to_move: PlayerColor,
+    pub visits: u32,
+    // most visited first
+    pub candidates: Vec<Candidate>,
+    // who each point looks like ending up with, row by row, from -1 for
+    // White to 1 for Black; not every engine estimates it
+    pub ownership: Option<Vec<f32>>,
+}
+impl Analysis {
+    /// Black's chance of winning, going by the engine's favourite move.
+    pub fn black_winrate(&self) -> Option<f32> {
+        let winrate = self.candidates.first()?.winrate;
+        Some(match self.to_move {
+            PlayerColor::Black => winrate,
+            PlayerColor::White => 1.0 - winrate,
+        })
+    }
+
+    /// How many points Black is ahead by, going by the engine's favourite move.
+    pub fn black_score_lead(&self) -> Option<f32> {
+        let lead = self.candidates.first()?.score_lead?;
+        Some(match self.to_move {
+            PlayerColor::Black => lead,
+            PlayerColor::White => -lead,
+        })
+    }
+}
+
+/// Which engine analyses the game, and how long it spends on each position.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Engine {
+    BuiltIn(Budget),
+    // a command line such as "katago gtp -model m.bin.gz -config analysis.cfg"
+    Gtp { command: String, time: Duration },
+}
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    Io(std::io::Error),
+    // the engine answered a command with an error
+    Refused { command: String, reason: String },
+    BadLine(String),
+    Exited,
+    NoCommand,
+    Unsupported,
+}
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalysisError::Io(e) => write!(f, "{}", e),
+            AnalysisError::Refused { command, reason } => {
+                write!(f, "the engine refused \"{}\": {}", command, reason)
+            }
+            AnalysisError::BadLine(line) => write!(f, "couldn't read the engine's \"{}\"", line),
+            AnalysisError::Exited => write!(f, "the engine quit"),
+            AnalysisError::NoCommand => write!(f, "no engine command was given"),
+            AnalysisError::Unsupported => write!(f, "analysis isn't available in the browser"),
+        }
+    }
+}
+impl std::error::Error for AnalysisError {}
+impl From<std::io::Error> for AnalysisError {
+    fn from(e: std::io::Error) -> Self {
+        AnalysisError::Io(e)
+    }
+}
+
+pub enum AnalysisEvent {
+    // the position after this many moves
+    Analysed(usize, Analysis),
+    Failed(AnalysisError),
+}
+
+/// A point or pass as GTP writes it, e.g. "Q16" or "pass".
+pub fn gtp_vertex(mv: Move, size: usize) -> String {
+    match mv {
+        Move::Play(point) => crate::board::point_name(point, size),
+        Move::Pass => "pass".to_string(),
+        Move::Resign => "resign".to_string(),
+    }
+}
+
+/// The reverse of `gtp_vertex`; letters may be either case.
+pub fn parse_gtp_vertex(text: &str, size: usize) -> Option<Move> {
+    match text.to_ascii_lowercase().as_str() {
+        "pass" => return Some(Move::Pass),
+        "resign" => return Some(Move::Resign),
+        _ => {}
+    }
+    let mut chars = text.chars();
+    let letter = chars.next()?.to_ascii_uppercase() as u8;
+    let x = COLUMN_LETTERS.iter().position(|&l| l == letter)?;
+    let row = chars.as_str().parse::<usize>().ok()?;
+    (x < size && (1..=size).contains(&row)).then(|| Move::Play((x, size - row)))
+}
+
+/// Reads one line of `kata-analyze` output, e.g.
+/// "info move D4 visits 120 winrate 0.53 scoreLead 1.2 order 0 pv D4 Q16 info move ...",
+/// possibly ending in "ownership" and a value for every point. Fields it
+/// doesn't know are skipped.
+pub fn parse_kata_analyze(
+    line: &str,
+    size: usize,
+    to_move: PlayerColor,
+) -> Result<Analysis, AnalysisError> {
+    let bad = || AnalysisError::BadLine(line.to_string());
+    let mut words = line.split_whitespace().peekable();
+    let mut candidates = Vec::new();
+    let mut ownership = None;
+    while let Some(word) = words.next() {
+        if word == "ownership" && !candidates.is_empty() {
+            // from the side of the player to move, like everything else
+            let sign = match to_move {
+                PlayerColor::Black => 1.0,
+                PlayerColor::White => -1.0,
+            };
+            let values = words
+                .by_ref()
+                .take(size * size)
+                .map(|value| value.parse::<f32>().map(|v| v * sign))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| bad())?;
+            if values.len() != size * size {
+                return Err(bad());
+            }
+            ownership = Some(values);
+            break;
+        }
+        if word != "info" {
+            return Err(bad());
+        }
+        let mut candidate = Candidate {
+            mv: Move::Pass,
+            visits: 0,
+            winrate: 0.0,
+            score_lead: None,
+            pv: Vec::new(),
+        };
+        while let Some(&key) = words.peek() {
+            if key == "info" || key == "ownership" {
+                break;
+            }
+            words.next();
+            if key == "pv" {
+                while let Some(mv) = words.peek().and_then(|w| parse_gtp_vertex(w, size)) {
+                    candidate.pv.push(mv);
+                    words.next();
+                }
+                continue;
+            }
+            let value = words.next().ok_or_else(bad)?;
+            match key {
+                "move" => candidate.mv = parse_gtp_vertex(value, size).ok_or_else(bad)?,
+                "visits" => candidate.visits = value.parse().map_err(|_| bad())?,
+                "winrate" => candidate.winrate = value.parse().map_err(|_| bad())?,
+                "scoreLead" => candidate.score_lead = Some(value.parse().map_err(|_| bad())?),
+                _ => {}
+            }
+        }
+        candidates.push(candidate);
+    }
+    if candidates.is_empty() {
+        return Err(bad());
+    }
+    Ok(Analysis {
+        to_move,
+        visits: candidates.iter().map(|c| c.visits).sum(),
+        candidates,
+        ownership,
+    })
+}
+
+/// Analyses every position of a game, from the first move on, away from the
+/// UI. Dropping it stops the engine.
+pub struct Analyzer {
+    stop: Arc<AtomicBool>,
+    events: Receiver<AnalysisEvent>,
+}
+impl Analyzer {
+    pub fn start(game: &Game, engine: Engine) -> Analyzer {
+        let (sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let game = game.clone();
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            // no threads to think on and no programs to run
+            let _ = (game, engine);
+            let _ = sender.send(AnalysisEvent::Failed(AnalysisError::Unsupported));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let result = match engine {
+                    Engine::BuiltIn(budget) => {
+                        analyse_built_in(&game, budget, &stop, &sender);
+                        Ok(())
+                    }
+                    Engine::Gtp { command, time } => {
+                        gtp::analyse(&game, &command, time, &stop, &sender)
+                    }
+                };
+                if let Err(e) = result {
+                    let _ = sender.send(AnalysisEvent::Failed(e));
+                }
+            });
+        }
+
+        Analyzer { stop, events }
+    }
+
+    /// Everything analysed since the last call.
+    pub fn poll(&self) -> Vec<AnalysisEvent> {
+        self.events.try_iter().collect()
+    }
+}
+impl Drop for Analyzer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The number of moves before the last position worth analysing: the end
+/// of the game, or the move before a resignation.
+pub fn last_position(game: &Game) -> usize {
+    let moves = game.moves();
+    match moves.last() {
+        Some((_, Move::Resign)) => moves.len() - 1,
+        _ => moves.len(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn analyse_built_in(
+    game: &Game,
+    budget: Budget,
+    stop: &AtomicBool,
+    sender: &mpsc::Sender<AnalysisEvent>,
+) {
+    for n in 0..=last_position(game) {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let analysis = crate::ai::analyze(&game.replayed(n), budget, stop);
+        if sender.send(AnalysisEvent::Analysed(n, analysis)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod gtp {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{Child, ChildStdin, Command, Stdio};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::time::Duration;
+
+    use web_time::Instant;
+
+    use super::{
+        gtp_vertex, last_position, parse_kata_analyze, Analysis, AnalysisError, AnalysisEvent,
+    };
+    use crate::board::PlayerColor;
+    use crate::game::{Game, Move};
+
+    // how often the engine reports while it thinks, in hundredths of a second
+    const REPORT_INTERVAL: u32 = 20;
+
+    fn letter(color: PlayerColor) -> &'static str {
+        match color {
+            PlayerColor::Black => "B",
+            PlayerColor::White => "W",
+        }
+    }
+
+    /// What a line says, if it starts the answer to command `id`: "=id text"
+    /// for success or "?id reason" for failure.
+    pub(super) fn answer_to(line: &str, id: u32) -> Option<Result<&str, &str>> {
+        let (success, rest) = match line.as_bytes().first()? {
+            b'=' => (true, &line[1..]),
+            b'?' => (false, &line[1..]),
+            _ => return None,
+        };
+        let text = rest.strip_prefix(id.to_string().as_str())?;
+        if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+            // a longer id, such as 12 when waiting for 1
+            return None;
+        }
+        let text = text.trim();
+        Some(if success { Ok(text) } else { Err(text) })
+    }
+
+    struct Engine {
+        child: Child,
+        stdin: ChildStdin,
+        // the engine's output, line by line, from a reader thread
+        lines: Receiver<String>,
+        size: usize,
+        // the last `kata-analyze` report seen
+        latest: Option<String>,
+        // the id the next command is sent with
+        next_id: u32,
+    }
+    impl Engine {
+        fn spawn(command: &str, size: usize) -> Result<Engine, AnalysisError> {
+            let mut words = command.split_whitespace();
+            let program = words.next().ok_or(AnalysisError::NoCommand)?;
+            let mut child = Command::new(program)
+                .args(words)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?;
+            let stdin = child.stdin.take().unwrap();
+            let stdout = child.stdout.take().unwrap();
+            let (sender, lines): (Sender<String>, _) = mpsc::channel();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else { break };
+                    if sender.send(line.trim().to_string()).is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(Engine {
+                child,
+                stdin,
+                lines,
+                size,
+                latest: None,
+                next_id: 1,
+            })
+        }
+
+        /// Sends a command numbered so its answer can be told apart from
+        /// any other, and gives the number.
+        fn send(&mut self, command: &str) -> Result<u32, AnalysisError> {
+            let id = self.next_id;
+            self.next_id += 1;
+            writeln!(self.stdin, "{} {}", id, command)?;
+            self.stdin.flush()?;
+            Ok(id)
+        }
+
+        fn next_line(&mut self, timeout: Duration) -> Result<Option<String>, AnalysisError> {
+            match self.lines.recv_timeout(timeout) {
+                Ok(line) => {
+                    if line.starts_with("info") {
+                        self.latest = Some(line.clone());
+                    }
+                    Ok(Some(line))
+                }
+                Err(RecvTimeoutError::Timeout) => Ok(None),
+                Err(RecvTimeoutError::Disconnected) => Err(AnalysisError::Exited),
+            }
+        }
+
+        /// Sends a command and waits for its answer. Analysis reports and
+        /// answers to earlier commands still arriving are noted and passed over.
+        fn command(&mut self, command: &str) -> Result<String, AnalysisError> {
+            let id = self.send(command)?;
+            let mut answer: Option<String> = None;
+            loop {
+                let Some(line) = self.next_line(Duration::from_secs(60))? else {
+                    return Err(AnalysisError::Exited);
+                };
+                match &mut answer {
+                    // a blank line ends the answer
+                    Some(text) if line.is_empty() => return Ok(std::mem::take(text)),
+                    Some(text) => {
+                        text.push('\n');
+                        text.push_str(&line);
+                    }
+                    None => match answer_to(&line, id) {
+                        Some(Ok(text)) => answer = Some(text.to_string()),
+                        Some(Err(reason)) => {
+                            return Err(AnalysisError::Refused {
+                                command: command.to_string(),
+                                reason: reason.to_string(),
+                            })
+                        }
+                        None => {}
+                    },
+                }
+            }
+        }
+
+        /// Lets the engine think about the current position for `time`, and
+        /// gives its last report.
+        fn analyse(
+            &mut self,
+            color: PlayerColor,
+            time: Duration,
+            stop: &AtomicBool,
+        ) -> Result<Analysis, AnalysisError> {
+            self.latest = None;
+            self.send(&format!(
+                "kata-analyze {} {} ownership true",
+                letter(color),
+                REPORT_INTERVAL
+            ))?;
+            let started = Instant::now();
+            while started.elapsed() < time && !stop.load(Ordering::Relaxed) {
+                self.next_line(Duration::from_millis(50))?;
+            }
+            // any other command ends the analysis
+            self.command("protocol_version")?;
+
+            match self.latest.take() {
+                Some(line) => parse_kata_analyze(&line, self.size, color),
+                // it hadn't got as far as a report
+                None => Ok(Analysis {
+                    to_move: color,
+                    visits: 0,
+                    candidates: Vec::new(),
+                    ownership: None,
+                }),
+            }
+        }
+    }
+    impl Drop for Engine {
+        fn drop(&mut self) {
+            let _ = self.send("quit");
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    pub(super) fn analyse(
+        game: &Game,
+        command: &str,
+        time: Duration,
+        stop: &AtomicBool,
+        sender: &Sender<AnalysisEvent>,
+    ) -> Result<(), AnalysisError> {
+        let size = game.settings.size;
+        let mut engine = Engine::spawn(command, size)?;
+        engine.command(&format!("boardsize {}", size))?;
+        engine.command("clear_board")?;
+        engine.command(&format!("komi {}", game.settings.komi))?;
+        // handicap and other setup stones go down as moves; the colour to
+        // move is given with each analysis, so the order doesn't matter
+        for (point, color) in game.replayed(0).board().stones() {
+            let vertex = gtp_vertex(Move::Play(point), size);
+            engine.command(&format!("play {} {}", letter(color), vertex))?;
+        }
+
+        let moves = game.moves();
+        for n in 0..=last_position(game) {
+            if stop.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let to_move = moves.get(n).map_or(game.to_move(), |&(color, _)| color);
+            let analysis = engine.analyse(to_move, time, stop)?;
+            if sender.send(AnalysisEvent::Analysed(n, analysis)).is_err() {
+                return Ok(());
+            }
+            let Some(&(color, mv)) = moves.get(n) else {
+                break;
+            };
+            if mv == Move::Resign {
+                break;
+            }
+            engine.command(&format!("play {} {}", letter(color), gtp_vertex(mv, size)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_gtp_vertices() {
+        assert_eq!(parse_gtp_vertex("pass", 9), Some(Move::Pass));
+        assert_eq!(parse_gtp_vertex("PASS", 9), Some(Move::Pass));
+        assert_eq!(parse_gtp_vertex("A9", 9), Some(Move::Play((0, 0))));
+        assert_eq!(parse_gtp_vertex("q16", 19), Some(Move::Play((15, 3))));
+        // there's no I column, so J comes straight after H
+        assert_eq!(parse_gtp_vertex("H1", 9), Some(Move::Play((7, 8))));
+        assert_eq!(parse_gtp_vertex("J1", 9), Some(Move::Play((8, 8))));
+        assert_eq!(parse_gtp_vertex("I5", 9), None);
+        assert_eq!(parse_gtp_vertex("K5", 9), None);
+        assert_eq!(parse_gtp_vertex("A10", 9), None);
+        assert_eq!(parse_gtp_vertex("A0", 9), None);
+        for mv in [Move::Pass, Move::Play((8, 0)), Move::Play((3, 5))] {
+            assert_eq!(parse_gtp_vertex(&gtp_vertex(mv, 9), 9), Some(mv));
+        }
+    }
+
+    #[test]
+    fn reads_kata_analyze_reports() {
+        let line = "info move B2 visits 10 winrate 0.75 scoreLead 2.5 order 0 pv B2 A1 pass \
+            info move C3 visits 4 winrate 0.4 order 1 pv C3 \
+            ownership 1 0.5 0 0 0 0 0 -0.5 -1";
+        let analysis = parse_kata_analyze(line, 3, PlayerColor::White).unwrap();
+        assert_eq!(analysis.visits, 14);
+        assert_eq!(
+            analysis.candidates,
+            [
+                Candidate {
+                    mv: Move::Play((1, 1)),
+                    visits: 10,
+                    winrate: 0.75,
+                    score_lead: Some(2.5),
+                    pv: vec![Move::Play((1, 1)), Move::Play((0, 2)), Move::Pass],
+                },
+                Candidate {
+                    mv: Move::Play((2, 0)),
+                    visits: 4,
+                    winrate: 0.4,
+                    score_lead: None,
+                    pv: vec![Move::Play((2, 0))],
+                },
+            ]
+        );
+        // ownership is from white's side here, so it comes out the other way round
+        assert_eq!(
+            analysis.ownership,
+            Some(vec![-1.0, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.5, 1.0])
+        );
+        assert_eq!(analysis.black_winrate(), Some(0.25));
+        assert_eq!(analysis.black_score_lead(), Some(-2.5));
+
+        assert!(parse_kata_analyze("", 3, PlayerColor::Black).is_err());
+        assert!(parse_kata_analyze("info move Z9 visits 1", 3, PlayerColor::Black).is_err());
+        // too few ownership values
+        let short = "info move B2 visits 1 winrate 0.5 ownership 1 0";
+        assert!(parse_kata_analyze(short, 3, PlayerColor::Black).is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn matches_answers_to_their_command() {
+        assert_eq!(gtp::answer_to("=3 D4", 3), Some(Ok("D4")));
+        assert_eq!(gtp::answer_to("=3", 3), Some(Ok("")));
+        assert_eq!(
+            gtp::answer_to("?3 illegal move", 3),
+            Some(Err("illegal move"))
+        );
+        // answers to other commands, or to none, are someone else's
+        assert_eq!(gtp::answer_to("=2", 3), None);
+        assert_eq!(gtp::answer_to("=", 3), None);
+        assert_eq!(gtp::answer_to("= 2", 3), None);
+        assert_eq!(gtp::answer_to("=13 D4", 1), None);
+        assert_eq!(gtp::answer_to("info move D4 visits 1", 3), None);
+    }
+}
@@ -0,0 +1,537 @@
+//! A headless server that hosts many games at once. Clients connect with the
+//! `go` binary's Network panel, pick a table from the lobby and play or watch.
+//! The server keeps its own copy of every game, so moves and clocks are
+//! checked here rather than trusted from the clients.
+//!
+//! A player whose connection drops keeps their seat for a grace period and
+//! can resume with the session token they were given when the game started.
+//!
+//! Usage: `go-server [port] [--grace SECONDS] [--run-clocks]`, where port 0
+//! picks any free port.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use go::board::PlayerColor;
+use go::game::{Game, GameSettings, Move};
+use go::history::History;
+use go::net::{self, ChatLine, GameListing, Message, NetEvent, Peer, DEFAULT_PORT};
+
+// how long the loop sleeps when nothing is happening
+const IDLE: Duration = Duration::from_millis(10);
+const USAGE: &str = "usage: go-server [port] [--grace SECONDS] [--run-clocks]";
+
+/// What happens to a game while one of its players is disconnected.
+#[derive(Clone, Copy, Debug)]
+struct DisconnectPolicy {
+    // how long a seat is kept before the game is given up
+    grace: Duration,
+    // stop both clocks until the player is back, rather than letting theirs run
+    pause_clocks: bool,
+}
+impl Default for DisconnectPolicy {
+    fn default() -> Self {
+        DisconnectPolicy {
+            grace: Duration::from_secs(60),
+            pause_clocks: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Seat {
+    Player(PlayerColor),
+    Spectator,
+}
+
+struct Client {
+    peer: Peer,
+    // false until the handshake is done; the lobby is only sent to connected clients
+    connected: bool,
+    table: Option<(u32, Seat)>,
+}
+
+struct Table {
+    game: Game,
+    history: History,
+    // client ids, indexed by `PlayerColor::index`
+    players: [Option<usize>; 2],
+    // what each player sends in `Resume` to get their seat back
+    tokens: [String; 2],
+    // when each player's connection dropped, while the seat is being held for them
+    away: [Option<Instant>; 2],
+    spectators: Vec<usize>,
+    // who asked to take a move back and is waiting for an answer
+    undo_requested: Option<PlayerColor>,
+}
+impl Table {
+    fn started(&self) -> bool {
+        self.players.iter().all(Option::is_some)
+    }
+
+    fn seated(&self) -> impl Iterator<Item = usize> + '_ {
+        self.players
+            .iter()
+            .flatten()
+            .copied()
+            .chain(self.spectators.iter().copied())
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    clients: HashMap<usize, Client>,
+    tables: BTreeMap<u32, Table>,
+    next_client: usize,
+    next_table: u32,
+    policy: DisconnectPolicy,
+}
+impl Server {
+    fn send(&self, client: usize, message: Message) {
+        if let Some(client) = self.clients.get(&client) {
+            client.peer.send(message);
+        }
+    }
+
+    fn broadcast(&self, id: u32, message: Message) {
+        if let Some(table) = self.tables.get(&id) {
+            for client in table.seated() {
+                self.send(client, message.clone());
+            }
+        }
+    }
+
+    fn lobby(&self) -> Message {
+        let games = self
+            .tables
+            .iter()
+            .map(|(&id, table)| GameListing {
+                id,
+                settings: table.game.settings,
+                open: !table.started(),
+                moves: table.game.moves().len(),
+                spectators: table.spectators.len(),
+            })
+            .collect();
+        Message::Lobby { games }
+    }
+
+    fn broadcast_lobby(&self) {
+        let lobby = self.lobby();
+        for client in self.clients.values().filter(|client| client.connected) {
+            client.peer.send(lobby.clone());
+        }
+    }
+
+    fn poll(&mut self) -> bool {
+        let mut busy = false;
+        let ids = self.clients.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let events = self.clients[&id].peer.poll();
+            for event in events {
+                busy = true;
+                match event {
+                    NetEvent::Connected(address) => {
+                        println!("client {} connected from {}", id, address);
+                        self.clients.get_mut(&id).unwrap().connected = true;
+                        self.send(id, self.lobby());
+                    }
+                    NetEvent::Received(message) => {
+                        if let Err(reason) = self.handle(id, message) {
+                            self.send(id, Message::Rejected { reason });
+                        }
+                    }
+                    NetEvent::Disconnected(e) => {
+                        println!("client {} left: {}", id, e);
+                        self.drop_client(id);
+                        self.clients.remove(&id);
+                        break;
+                    }
+                }
+            }
+        }
+        busy
+    }
+
+    fn handle(&mut self, client: usize, message: Message) -> Result<(), String> {
+        match message {
+            Message::ListGames => self.send(client, self.lobby()),
+            Message::CreateGame { settings } => self.create(client, settings)?,
+            Message::JoinGame { id } => self.join(client, id)?,
+            Message::WatchGame { id } => self.watch(client, id)?,
+            Message::LeaveGame => self.leave(client),
+            Message::Resume { token } => self.resume(client, &token)?,
+            Message::Move { mv: Move::Resign } | Message::Resign { .. } => self.resign(client)?,
+            Message::Move { mv } => {
+                let (id, color) = self.player(client)?;
+                let table = self.tables.get_mut(&id).unwrap();
+                if !table.started() {
+                    return Err("Nobody has joined your game yet".to_string());
+                }
+                if table.game.to_move() != color {
+                    return Err("It isn't your turn".to_string());
+                }
+                let before = table.game.clone();
+                table.game.play(mv).map_err(|e| e.to_string())?;
+                table.history.record(before);
+                table.undo_requested = None;
+                let players = table.game.clock.players;
+                self.broadcast(id, Message::Move { mv });
+                self.broadcast(id, Message::Clock { players });
+            }
+            Message::UndoRequest => {
+                let (id, color) = self.player(client)?;
+                let table = self.tables.get_mut(&id).unwrap();
+                let settings = table.game.settings;
+                if !settings.undo_policy.allows(settings.rated) {
+                    return Err("Undo isn't allowed in this game".to_string());
+                }
+                if table.game.moves().is_empty() {
+                    return Err("There's nothing to undo".to_string());
+                }
+                table.undo_requested = Some(color);
+                if let Some(opponent) = table.players[color.opponent().index()] {
+                    self.send(opponent, Message::UndoRequest);
+                }
+            }
+            Message::UndoReply { accepted } => {
+                let (id, color) = self.player(client)?;
+                let table = self.tables.get_mut(&id).unwrap();
+                if table.undo_requested != Some(color.opponent()) {
+                    return Err("Nobody asked for an undo".to_string());
+                }
+                table.undo_requested = None;
+                if accepted {
+                    table
+                        .history
+                        .undo(&mut table.game)
+                        .map_err(|e| e.to_string())?;
+                }
+                // the answering player has already acted on it
+                let table = &self.tables[&id];
+                for other in table.seated().filter(|&other| other != client) {
+                    self.send(other, Message::UndoReply { accepted });
+                }
+            }
+            Message::Chat(line) => {
+                let Some((id, seat)) = self.clients[&client].table else {
+                    return Err("You aren't in a game".to_string());
+                };
+                // the server says who spoke and when, whatever the client claimed
+                let line = ChatLine {
+                    from: match seat {
+                        Seat::Player(color) => color.name().to_string(),
+                        Seat::Spectator => "Spectator".to_string(),
+                    },
+                    move_number: self.tables[&id].game.moves().len(),
+                    ..line
+                };
+                self.broadcast(id, Message::Chat(line));
+            }
+            _ => return Err("The server doesn't expect that message".to_string()),
+        }
+        Ok(())
+    }
+
+    // players can resign on either turn, but only for themselves
+    fn resign(&mut self, client: usize) -> Result<(), String> {
+        let (id, color) = self.player(client)?;
+        let table = self.tables.get_mut(&id).unwrap();
+        if !table.started() {
+            return Err("Nobody has joined your game yet".to_string());
+        }
+        let before = table.game.clone();
+        table.game.resign(color).map_err(|e| e.to_string())?;
+        table.history.record(before);
+        table.undo_requested = None;
+        self.broadcast(id, Message::Resign { color });
+        Ok(())
+    }
+
+    // the table and colour of a client that is playing
+    fn player(&self, client: usize) -> Result<(u32, PlayerColor), String> {
+        match self.clients[&client].table {
+            Some((id, Seat::Player(color))) => Ok((id, color)),
+            Some((_, Seat::Spectator)) => Err("Spectators can't play".to_string()),
+            None => Err("You aren't in a game".to_string()),
+        }
+    }
+
+    fn create(&mut self, client: usize, settings: GameSettings) -> Result<(), String> {
+        self.leave(client);
+        let id = self.next_table;
+        self.next_table += 1;
+        // whoever opens the table plays black
+        let mut players = [None; 2];
+        players[PlayerColor::Black.index()] = Some(client);
+        self.tables.insert(
+            id,
+            Table {
+                game: Game::new(settings),
+                history: History::default(),
+                players,
+                tokens: Default::default(),
+                away: [None; 2],
+                spectators: Vec::new(),
+                undo_requested: None,
+            },
+        );
+        self.clients.get_mut(&client).unwrap().table = Some((id, Seat::Player(PlayerColor::Black)));
+        println!("client {} opened game {}", client, id);
+        self.send(client, Message::Created { id });
+        self.broadcast_lobby();
+        Ok(())
+    }
+
+    fn join(&mut self, client: usize, id: u32) -> Result<(), String> {
+        let table = self.tables.get(&id).ok_or("There's no such game")?;
+        if table.started() {
+            return Err("That game already has two players".to_string());
+        }
+        self.leave(client);
+        let table = self.tables.get_mut(&id).unwrap();
+        let color = PlayerColor::White;
+        table.players[color.index()] = Some(client);
+        // the clocks start now rather than when the table was opened
+        table.game = Game::new(table.game.settings);
+        table.history = History::default();
+        for color in [PlayerColor::Black, PlayerColor::White] {
+            table.tokens[color.index()] = format!("{:016x}", rand::random::<u64>());
+        }
+        self.clients.get_mut(&client).unwrap().table = Some((id, Seat::Player(color)));
+        println!("client {} joined game {}", client, id);
+
+        let table = &self.tables[&id];
+        let settings = table.game.settings;
+        for color in [PlayerColor::Black, PlayerColor::White] {
+            let player = table.players[color.index()].unwrap();
+            self.send(
+                player,
+                Message::NewGame {
+                    settings,
+                    color: Some(color),
+                },
+            );
+            self.send(
+                player,
+                Message::Session {
+                    token: table.tokens[color.index()].clone(),
+                    grace_secs: self.policy.grace.as_secs(),
+                    pause_clocks: self.policy.pause_clocks,
+                },
+            );
+        }
+        self.broadcast_lobby();
+        Ok(())
+    }
+
+    fn watch(&mut self, client: usize, id: u32) -> Result<(), String> {
+        if !self.tables.contains_key(&id) {
+            return Err("There's no such game".to_string());
+        }
+        self.leave(client);
+        let table = self.tables.get_mut(&id).unwrap();
+        table.spectators.push(client);
+        self.clients.get_mut(&client).unwrap().table = Some((id, Seat::Spectator));
+
+        // the newcomer catches up from the moves so far and the clocks as they are now
+        self.send(client, Message::snapshot(&self.tables[&id].game, None));
+        self.broadcast_lobby();
+        Ok(())
+    }
+
+    // a player leaving closes the table; a spectator leaving doesn't
+    fn leave(&mut self, client: usize) {
+        let Some((id, seat)) = self.clients.get_mut(&client).and_then(|c| c.table.take()) else {
+            return;
+        };
+        match seat {
+            Seat::Spectator => {
+                if let Some(table) = self.tables.get_mut(&id) {
+                    table.spectators.retain(|&other| other != client);
+                }
+            }
+            Seat::Player(color) => self.close(id, color),
+        }
+        self.broadcast_lobby();
+    }
+
+    // ends the game at table `id` because `color` is gone for good
+    fn close(&mut self, id: u32, color: PlayerColor) {
+        let Some(table) = self.tables.remove(&id) else {
+            return;
+        };
+        println!("game {} closed", id);
+        let leaver = table.players[color.index()];
+        for other in table.seated().filter(|&other| Some(other) != leaver) {
+            self.send(other, Message::PlayerLeft { color });
+            if let Some(other) = self.clients.get_mut(&other) {
+                other.table = None;
+            }
+        }
+    }
+
+    // a lost connection holds a player's seat in a game still being played
+    fn drop_client(&mut self, client: usize) {
+        let Some((id, Seat::Player(color))) = self.clients[&client].table else {
+            self.leave(client);
+            return;
+        };
+        let table = self.tables.get_mut(&id).unwrap();
+        if !table.started() || table.game.is_over() {
+            self.leave(client);
+            return;
+        }
+        table.away[color.index()] = Some(Instant::now());
+        println!("holding {}'s seat in game {}", color.name(), id);
+        self.broadcast(
+            id,
+            Message::PlayerAway {
+                color,
+                grace_secs: self.policy.grace.as_secs(),
+                pause_clocks: self.policy.pause_clocks,
+            },
+        );
+    }
+
+    fn resume(&mut self, client: usize, token: &str) -> Result<(), String> {
+        let (id, color) = self
+            .tables
+            .iter()
+            .find_map(|(&id, table)| {
+                [PlayerColor::Black, PlayerColor::White]
+                    .into_iter()
+                    .find(|color| {
+                        table.away[color.index()].is_some() && table.tokens[color.index()] == token
+                    })
+                    .map(|color| (id, color))
+            })
+            .ok_or("That game is no longer waiting for you")?;
+        self.leave(client);
+        let table = self.tables.get_mut(&id).unwrap();
+        table.players[color.index()] = Some(client);
+        table.away[color.index()] = None;
+        self.clients.get_mut(&client).unwrap().table = Some((id, Seat::Player(color)));
+        println!(
+            "client {} is back as {} in game {}",
+            client,
+            color.name(),
+            id
+        );
+
+        // the snapshot carries every move made while they were gone
+        let game = &self.tables[&id].game;
+        let players = game.clock.players;
+        self.send(client, Message::snapshot(game, Some(color)));
+        self.broadcast(id, Message::PlayerBack { color });
+        self.broadcast(id, Message::Clock { players });
+        Ok(())
+    }
+
+    // charges thinking time in every running game and tells the table when someone
+    // flags; also gives up on players who stayed away too long
+    fn tick(&mut self, elapsed: Duration) {
+        let mut abandoned = Vec::new();
+        let mut flagged = Vec::new();
+        for (&id, table) in self.tables.iter_mut() {
+            for color in [PlayerColor::Black, PlayerColor::White] {
+                if table.away[color.index()]
+                    .is_some_and(|since| since.elapsed() > self.policy.grace)
+                {
+                    abandoned.push((id, color));
+                }
+            }
+            let paused = self.policy.pause_clocks && table.away.iter().any(Option::is_some);
+            if !table.started() || table.game.is_over() || paused {
+                continue;
+            }
+            table.game.tick(elapsed);
+            if table.game.is_over() {
+                flagged.push((id, table.game.clock.players));
+            }
+        }
+        for (id, color) in abandoned {
+            println!("{} didn't come back to game {}", color.name(), id);
+            self.close(id, color);
+            self.broadcast_lobby();
+        }
+        for (id, players) in flagged {
+            println!("game {} ended on time", id);
+            self.broadcast(id, Message::Clock { players });
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn main() {
+    env_logger::init();
+    let mut port = DEFAULT_PORT;
+    let mut policy = DisconnectPolicy::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--grace" => match args.next().and_then(|secs| secs.parse().ok()) {
+                Some(secs) => policy.grace = Duration::from_secs(secs),
+                None => usage(),
+            },
+            "--run-clocks" => policy.pause_clocks = false,
+            _ => match arg.parse() {
+                Ok(p) => port = p,
+                Err(_) => usage(),
+            },
+        }
+    }
+    let listener = match net::listen(port) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("can't listen on port {}: {}", port, e);
+            std::process::exit(1);
+        }
+    };
+    // port 0 asks for any free port, so say which one it got
+    let port = listener.local_addr().map_or(port, |address| address.port());
+    println!("listening on port {}", port);
+
+    let mut server = Server {
+        policy,
+        ..Server::default()
+    };
+    let mut last = Instant::now();
+    loop {
+        let mut busy = false;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let id = server.next_client;
+                    server.next_client += 1;
+                    server.clients.insert(
+                        id,
+                        Client {
+                            peer: Peer::accept(stream),
+                            connected: false,
+                            table: None,
+                        },
+                    );
+                    busy = true;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("accept failed: {}", e);
+                    break;
+                }
+            }
+        }
+        busy |= server.poll();
+
+        let now = Instant::now();
+        server.tick(now - last);
+        last = now;
+        if !busy {
+            std::thread::sleep(IDLE);
+        }
+    }
+}
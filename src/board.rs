@@ -1,12 +1,464 @@
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum PlayerColor {
+    Black,
+    White,
+}
+impl PlayerColor {
+    pub fn opponent(self) -> Self {
+        match self {
+            PlayerColor::Black => PlayerColor::White,
+            PlayerColor::White => PlayerColor::Black,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            PlayerColor::Black => 0,
+            PlayerColor::White => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlayerColor::Black => "Black",
+            PlayerColor::White => "White",
+        }
+    }
+}
+
+pub type Point = (usize, usize);
+
+// columns skip I, following the usual go convention
+pub const COLUMN_LETTERS: &[u8] = b"ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+/// Human-readable name of a point, e.g. "Q16". Rows count up from the bottom edge.
+pub fn point_name((x, y): Point, size: usize) -> String {
+    format!("{}{}", COLUMN_LETTERS[x] as char, size - y)
+}
+
+/// A connected chain of same-coloured stones and the empty points touching it.
+pub struct Group {
+    pub color: PlayerColor,
+    pub stones: Vec<Point>,
+    pub liberties: Vec<Point>,
+}
+
+/// Stone positions only; whose turn it is, ko and captures live on `Game`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Board {
-    pipeline: wgpu::Pipeline,
-    vertex_buffer: wgpu::Buffer,
+    size: usize,
+    points: Vec<Option<PlayerColor>>,
+}
+impl Board {
+    pub fn new(size: usize) -> Self {
+        Board {
+            size,
+            points: vec![None; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn contains(&self, (x, y): Point) -> bool {
+        x < self.size && y < self.size
+    }
+
+    pub fn get(&self, (x, y): Point) -> Option<PlayerColor> {
+        self.points[y * self.size + x]
+    }
+
+    pub fn set(&mut self, (x, y): Point, stone: Option<PlayerColor>) {
+        self.points[y * self.size + x] = stone;
+    }
+
+    pub fn stones(&self) -> impl Iterator<Item = (Point, PlayerColor)> + '_ {
+        self.points
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.map(|c| ((i % self.size, i / self.size), c)))
+    }
+
+    pub fn neighbours(&self, (x, y): Point) -> impl Iterator<Item = Point> {
+        let size = self.size;
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(move |&(nx, ny)| nx < size && ny < size)
+    }
+
+    /// The group containing the stone at `point`, or `None` if it is empty.
+    pub fn group(&self, point: Point) -> Option<Group> {
+        let color = self.get(point)?;
+        let mut seen = vec![false; self.size * self.size];
+        let mut liberty_seen = vec![false; self.size * self.size];
+        let mut stones = Vec::new();
+        let mut liberties = Vec::new();
+        let mut stack = vec![point];
+        seen[point.1 * self.size + point.0] = true;
+
+        while let Some(p) = stack.pop() {
+            stones.push(p);
+            for n in self.neighbours(p) {
+                let i = n.1 * self.size + n.0;
+                match self.get(n) {
+                    Some(c) if c == color && !seen[i] => {
+                        seen[i] = true;
+                        stack.push(n);
+                    }
+                    None if !liberty_seen[i] => {
+                        liberty_seen[i] = true;
+                        liberties.push(n);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Some(Group {
+            color,
+            stones,
+            liberties,
+        })
+    }
+
+    /// Every group on the board, each once.
+    pub fn groups(&self) -> Vec<Group> {
+        let mut seen = vec![false; self.size * self.size];
+        let mut groups = Vec::new();
+        for ((x, y), _) in self.stones() {
+            if seen[y * self.size + x] {
+                continue;
+            }
+            let group = self.group((x, y)).unwrap();
+            for &(sx, sy) in &group.stones {
+                seen[sy * self.size + sx] = true;
+            }
+            groups.push(group);
+        }
+        groups
+    }
+
+    /// Removes every stone of the group at `point` and returns how many were taken.
+    pub fn remove_group(&mut self, point: Point) -> usize {
+        match self.group(point) {
+            Some(group) => {
+                for &p in &group.stones {
+                    self.set(p, None);
+                }
+                group.stones.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// Empty regions and the single colour that surrounds each of them, if any.
+    pub fn territory(&self) -> Vec<(Vec<Point>, Option<PlayerColor>)> {
+        let mut seen = vec![false; self.size * self.size];
+        let mut regions = Vec::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if seen[y * self.size + x] || self.get((x, y)).is_some() {
+                    continue;
+                }
+                let mut region = Vec::new();
+                let mut borders = [false; 2];
+                let mut stack = vec![(x, y)];
+                seen[y * self.size + x] = true;
+                while let Some(p) = stack.pop() {
+                    region.push(p);
+                    for n in self.neighbours(p) {
+                        match self.get(n) {
+                            Some(c) => borders[c.index()] = true,
+                            None if !seen[n.1 * self.size + n.0] => {
+                                seen[n.1 * self.size + n.0] = true;
+                                stack.push(n);
+                            }
+                            None => {}
+                        }
+                    }
+                }
+                let owner = match borders {
+                    [true, false] => Some(PlayerColor::Black),
+                    [false, true] => Some(PlayerColor::White),
+                    _ => None,
+                };
+                regions.push((region, owner));
+            }
+        }
+        regions
+    }
 }
+
+/// One of the 8 ways a square board maps onto itself: the axes swapped or
+/// not, and then each of them mirrored or not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Symmetry {
+    pub transpose: bool,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+impl Symmetry {
+    pub const IDENTITY: Symmetry = Symmetry {
+        transpose: false,
+        flip_x: false,
+        flip_y: false,
+    };
+    /// A quarter turn clockwise, with rows running down the screen.
+    pub const ROTATE: Symmetry = Symmetry {
+        transpose: true,
+        flip_x: true,
+        flip_y: false,
+    };
+    /// Left and right swapped.
+    pub const MIRROR: Symmetry = Symmetry {
+        transpose: false,
+        flip_x: true,
+        flip_y: false,
+    };
+
+    /// All 8, the identity first.
+    pub fn all() -> impl Iterator<Item = Symmetry> {
+        (0..8).map(|i| Symmetry {
+            transpose: i & 4 != 0,
+            flip_x: i & 2 != 0,
+            flip_y: i & 1 != 0,
+        })
+    }
+
+    pub fn apply(self, (x, y): Point, size: usize) -> Point {
+        let (x, y) = if self.transpose { (y, x) } else { (x, y) };
+        (
+            if self.flip_x { size - 1 - x } else { x },
+            if self.flip_y { size - 1 - y } else { y },
+        )
+    }
+
+    /// Where a step from one point to another points afterwards.
+    pub fn apply_offset(self, (dx, dy): (i32, i32)) -> (i32, i32) {
+        let (dx, dy) = if self.transpose { (dy, dx) } else { (dx, dy) };
+        (
+            if self.flip_x { -dx } else { dx },
+            if self.flip_y { -dy } else { dy },
+        )
+    }
+
+    /// `self` and then `next`.
+    pub fn then(self, next: Symmetry) -> Symmetry {
+        // swapping the axes afterwards swaps which of them were mirrored
+        let (flip_x, flip_y) = if next.transpose {
+            (self.flip_y, self.flip_x)
+        } else {
+            (self.flip_x, self.flip_y)
+        };
+        Symmetry {
+            transpose: self.transpose != next.transpose,
+            flip_x: flip_x != next.flip_x,
+            flip_y: flip_y != next.flip_y,
+        }
+    }
+
+    pub fn inverse(self) -> Symmetry {
+        // undoing the mirrors first means mirroring the axes they end up on
+        if self.transpose {
+            Symmetry {
+                transpose: true,
+                flip_x: self.flip_y,
+                flip_y: self.flip_x,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// A symmetry of the board, maybe with Black and White swapped too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Transform {
+    pub symmetry: Symmetry,
+    pub swap_colors: bool,
+}
+impl Transform {
+    pub fn apply_color(self, color: PlayerColor) -> PlayerColor {
+        if self.swap_colors {
+            color.opponent()
+        } else {
+            color
+        }
+    }
+
+    pub fn inverse(self) -> Transform {
+        Transform {
+            symmetry: self.symmetry.inverse(),
+            swap_colors: self.swap_colors,
+        }
+    }
+}
+
 impl Board {
-    pub fn new(device: &wgpu::Device, layout: &wgpu::PipelineLayout) -> Self {
-        &wgpu::RenderPassDescriptor {
-            label: Some("board pipeline"),
-            layout: Some(&layout),
+    pub fn transformed(&self, transform: Transform) -> Board {
+        let mut board = Board::new(self.size);
+        for (point, color) in self.stones() {
+            board.set(
+                transform.symmetry.apply(point, self.size),
+                Some(transform.apply_color(color)),
+            );
+        }
+        board
+    }
+
+    /// The first of the board's images in a fixed order, the same for every
+    /// position that differs from this one only by symmetry (and by colour,
+    /// with `swap_colors`), and the transform that gives it.
+    pub fn normalized(&self, swap_colors: bool) -> (Board, Transform) {
+        let colors: &[bool] = if swap_colors {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        colors
+            .iter()
+            .flat_map(|&swap_colors| {
+                Symmetry::all().map(move |symmetry| Transform {
+                    symmetry,
+                    swap_colors,
+                })
+            })
+            .map(|transform| (self.transformed(transform), transform))
+            .min_by(|(a, _), (b, _)| a.points.cmp(&b.points))
+            .unwrap()
+    }
+
+    /// A transform that turns this position into `other`, if there is one.
+    pub fn symmetry_to(&self, other: &Board, swap_colors: bool) -> Option<Transform> {
+        if self.size != other.size {
+            return None;
+        }
+        let colors: &[bool] = if swap_colors {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        colors.iter().find_map(|&swap_colors| {
+            Symmetry::all()
+                .map(|symmetry| Transform {
+                    symmetry,
+                    swap_colors,
+                })
+                .find(|&transform| self.transformed(transform) == *other)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // a position with no symmetry of its own, so each image of it is different
+    fn lopsided() -> Board {
+        let mut board = Board::new(9);
+        board.set((2, 2), Some(PlayerColor::Black));
+        board.set((3, 2), Some(PlayerColor::Black));
+        board.set((2, 6), Some(PlayerColor::White));
+        board.set((7, 1), Some(PlayerColor::White));
+        board
+    }
+
+    fn transforms() -> impl Iterator<Item = Transform> {
+        [false, true].into_iter().flat_map(|swap_colors| {
+            Symmetry::all().map(move |symmetry| Transform {
+                symmetry,
+                swap_colors,
+            })
+        })
+    }
+
+    #[test]
+    fn symmetries_compose_and_invert() {
+        let size = 5;
+        let points = (0..size * size).map(|i| (i % size, i / size));
+        assert_eq!(Symmetry::all().next(), Some(Symmetry::IDENTITY));
+        assert_eq!(Symmetry::all().collect::<HashSet<_>>().len(), 8);
+        for a in Symmetry::all() {
+            assert_eq!(a.then(a.inverse()), Symmetry::IDENTITY, "{:?}", a);
+            assert_eq!(a.inverse().then(a), Symmetry::IDENTITY, "{:?}", a);
+            for b in Symmetry::all() {
+                for point in points.clone() {
+                    assert_eq!(
+                        a.then(b).apply(point, size),
+                        b.apply(a.apply(point, size), size),
+                        "{:?} then {:?}",
+                        a,
+                        b
+                    );
+                }
+            }
+            for point in points.clone() {
+                assert_eq!(a.inverse().apply(a.apply(point, size), size), point);
+            }
+            // a step between two points turns with them
+            let (x, y) = a.apply((1, 3), size);
+            let (to_x, to_y) = a.apply((2, 1), size);
+            let step = (to_x as i32 - x as i32, to_y as i32 - y as i32);
+            assert_eq!(a.apply_offset((1, -2)), step, "{:?}", a);
+        }
+
+        assert_eq!(Symmetry::ROTATE.apply((0, 0), size), (size - 1, 0));
+        let turned = (0..4).fold(Symmetry::IDENTITY, |turned, _| {
+            turned.then(Symmetry::ROTATE)
+        });
+        assert_eq!(turned, Symmetry::IDENTITY);
+        assert_eq!(Symmetry::MIRROR.then(Symmetry::MIRROR), Symmetry::IDENTITY);
+    }
+
+    #[test]
+    fn every_image_normalizes_alike() {
+        let board = lopsided();
+        let (normal, transform) = board.normalized(false);
+        assert_eq!(board.transformed(transform), normal);
+        for symmetry in Symmetry::all() {
+            let image = board.transformed(Transform {
+                symmetry,
+                swap_colors: false,
+            });
+            assert_eq!(image.normalized(false).0, normal, "{:?}", symmetry);
+        }
+
+        let (normal, _) = board.normalized(true);
+        for transform in transforms() {
+            let (image_normal, image_transform) = board.transformed(transform).normalized(true);
+            assert_eq!(image_normal, normal, "{:?}", transform);
+            assert_eq!(
+                board.transformed(transform).transformed(image_transform),
+                normal
+            );
+        }
+    }
+
+    #[test]
+    fn finds_the_symmetry_between_two_boards() {
+        let board = lopsided();
+        for transform in transforms() {
+            let image = board.transformed(transform);
+            assert_eq!(board.symmetry_to(&image, true), Some(transform));
+            let back = image.symmetry_to(&board, true);
+            assert_eq!(back, Some(transform.inverse()), "{:?}", transform);
+            if transform.swap_colors {
+                assert_eq!(board.symmetry_to(&image, false), None);
+            }
         }
+        let mut moved = board.clone();
+        moved.set((7, 1), None);
+        moved.set((7, 2), Some(PlayerColor::White));
+        assert_eq!(board.symmetry_to(&moved, true), None);
+        assert_eq!(board.symmetry_to(&Board::new(13), true), None);
     }
 }
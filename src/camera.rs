@@ -1,3 +1,5 @@
+use cgmath::EuclideanSpace;
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -6,34 +8,60 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+/// Zoom and pan of the board view. Board positions are in the clip space of
+/// the board viewport, where the whole board spans -1..1 at zoom 1.
 pub struct Camera {
-    pub eye: cgmath::Point3<f32>,
-    pub target: cgmath::Point3<f32>,
-    pub up: cgmath::Vector3<f32>,
-    pub aspect: f32,
-    pub fovy: f32,
-    pub znear: f32,
-    pub zfar: f32,
+    pub zoom: f32,
+    // the board position shown in the middle of the viewport
+    pub center: cgmath::Point2<f32>,
 }
 
-impl Camera {
+impl Default for Camera {
     fn default() -> Self {
-        #[rustfmt::skip]
-        return Camera {
-            eye: cgmath::Point3 { x: 0.0, y: 0.0, z: 0.0, },
-            target: cgmath::Point3 { x: 0.0, y: 0.0, z: 0.0, },
-            up: cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0, },
-            aspect: 0.0,
-            fovy: 0.0,
-            znear: 0.0,
-            zfar: 0.0,
-        };
+        Camera {
+            zoom: 1.0,
+            center: cgmath::Point2::new(0.0, 0.0),
+        }
     }
+}
+
+impl Camera {
+    pub const MIN_ZOOM: f32 = 1.0;
+    pub const MAX_ZOOM: f32 = 5.0;
 
     pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        proj * view
+        cgmath::Matrix4::from_nonuniform_scale(self.zoom, self.zoom, 1.0)
+            * cgmath::Matrix4::from_translation(cgmath::Vector3::new(
+                -self.center.x,
+                -self.center.y,
+                0.0,
+            ))
+    }
+
+    /// The board position under `screen`, a clip-space point in the viewport.
+    pub fn to_board(&self, screen: cgmath::Point2<f32>) -> cgmath::Point2<f32> {
+        self.center + screen.to_vec() / self.zoom
+    }
+
+    /// Zooms by `factor` while keeping the board position under `screen` where it is.
+    pub fn zoom_about(&mut self, screen: cgmath::Point2<f32>, factor: f32) {
+        let anchor = self.to_board(screen);
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.center = anchor - screen.to_vec() / self.zoom;
+        self.clamp_center();
+    }
+
+    /// Moves the board by `delta`, in viewport clip space.
+    pub fn pan(&mut self, delta: cgmath::Vector2<f32>) {
+        self.center -= delta / self.zoom;
+        self.clamp_center();
+    }
+
+    // never show anything past the edge of the board
+    fn clamp_center(&mut self) {
+        let limit = 1.0 - 1.0 / self.zoom;
+        self.center.x = self.center.x.clamp(-limit, limit);
+        self.center.y = self.center.y.clamp(-limit, limit);
     }
 }
 
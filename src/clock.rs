@@ -1,13 +1,97 @@
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct ClockBuffer {
-    ms: f32,
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TimeControl {
+    Unlimited,
+    ByoYomi {
+        main_time: std::time::Duration,
+        periods: u32,
+        period_time: std::time::Duration,
+    },
 }
-impl ClockBuffer {
-    pub fn new() -> ClockBuffer {
-        ClockBuffer { ms: 0.0 }
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PlayerClock {
+    pub main_time: std::time::Duration,
+    pub periods: u32,
+    // what is left of the current byo-yomi period
+    pub period_time: std::time::Duration,
+}
+
+/// Both players' remaining time. The clock doesn't measure time itself, the
+/// caller charges elapsed time to whoever is thinking with `spend`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameClock {
+    pub time_control: TimeControl,
+    pub players: [PlayerClock; 2],
+}
+impl GameClock {
+    pub fn new(time_control: TimeControl) -> GameClock {
+        let player = match time_control {
+            TimeControl::Unlimited => PlayerClock {
+                main_time: std::time::Duration::ZERO,
+                periods: 0,
+                period_time: std::time::Duration::ZERO,
+            },
+            TimeControl::ByoYomi {
+                main_time,
+                periods,
+                period_time,
+            } => PlayerClock {
+                main_time,
+                periods,
+                period_time,
+            },
+        };
+        GameClock {
+            time_control,
+            players: [player; 2],
+        }
+    }
+
+    /// Charges `elapsed` to player `index`. Returns false once they run out of time.
+    pub fn spend(&mut self, index: usize, elapsed: std::time::Duration) -> bool {
+        let TimeControl::ByoYomi { period_time, .. } = self.time_control else {
+            return true;
+        };
+        let clock = &mut self.players[index];
+        let mut left = elapsed;
+        if clock.main_time >= left {
+            clock.main_time -= left;
+            return true;
+        }
+        left -= clock.main_time;
+        clock.main_time = std::time::Duration::ZERO;
+        while clock.periods > 0 {
+            if clock.period_time > left {
+                clock.period_time -= left;
+                return true;
+            }
+            // a period that runs out is lost and the next one starts fresh
+            left -= clock.period_time;
+            clock.periods -= 1;
+            clock.period_time = period_time;
+        }
+        false
+    }
+
+    /// A move made inside byo-yomi resets the current period.
+    pub fn end_turn(&mut self, index: usize) {
+        if let TimeControl::ByoYomi { period_time, .. } = self.time_control {
+            self.players[index].period_time = period_time;
+        }
     }
-    pub fn update(&mut self, delta: f32) {
-        self.ms += delta;
+
+    pub fn display(&self, index: usize) -> String {
+        let clock = &self.players[index];
+        let format = |d: std::time::Duration| {
+            let secs = d.as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        };
+        match self.time_control {
+            TimeControl::Unlimited => "-".to_string(),
+            TimeControl::ByoYomi { .. } if !clock.main_time.is_zero() => format(clock.main_time),
+            TimeControl::ByoYomi { .. } => {
+                format!("{} ({})", format(clock.period_time), clock.periods)
+            }
+        }
     }
 }
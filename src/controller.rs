@@ -1,59 +1,43 @@
 use winit::{
     event::{ElementState, KeyEvent, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    keyboard::{ModifiersState, PhysicalKey},
 };
 
+use crate::input::{Action, InputMap};
+
+/// Turns key presses into `Action`s through the input map. `App::update`
+/// takes the queued actions once per frame.
 #[derive(Default)]
 pub struct Controller {
-    pub velocity: f32,
-    pub is_up_pressed: bool,
-    pub is_down_pressed: bool,
-    pub is_left_pressed: bool,
-    pub is_right_pressed: bool,
+    pub input_map: InputMap,
+    pub modifiers: ModifiersState,
+    pub actions: Vec<Action>,
 }
 impl Controller {
-    pub fn new(velocity: f32) -> Self {
-        Self {
-            velocity,
-            is_up_pressed: false,
-            is_down_pressed: false,
-            is_left_pressed: false,
-            is_right_pressed: false,
-        }
-    }
-
     pub fn process_events(&mut self, event: &WindowEvent) -> bool {
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                true
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
-                        state,
+                        state: ElementState::Pressed,
                         physical_key: PhysicalKey::Code(keycode),
+                        repeat,
                         ..
                     },
                 ..
-            } => {
-                let is_pressed = *state == ElementState::Pressed;
-                match keycode {
-                    KeyCode::KeyW | KeyCode::ArrowUp => {
-                        self.is_up_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyA | KeyCode::ArrowLeft => {
-                        self.is_left_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyS | KeyCode::ArrowDown => {
-                        self.is_down_pressed = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyD | KeyCode::ArrowRight => {
-                        self.is_right_pressed = is_pressed;
-                        true
-                    }
-                    _ => false,
+            } => match self.input_map.action(*keycode, self.modifiers) {
+                // a held key only keeps firing for actions that step through something
+                Some(action) if !*repeat || action.repeats() => {
+                    self.actions.push(action);
+                    true
                 }
-            }
+                Some(_) => true,
+                None => false,
+            },
             _ => false,
         }
     }
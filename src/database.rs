@@ -0,0 +1,587 @@
+//! A local database of game records. Every SGF file under a folder is read
+//! once into an index file, with who played, the result, the moves and a
+//! hash of every position, and the index is then searched for a pattern in
+//! part of the board, in any of the board's symmetries, counting what was
+//! played next and how those games ended.
+//!
+//! The hashes only find whole positions. A pattern in part of the board is
+//! looked for by replaying each game, skipping the games that never had a
+//! stone of the right colour on every point the pattern has one.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use crate::board::{Board, PlayerColor, Point, Symmetry, Transform};
+use crate::game::{Game, Move};
+use crate::sgf;
+
+// bumped whenever the index or its hashes change, so an old index is rebuilt
+const INDEX_VERSION: u32 = 3;
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(std::io::Error),
+    Index(serde_json::Error),
+    // the index was written by another version and has to be built again
+    Version(u32),
+}
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::Io(e) => write!(f, "{}", e),
+            DatabaseError::Index(e) => write!(f, "unreadable index: {}", e),
+            DatabaseError::Version(version) => write!(
+                f,
+                "the index is version {} and this is version {}; index the games again",
+                version, INDEX_VERSION
+            ),
+        }
+    }
+}
+impl std::error::Error for DatabaseError {}
+impl From<std::io::Error> for DatabaseError {
+    fn from(e: std::io::Error) -> Self {
+        DatabaseError::Io(e)
+    }
+}
+impl From<serde_json::Error> for DatabaseError {
+    fn from(e: serde_json::Error) -> Self {
+        DatabaseError::Index(e)
+    }
+}
+
+// the same numbers for every run, since hashes are kept in the index
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A Zobrist hash of the stones on the board.
+pub fn position_hash(board: &Board) -> u64 {
+    let size = board.size();
+    board
+        .stones()
+        .fold(splitmix64(!(size as u64)), |hash, ((x, y), color)| {
+            hash ^ splitmix64(((y * size + x) * 2 + color.index()) as u64)
+        })
+}
+
+/// One game in the index.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct GameEntry {
+    pub path: String,
+    // which game in the file, for files holding more than one
+    pub tree: usize,
+    pub black: String,
+    pub white: String,
+    pub result: String,
+    pub date: String,
+    pub event: String,
+    pub size: usize,
+    setup: Vec<(Point, PlayerColor)>,
+    // "B" or "W" and the point in SGF letters for every move, "--" for a pass
+    moves: String,
+    // the position before the first move and after each one
+    hashes: Vec<u64>,
+    // the points each colour ever had a stone on, a bit per point, indexed by
+    // `PlayerColor::index`
+    played: [Vec<u64>; 2],
+}
+impl GameEntry {
+    fn new(path: &str, tree: usize, record: &sgf::GameTree) -> Result<GameEntry, sgf::SgfError> {
+        let game = Game::from_sgf(record)?;
+        let root = &record.nodes[0];
+        let text = |ident| root.first(ident).unwrap_or("").to_string();
+
+        let size = game.settings.size;
+        let words = (size * size).div_ceil(64);
+        let mut played = [vec![0; words], vec![0; words]];
+        let mut mark = |(x, y): Point, color: PlayerColor| {
+            let bit = y * size + x;
+            played[color.index()][bit / 64] |= 1 << (bit % 64);
+        };
+        for &(point, color) in game.setup() {
+            mark(point, color);
+        }
+
+        let mut replay = game.replayed(0);
+        let mut hashes = vec![position_hash(replay.board())];
+        let mut moves = String::new();
+        for &(color, mv) in game.moves() {
+            let point = match mv {
+                Move::Play(point) => {
+                    mark(point, color);
+                    sgf::encode_point(point)
+                }
+                // "tt" would be a point on boards over 19x19
+                Move::Pass => "--".to_string(),
+                Move::Resign => break,
+            };
+            replay
+                .play_as(color, mv)
+                .map_err(|error| sgf::SgfError::IllegalMove {
+                    number: hashes.len(),
+                    error,
+                })?;
+            moves.push(if color == PlayerColor::Black {
+                'B'
+            } else {
+                'W'
+            });
+            moves.push_str(&point);
+            hashes.push(position_hash(replay.board()));
+        }
+        Ok(GameEntry {
+            path: path.to_string(),
+            tree,
+            black: text("PB"),
+            white: text("PW"),
+            result: text("RE"),
+            date: text("DT"),
+            event: text("EV"),
+            size,
+            setup: game.setup().to_vec(),
+            moves,
+            hashes,
+            played,
+        })
+    }
+
+    pub fn winner(&self) -> Option<PlayerColor> {
+        match self.result.chars().next() {
+            Some('B') => Some(PlayerColor::Black),
+            Some('W') => Some(PlayerColor::White),
+            _ => None,
+        }
+    }
+
+    pub fn moves(&self) -> impl Iterator<Item = (PlayerColor, Move)> + '_ {
+        self.moves.as_bytes().chunks(3).map(|chunk| {
+            let color = if chunk[0] == b'B' {
+                PlayerColor::Black
+            } else {
+                PlayerColor::White
+            };
+            let point = std::str::from_utf8(&chunk[1..]).unwrap_or("--");
+            let mv = sgf::decode_point(point, self.size).map_or(Move::Pass, Move::Play);
+            (color, mv)
+        })
+    }
+
+    // whether every stone in the pattern, where `transform` takes it, was ever
+    // on the board in this game; if not, no position in it can match
+    fn could_match(&self, pattern: &Pattern, transform: Transform) -> bool {
+        pattern.stones.iter().all(|&(point, stone)| {
+            let Some(color) = stone else {
+                return true;
+            };
+            let (x, y) = transform.symmetry.apply(point, self.size);
+            let bit = y * self.size + x;
+            let played = &self.played[transform.apply_color(color).index()];
+            played[bit / 64] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    // the board before the first move and after each one
+    fn positions(&self) -> impl Iterator<Item = Board> + '_ {
+        let mut root = sgf::Node::default();
+        root.push("SZ", self.size.to_string());
+        for &(point, color) in &self.setup {
+            let ident = if color == PlayerColor::Black {
+                "AB"
+            } else {
+                "AW"
+            };
+            root.push(ident, sgf::encode_point(point));
+        }
+        let start = Game::from_sgf(&sgf::GameTree {
+            nodes: vec![root],
+            variations: Vec::new(),
+        });
+        let mut game = start.ok();
+        let mut moves = self.moves();
+        let mut started = false;
+        std::iter::from_fn(move || {
+            let game = game.as_mut()?;
+            if started {
+                let (color, mv) = moves.next()?;
+                game.play_as(color, mv).ok()?;
+            }
+            started = true;
+            Some(game.board().clone())
+        })
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct IndexedFile {
+    path: String,
+    // seconds since the epoch, to tell which files have changed since
+    modified: u64,
+    games: Vec<GameEntry>,
+}
+
+/// What indexing a folder did.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IndexReport {
+    pub games: usize,
+    // files that couldn't be read, which are left out
+    pub failed: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Database {
+    version: u32,
+    files: Vec<IndexedFile>,
+}
+impl Default for Database {
+    fn default() -> Self {
+        Database {
+            version: INDEX_VERSION,
+            files: Vec::new(),
+        }
+    }
+}
+impl Database {
+    /// Reads an index; there being none yet gives an empty database.
+    pub fn load(path: impl AsRef<Path>) -> Result<Database, DatabaseError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Database::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let database: Database = serde_json::from_str(&text)?;
+        if database.version != INDEX_VERSION {
+            return Err(DatabaseError::Version(database.version));
+        }
+        Ok(database)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), DatabaseError> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.iter().map(|file| file.games.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn games(&self) -> impl Iterator<Item = &GameEntry> {
+        self.files.iter().flat_map(|file| &file.games)
+    }
+
+    pub fn game(&self, index: usize) -> Option<&GameEntry> {
+        self.games().nth(index)
+    }
+
+    /// Reads every SGF file under `folder` that is new or has changed since it
+    /// was last indexed, and drops files that have gone.
+    pub fn index_folder(&mut self, folder: impl AsRef<Path>) -> Result<IndexReport, DatabaseError> {
+        let mut paths = Vec::new();
+        find_sgf_files(folder.as_ref(), &mut paths)?;
+        self.files.retain(|file| Path::new(&file.path).exists());
+
+        let mut report = IndexReport::default();
+        for path in paths {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            let path = path.to_string_lossy().into_owned();
+            let known = self.files.iter().position(|file| file.path == path);
+            if let Some(i) = known {
+                if self.files[i].modified == modified {
+                    continue;
+                }
+                self.files.remove(i);
+            }
+
+            let games = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| sgf::parse(&text).map_err(|e| e.to_string()))
+                .and_then(|trees| {
+                    trees
+                        .iter()
+                        .enumerate()
+                        .map(|(i, tree)| GameEntry::new(&path, i, tree))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| e.to_string())
+                });
+            match games {
+                Ok(games) => {
+                    report.games += games.len();
+                    self.files.push(IndexedFile {
+                        path,
+                        modified,
+                        games,
+                    });
+                }
+                Err(e) => {
+                    log::warn!("leaving {} out of the index: {}", path, e);
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Every game where the pattern turns up, at the first position it does,
+    /// and what was played next there. With `swap_colors` the pattern with
+    /// Black and White the other way round counts too. The whole board is
+    /// looked up by hash; part of it means replaying every game that could
+    /// have it.
+    pub fn search(&self, pattern: &Pattern, swap_colors: bool) -> SearchResult {
+        let colors: &[bool] = if swap_colors {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        let transforms = colors
+            .iter()
+            .flat_map(|&swap_colors| {
+                Symmetry::all().map(move |symmetry| Transform {
+                    symmetry,
+                    swap_colors,
+                })
+            })
+            .collect::<Vec<_>>();
+        // the whole board is found from the hashes alone, without replaying anything
+        let whole_board = pattern.whole_board().map(|board| {
+            transforms
+                .iter()
+                .map(|&transform| (position_hash(&board.transformed(transform)), transform))
+                .collect::<Vec<_>>()
+        });
+
+        let mut result = SearchResult {
+            pattern: pattern.clone(),
+            hits: Vec::new(),
+            continuations: Vec::new(),
+        };
+        for (i, entry) in self.games().enumerate() {
+            if entry.size != pattern.size {
+                continue;
+            }
+            let found = match &whole_board {
+                Some(hashes) => entry.hashes.iter().enumerate().find_map(|(n, hash)| {
+                    hashes
+                        .iter()
+                        .find(|(h, _)| h == hash)
+                        .map(|&(_, transform)| (n, transform))
+                }),
+                None => {
+                    let possible = transforms
+                        .iter()
+                        .copied()
+                        .filter(|&transform| entry.could_match(pattern, transform))
+                        .collect::<Vec<_>>();
+                    if possible.is_empty() {
+                        continue;
+                    }
+                    entry.positions().enumerate().find_map(|(n, board)| {
+                        possible
+                            .iter()
+                            .find(|&&transform| pattern.matches(&board, transform))
+                            .map(|&transform| (n, transform))
+                    })
+                }
+            };
+            let Some((move_number, transform)) = found else {
+                continue;
+            };
+            result.hits.push(Hit {
+                game: i,
+                move_number,
+            });
+
+            // what came next, seen the way the pattern was given
+            let Some((color, mv)) = entry.moves().nth(move_number) else {
+                continue;
+            };
+            let back = transform.inverse();
+            let point = match mv {
+                Move::Play(point) => Some(back.symmetry.apply(point, entry.size))
+                    .filter(|&point| pattern.contains(point)),
+                _ => None,
+            };
+            let color = back.apply_color(color);
+            let winner = entry.winner().map(|winner| back.apply_color(winner));
+            let at = match result
+                .continuations
+                .iter()
+                .position(|c| c.point == point && c.color == color)
+            {
+                Some(at) => at,
+                None => {
+                    result.continuations.push(Continuation {
+                        point,
+                        color,
+                        games: 0,
+                        wins: 0,
+                    });
+                    result.continuations.len() - 1
+                }
+            };
+            let continuation = &mut result.continuations[at];
+            continuation.games += 1;
+            if winner == Some(color) {
+                continuation.wins += 1;
+            }
+        }
+        result
+            .continuations
+            .sort_by_key(|c| std::cmp::Reverse(c.games));
+        result
+    }
+}
+
+fn find_sgf_files(folder: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_sgf_files(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("sgf"))
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Part of a position to look for: a rectangle of the board, empty points included.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    size: usize,
+    from: Point,
+    to: Point,
+    stones: Vec<(Point, Option<PlayerColor>)>,
+}
+impl Pattern {
+    /// The rectangle of `board` with corners `a` and `b`.
+    pub fn new(board: &Board, a: Point, b: Point) -> Pattern {
+        let from = (a.0.min(b.0), a.1.min(b.1));
+        let to = (a.0.max(b.0), a.1.max(b.1));
+        let stones = (from.1..=to.1)
+            .flat_map(|y| (from.0..=to.0).map(move |x| (x, y)))
+            .map(|point| (point, board.get(point)))
+            .collect();
+        Pattern {
+            size: board.size(),
+            from,
+            to,
+            stones,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn contains(&self, (x, y): Point) -> bool {
+        (self.from.0..=self.to.0).contains(&x) && (self.from.1..=self.to.1).contains(&y)
+    }
+
+    fn whole_board(&self) -> Option<Board> {
+        if self.from != (0, 0) || self.to != (self.size - 1, self.size - 1) {
+            return None;
+        }
+        let mut board = Board::new(self.size);
+        for &(point, stone) in &self.stones {
+            board.set(point, stone);
+        }
+        Some(board)
+    }
+
+    /// Whether `board` has the pattern where `transform` takes it.
+    pub fn matches(&self, board: &Board, transform: Transform) -> bool {
+        self.stones.iter().all(|&(point, stone)| {
+            board.get(transform.symmetry.apply(point, self.size))
+                == stone.map(|color| transform.apply_color(color))
+        })
+    }
+}
+
+/// A game with the pattern in it, and how many moves in it first appears.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hit {
+    // the game's place in `Database::games`
+    pub game: usize,
+    pub move_number: usize,
+}
+
+/// A move played next in the games found, where the pattern was searched for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Continuation {
+    // None for a move outside the pattern, or a pass
+    pub point: Option<Point>,
+    pub color: PlayerColor,
+    pub games: u32,
+    // games won by whoever played it
+    pub wins: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub pattern: Pattern,
+    pub hits: Vec<Hit>,
+    // the most played first
+    pub continuations: Vec<Continuation>,
+}
+
+pub enum DatabaseEvent {
+    Loaded(Result<(Database, IndexReport), DatabaseError>),
+    Found(SearchResult),
+}
+
+/// Indexing or a search, done away from the window on a thread of its own.
+pub struct Task {
+    events: Receiver<DatabaseEvent>,
+}
+impl Task {
+    /// Reads the index at `index`, brings it up to date with `folder` if
+    /// given, and writes it back.
+    pub fn index(index: PathBuf, folder: Option<PathBuf>) -> Task {
+        Task::run(move || {
+            let indexed = Database::load(&index).and_then(|mut database| {
+                let Some(folder) = folder else {
+                    return Ok((database, IndexReport::default()));
+                };
+                let report = database.index_folder(folder)?;
+                database.save(&index)?;
+                Ok((database, report))
+            });
+            DatabaseEvent::Loaded(indexed)
+        })
+    }
+
+    pub fn search(database: Arc<Database>, pattern: Pattern, swap_colors: bool) -> Task {
+        Task::run(move || DatabaseEvent::Found(database.search(&pattern, swap_colors)))
+    }
+
+    fn run(work: impl FnOnce() -> DatabaseEvent + Send + 'static) -> Task {
+        let (sender, events) = mpsc::channel();
+        // no threads in the browser, so the work is done there and then
+        #[cfg(target_arch = "wasm32")]
+        let _ = sender.send(work());
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(move || {
+            let _ = sender.send(work());
+        });
+        Task { events }
+    }
+
+    /// The task's outcome, once it is done.
+    pub fn poll(&self) -> Option<DatabaseEvent> {
+        self.events.try_recv().ok()
+    }
+}
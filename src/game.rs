@@ -1,66 +1,840 @@
+use std::collections::BTreeMap;
 
+use crate::board::{Board, PlayerColor, Point};
+use crate::clock::{GameClock, TimeControl};
+use crate::history::UndoPolicy;
+use crate::sgf::{self, SgfError};
 
-// let mut game = Game {
-
-//     pieces: [Piece {
-//         color: PieceColor::Black,
-//         piece_type: PieceType::Pawn,
-//         x: 0,
-//         y: 0,
-//     }; 32],
-// };
-
-// let rook = Piece {
-//     color: PieceColor::Black,
-//     piece_type: PieceType::Rook,
-//     x: 0,
-//     y: 0,
-// };
-// game.pieces[0] = rook;
-// game.pieces[16] = rook.clone();
-// game.pieces[16].color = PieceColor::White;
-
-// let knight = Piece {
-//     color: PieceColor::Black,
-//     piece_type: PieceType::Knight,
-//     x: 1,
-//     y: 0,
-// };
-// game.pieces[1] = knight;
-// game.pieces[17] = knight.clone()
-// game.pieces[17].color = PieceColor::White;
-
-// for color in PieceColor::iter() {
-//     game.pieces[0].
-// }
-// let rook = pie
-//     for
-// game.pieces[0].
-// game
-// }
-// }
-
-// ,
-//         Piece {
-//             color: PieceColor::Black,
-//             piece_type: PieceType::Knight,
-//             x: 1,
-//             y: 0,
-//         },
-//         Piece {
-//             color: PieceColor::Black,
-//             piece_type: PieceType::Bishop,
-//             x: 2,
-//             y: 0,
-//         },
-//         Piece {
-//             color: PieceColor::Black,
-//             piece_type: PieceType::Queen,
-//             x: 3,
-//             y: 0,
-//         },
-//     ],
-// }
-// }
-// }
-//
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Rules {
+    Japanese,
+    Chinese,
+}
+impl Rules {
+    pub fn name(self) -> &'static str {
+        match self {
+            Rules::Japanese => "Japanese",
+            Rules::Chinese => "Chinese",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GameSettings {
+    pub size: usize,
+    pub komi: f32,
+    pub handicap: usize,
+    pub rules: Rules,
+    pub time_control: TimeControl,
+    pub rated: bool,
+    pub undo_policy: UndoPolicy,
+}
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            size: 19,
+            komi: 6.5,
+            handicap: 0,
+            rules: Rules::Japanese,
+            time_control: TimeControl::Unlimited,
+            rated: false,
+            undo_policy: UndoPolicy::UnratedOnly,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Move {
+    Play(Point),
+    Pass,
+    Resign,
+}
+
+/// Annotations a teacher puts on the board, as in the SGF TR, SQ, CR, MA, LB
+/// and TB/TW properties.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Markup {
+    Triangle,
+    Square,
+    Circle,
+    Cross,
+    Label(String),
+    Territory(PlayerColor),
+}
+impl Markup {
+    fn sgf_ident(&self) -> &'static str {
+        match self {
+            Markup::Triangle => "TR",
+            Markup::Square => "SQ",
+            Markup::Circle => "CR",
+            Markup::Cross => "MA",
+            Markup::Label(_) => "LB",
+            Markup::Territory(PlayerColor::Black) => "TB",
+            Markup::Territory(PlayerColor::White) => "TW",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    GameOver,
+    OutOfBounds,
+    Occupied,
+    Suicide,
+    Ko,
+}
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            MoveError::GameOver => "the game is over",
+            MoveError::OutOfBounds => "that point is off the board",
+            MoveError::Occupied => "that point is occupied",
+            MoveError::Suicide => "suicide is not allowed",
+            MoveError::Ko => "the ko must be taken back elsewhere first",
+        };
+        f.write_str(reason)
+    }
+}
+impl std::error::Error for MoveError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameResult {
+    Resignation(PlayerColor),
+    Time(PlayerColor),
+    Score { winner: PlayerColor, margin: f32 },
+}
+impl GameResult {
+    pub fn winner(&self) -> PlayerColor {
+        match *self {
+            GameResult::Resignation(winner) | GameResult::Time(winner) => winner,
+            GameResult::Score { winner, .. } => winner,
+        }
+    }
+}
+// same shape as an SGF RE[] value, e.g. "B+R", "W+6.5" or "Draw"
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let winner = match self.winner() {
+            PlayerColor::Black => "B",
+            PlayerColor::White => "W",
+        };
+        match self {
+            GameResult::Resignation(_) => write!(f, "{}+R", winner),
+            GameResult::Time(_) => write!(f, "{}+T", winner),
+            GameResult::Score { margin, .. } if *margin == 0.0 => f.write_str("Draw"),
+            GameResult::Score { margin, .. } => write!(f, "{}+{}", winner, margin),
+        }
+    }
+}
+
+/// The most handicap stones a board of `size` lines has star points for:
+/// none below 7x7, and only the corners on even sizes, which have no centre.
+pub fn max_handicap(size: usize) -> usize {
+    if size < 7 {
+        0
+    } else if size % 2 == 1 {
+        9
+    } else {
+        4
+    }
+}
+
+// star points in the order handicap stones are placed
+fn handicap_points(size: usize, handicap: usize) -> Vec<Point> {
+    if handicap == 0 {
+        return Vec::new();
+    }
+    let near = if size < 13 { 2 } else { 3 };
+    let far = size - 1 - near;
+    let mid = size / 2;
+    let corners = [(far, near), (near, far), (far, far), (near, near)];
+    let sides = [(near, mid), (far, mid), (mid, near), (mid, far)];
+
+    let mut points = corners[..handicap.min(4)].to_vec();
+    if handicap > 5 {
+        let side_count = if handicap >= 8 { 4 } else { 2 };
+        points.extend_from_slice(&sides[..side_count]);
+    }
+    if handicap >= 5 && handicap % 2 == 1 {
+        points.push((mid, mid));
+    }
+    points
+}
+
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub settings: GameSettings,
+    pub clock: GameClock,
+    board: Board,
+    // stones on the board before the first move: handicap, or AB/AW from a loaded game
+    setup: Vec<(Point, PlayerColor)>,
+    to_move: PlayerColor,
+    // prisoners taken by each player, indexed by `PlayerColor::index`
+    captures: [usize; 2],
+    ko: Option<Point>,
+    moves: Vec<(PlayerColor, Move)>,
+    consecutive_passes: usize,
+    result: Option<GameResult>,
+    // one entry per node of the game tree: the setup node, then one after each move
+    markup: Vec<BTreeMap<Point, Markup>>,
+}
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(GameSettings::default())
+    }
+}
+impl Game {
+    pub fn new(settings: GameSettings) -> Self {
+        let mut board = Board::new(settings.size);
+        let handicap = if settings.handicap >= 2 {
+            settings.handicap.min(max_handicap(settings.size))
+        } else {
+            0
+        };
+        let setup = handicap_points(settings.size, handicap)
+            .into_iter()
+            .map(|point| (point, PlayerColor::Black))
+            .collect::<Vec<_>>();
+        for &(point, color) in &setup {
+            board.set(point, Some(color));
+        }
+
+        Game {
+            settings,
+            clock: GameClock::new(settings.time_control),
+            board,
+            setup,
+            to_move: if handicap > 0 {
+                PlayerColor::White
+            } else {
+                PlayerColor::Black
+            },
+            captures: [0, 0],
+            ko: None,
+            moves: Vec::new(),
+            consecutive_passes: 0,
+            result: None,
+            markup: vec![BTreeMap::new()],
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn to_move(&self) -> PlayerColor {
+        self.to_move
+    }
+
+    pub fn captures(&self, color: PlayerColor) -> usize {
+        self.captures[color.index()]
+    }
+
+    pub fn moves(&self) -> &[(PlayerColor, Move)] {
+        &self.moves
+    }
+
+    /// The stones on the board before the first move.
+    pub fn setup(&self) -> &[(Point, PlayerColor)] {
+        &self.setup
+    }
+
+    /// Where the most recent stone was played, if the last move was a stone.
+    pub fn last_play(&self) -> Option<Point> {
+        match self.moves.last() {
+            Some((_, Move::Play(point))) => Some(*point),
+            _ => None,
+        }
+    }
+
+    /// The move number that put each stone currently on the board there.
+    /// Handicap stones have no number.
+    pub fn move_numbers(&self) -> std::collections::HashMap<Point, usize> {
+        let mut numbers = std::collections::HashMap::new();
+        for (i, (_, mv)) in self.moves.iter().enumerate() {
+            if let Move::Play(point) = mv {
+                numbers.insert(*point, i + 1);
+            }
+        }
+        numbers.retain(|point, _| self.board.get(*point).is_some());
+        numbers
+    }
+
+    /// Markup on the current node.
+    pub fn markup(&self) -> &BTreeMap<Point, Markup> {
+        self.markup.last().unwrap()
+    }
+
+    /// Puts `markup` on `point` in the current node, or takes it off if it is already there.
+    /// Any label counts as the same markup as any other.
+    pub fn toggle_markup(&mut self, point: Point, markup: Markup) {
+        self.toggle_markup_at(self.moves.len(), point, markup);
+    }
+
+    /// `toggle_markup` on an earlier node: 0 is the setup, `n` the position after move `n`.
+    pub fn toggle_markup_at(&mut self, node: usize, point: Point, markup: Markup) {
+        let Some(node) = self.markup.get_mut(node) else {
+            return;
+        };
+        let same = match (node.get(&point), &markup) {
+            (Some(Markup::Label(_)), Markup::Label(_)) => true,
+            (Some(existing), markup) => existing == markup,
+            (None, _) => false,
+        };
+        if same {
+            node.remove(&point);
+        } else {
+            node.insert(point, markup);
+        }
+    }
+
+    /// The first letter not yet used as a label in the current node.
+    pub fn next_label(&self) -> String {
+        let used = self
+            .markup()
+            .values()
+            .filter_map(|m| match m {
+                Markup::Label(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ('A'..='Z')
+            .map(String::from)
+            .chain((1..).map(|n: usize| n.to_string()))
+            .find(|label| !used.contains(&label.as_str()))
+            .unwrap()
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Checks whether the player to move may play at `point` without changing anything.
+    pub fn check_play(&self, point: Point) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        if !self.board.contains(point) {
+            return Err(MoveError::OutOfBounds);
+        }
+        if self.board.get(point).is_some() {
+            return Err(MoveError::Occupied);
+        }
+        if self.ko == Some(point) {
+            return Err(MoveError::Ko);
+        }
+
+        let mut board = self.board.clone();
+        board.set(point, Some(self.to_move));
+        let captures_something = board.neighbours(point).any(|n| {
+            board.get(n) == Some(self.to_move.opponent())
+                && board.group(n).unwrap().liberties.is_empty()
+        });
+        if !captures_something && board.group(point).unwrap().liberties.is_empty() {
+            return Err(MoveError::Suicide);
+        }
+        Ok(())
+    }
+
+    /// Plays `mv` for the player to move and returns the number of stones it captured.
+    pub fn play(&mut self, mv: Move) -> Result<usize, MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        let color = self.to_move;
+        let mut captured = 0;
+
+        match mv {
+            Move::Play(point) => {
+                self.check_play(point)?;
+                self.board.set(point, Some(color));
+                let mut captured_points = Vec::new();
+                for n in self.board.neighbours(point).collect::<Vec<_>>() {
+                    if self.board.get(n) != Some(color.opponent()) {
+                        continue;
+                    }
+                    let group = self.board.group(n).unwrap();
+                    if group.liberties.is_empty() {
+                        captured += self.board.remove_group(n);
+                        captured_points.extend(group.stones);
+                    }
+                }
+                self.captures[color.index()] += captured;
+
+                // a lone stone that took exactly one stone and sits in atari makes a ko
+                let own_group = self.board.group(point).unwrap();
+                self.ko = if captured == 1
+                    && own_group.stones.len() == 1
+                    && own_group.liberties.len() == 1
+                {
+                    Some(captured_points[0])
+                } else {
+                    None
+                };
+                self.consecutive_passes = 0;
+            }
+            Move::Pass => {
+                self.ko = None;
+                self.consecutive_passes += 1;
+            }
+            Move::Resign => {
+                self.result = Some(GameResult::Resignation(color.opponent()));
+            }
+        }
+
+        self.moves.push((color, mv));
+        self.markup.push(BTreeMap::new());
+        self.clock.end_turn(color.index());
+        self.to_move = color.opponent();
+        if self.consecutive_passes >= 2 {
+            self.result = Some(self.scored_result());
+        }
+        Ok(captured)
+    }
+
+    /// Ends the game with `color` resigning, which they may do on either turn.
+    pub fn resign(&mut self, color: PlayerColor) -> Result<(), MoveError> {
+        if self.is_over() {
+            return Err(MoveError::GameOver);
+        }
+        self.result = Some(GameResult::Resignation(color.opponent()));
+        self.moves.push((color, Move::Resign));
+        self.markup.push(BTreeMap::new());
+        Ok(())
+    }
+
+    /// Plays `mv` for `color` whoever's turn it is, as records do when a move
+    /// elsewhere on the board has been left out.
+    pub fn play_as(&mut self, color: PlayerColor, mv: Move) -> Result<usize, MoveError> {
+        let to_move = std::mem::replace(&mut self.to_move, color);
+        self.play(mv).inspect_err(|_| self.to_move = to_move)
+    }
+
+    /// Charges thinking time to the player to move and ends the game if they flag.
+    pub fn tick(&mut self, elapsed: std::time::Duration) {
+        if self.is_over() {
+            return;
+        }
+        if !self.clock.spend(self.to_move.index(), elapsed) {
+            self.result = Some(GameResult::Time(self.to_move.opponent()));
+        }
+    }
+
+    /// Black's and White's score, komi included. Every stone still on the
+    /// board counts as alive.
+    pub fn score(&self) -> [f32; 2] {
+        let mut score = [0.0, self.settings.komi];
+        for (region, owner) in self.board.territory() {
+            if let Some(owner) = owner {
+                score[owner.index()] += region.len() as f32;
+            }
+        }
+        match self.settings.rules {
+            Rules::Chinese => {
+                for (_, color) in self.board.stones() {
+                    score[color.index()] += 1.0;
+                }
+            }
+            Rules::Japanese => {
+                score[0] += self.captures[0] as f32;
+                score[1] += self.captures[1] as f32;
+            }
+        }
+        score
+    }
+
+    fn scored_result(&self) -> GameResult {
+        let [black, white] = self.score();
+        if black > white {
+            GameResult::Score {
+                winner: PlayerColor::Black,
+                margin: black - white,
+            }
+        } else {
+            GameResult::Score {
+                winner: PlayerColor::White,
+                margin: white - black,
+            }
+        }
+    }
+
+    /// The position after the first `n` moves, with the markup it had, for
+    /// looking back through the game. The clock of the copy doesn't run.
+    pub fn replayed(&self, n: usize) -> Game {
+        let mut game = Game::new(GameSettings {
+            handicap: 0,
+            time_control: TimeControl::Unlimited,
+            ..self.settings
+        });
+        game.settings = self.settings;
+        for &(point, color) in &self.setup {
+            game.board.set(point, Some(color));
+        }
+        game.setup = self.setup.clone();
+        game.to_move = self.moves.first().map_or(self.to_move, |&(color, _)| color);
+        for &(color, mv) in &self.moves[..n.min(self.moves.len())] {
+            // colours are taken from the record, as in `from_sgf`
+            game.to_move = color;
+            game.play(mv)
+                .expect("a move that was legal once is legal on replay");
+        }
+        game.markup = self.markup[..game.markup.len()].to_vec();
+        game
+    }
+
+    /// The game as an SGF tree: settings and handicap stones in the root node,
+    /// then one node per move, each with its markup.
+    pub fn to_sgf(&self) -> sgf::GameTree {
+        let mut root = sgf::Node::default();
+        for (ident, value) in [
+            ("FF", "4".to_string()),
+            ("GM", "1".to_string()),
+            ("CA", "UTF-8".to_string()),
+            ("AP", format!("go:{}", env!("CARGO_PKG_VERSION"))),
+            ("SZ", self.settings.size.to_string()),
+            ("KM", self.settings.komi.to_string()),
+            ("RU", self.settings.rules.name().to_string()),
+        ] {
+            root.push(ident, value);
+        }
+        if self.settings.handicap >= 2 {
+            root.push("HA", self.settings.handicap.to_string());
+        }
+        for &(point, color) in &self.setup {
+            let ident = match color {
+                PlayerColor::Black => "AB",
+                PlayerColor::White => "AW",
+            };
+            root.push(ident, sgf::encode_point(point));
+        }
+        if let TimeControl::ByoYomi {
+            main_time,
+            periods,
+            period_time,
+        } = self.settings.time_control
+        {
+            root.push("TM", main_time.as_secs().to_string());
+            root.push(
+                "OT",
+                format!("{}x{} byo-yomi", periods, period_time.as_secs()),
+            );
+        }
+        if let Some(result) = self.result {
+            root.push("RE", result.to_string());
+        }
+        push_markup(&mut root, &self.markup[0]);
+
+        let mut nodes = vec![root];
+        for ((color, mv), markup) in self.moves.iter().zip(&self.markup[1..]) {
+            let mut node = sgf::Node::default();
+            let ident = match color {
+                PlayerColor::Black => "B",
+                PlayerColor::White => "W",
+            };
+            match mv {
+                Move::Play(point) => node.push(ident, sgf::encode_point(*point)),
+                Move::Pass => node.push(ident, String::new()),
+                // resigning has no node of its own in SGF, it only shows in RE[]
+                Move::Resign if markup.is_empty() => continue,
+                Move::Resign => {}
+            }
+            push_markup(&mut node, markup);
+            nodes.push(node);
+        }
+        sgf::GameTree {
+            nodes,
+            variations: Vec::new(),
+        }
+    }
+
+    /// Replays the main line of an SGF game, markup included. The clock is
+    /// left unlimited so a loaded game can be reviewed at leisure.
+    pub fn from_sgf(tree: &sgf::GameTree) -> Result<Game, SgfError> {
+        let main_line = tree.main_line();
+        let root = *main_line.first().ok_or(SgfError::NoGame)?;
+        let bad_value = |property: &str, value: &str| SgfError::BadValue {
+            property: property.to_string(),
+            value: value.to_string(),
+        };
+
+        let mut settings = GameSettings {
+            komi: 0.0,
+            ..GameSettings::default()
+        };
+        if let Some(value) = root.first("SZ") {
+            settings.size = value
+                .parse()
+                .ok()
+                .filter(|size| (2..=25).contains(size))
+                .ok_or_else(|| bad_value("SZ", value))?;
+        }
+        if let Some(value) = root.first("KM") {
+            settings.komi = value.parse().map_err(|_| bad_value("KM", value))?;
+        }
+        if let Some(value) = root.first("RU") {
+            if value.eq_ignore_ascii_case("chinese") {
+                settings.rules = Rules::Chinese;
+            }
+        }
+        let handicap = match root.first("HA") {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|&handicap| handicap < 2 || handicap <= max_handicap(settings.size))
+                .ok_or_else(|| bad_value("HA", value))?,
+            None => 0,
+        };
+
+        // setup stones are taken from AB/AW rather than placed from HA, since
+        // not every program puts handicap stones on the same points
+        let mut game = Game::new(settings);
+        game.settings.handicap = handicap;
+        for (ident, color) in [("AB", PlayerColor::Black), ("AW", PlayerColor::White)] {
+            if let Some(values) = root.get(ident) {
+                for point in sgf::decode_points(ident, values, settings.size)? {
+                    game.board.set(point, Some(color));
+                    game.setup.push((point, color));
+                }
+            }
+        }
+        game.to_move = match root.first("PL") {
+            Some("W") => PlayerColor::White,
+            Some("B") => PlayerColor::Black,
+            _ if handicap >= 2 => PlayerColor::White,
+            _ => PlayerColor::Black,
+        };
+        game.markup[0] = read_markup(root, settings.size)?;
+
+        for node in &main_line[1..] {
+            let mv = [("B", PlayerColor::Black), ("W", PlayerColor::White)]
+                .into_iter()
+                .find_map(|(ident, color)| node.first(ident).map(|value| (color, value)));
+            let Some((color, value)) = mv else {
+                // nodes without a move only carry markup or comments for the position
+                let markup = read_markup(node, settings.size)?;
+                game.markup.last_mut().unwrap().extend(markup);
+                continue;
+            };
+            let mv = match sgf::decode_point(value, settings.size) {
+                Some(point) => Move::Play(point),
+                None if value.is_empty() || value == "tt" => Move::Pass,
+                None => return Err(bad_value("B/W", value)),
+            };
+            // records sometimes have the same colour move twice, e.g. after a dropped pass
+            game.play_as(color, mv)
+                .map_err(|error| SgfError::IllegalMove {
+                    number: game.moves.len() + 1,
+                    error,
+                })?;
+            *game.markup.last_mut().unwrap() = read_markup(node, settings.size)?;
+        }
+        Ok(game)
+    }
+}
+
+fn push_markup(node: &mut sgf::Node, markup: &BTreeMap<Point, Markup>) {
+    for (point, markup) in markup {
+        let value = match markup {
+            Markup::Label(text) => format!("{}:{}", sgf::encode_point(*point), text),
+            _ => sgf::encode_point(*point),
+        };
+        node.push(markup.sgf_ident(), value);
+    }
+}
+
+fn read_markup(node: &sgf::Node, size: usize) -> Result<BTreeMap<Point, Markup>, SgfError> {
+    let mut markup = BTreeMap::new();
+    for (ident, kind) in [
+        ("TR", Markup::Triangle),
+        ("SQ", Markup::Square),
+        ("CR", Markup::Circle),
+        ("MA", Markup::Cross),
+        ("TB", Markup::Territory(PlayerColor::Black)),
+        ("TW", Markup::Territory(PlayerColor::White)),
+    ] {
+        if let Some(values) = node.get(ident) {
+            for point in sgf::decode_points(ident, values, size)? {
+                markup.insert(point, kind.clone());
+            }
+        }
+    }
+    for value in node.get("LB").unwrap_or_default() {
+        let label = value
+            .split_once(':')
+            .and_then(|(point, text)| Some((sgf::decode_point(point, size)?, text)));
+        let Some((point, text)) = label else {
+            return Err(SgfError::BadValue {
+                property: "LB".to_string(),
+                value: value.clone(),
+            });
+        };
+        markup.insert(point, Markup::Label(text.to_string()));
+    }
+    Ok(markup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small(size: usize, komi: f32, rules: Rules) -> Game {
+        Game::new(GameSettings {
+            size,
+            komi,
+            rules,
+            ..GameSettings::default()
+        })
+    }
+
+    fn play(game: &mut Game, points: &[Point]) {
+        for &point in points {
+            game.play(Move::Play(point)).unwrap();
+        }
+    }
+
+    #[test]
+    fn captures_a_stone_without_liberties() {
+        let mut game = small(9, 6.5, Rules::Japanese);
+        play(&mut game, &[(1, 0), (1, 1), (0, 1), (8, 8), (2, 1), (8, 7)]);
+        assert_eq!(game.play(Move::Play((1, 2))), Ok(1));
+        assert_eq!(game.board().get((1, 1)), None);
+        assert_eq!(game.captures(PlayerColor::Black), 1);
+    }
+
+    #[test]
+    fn refuses_to_retake_a_ko_at_once() {
+        let mut game = small(9, 6.5, Rules::Japanese);
+        // black takes the white stone at (1, 1) with a lone stone in atari
+        play(
+            &mut game,
+            &[
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (1, 1),
+                (1, 2),
+                (3, 1),
+                (8, 8),
+                (2, 2),
+            ],
+        );
+        assert_eq!(game.play(Move::Play((2, 1))), Ok(1));
+        assert_eq!(game.play(Move::Play((1, 1))), Err(MoveError::Ko));
+        // a move elsewhere each, and white may take back
+        play(&mut game, &[(8, 0), (7, 7)]);
+        assert_eq!(game.play(Move::Play((1, 1))), Ok(1));
+        assert_eq!(game.board().get((2, 1)), None);
+    }
+
+    #[test]
+    fn refuses_suicide() {
+        let mut game = small(9, 6.5, Rules::Japanese);
+        play(&mut game, &[(1, 0), (8, 8), (0, 1)]);
+        assert_eq!(game.play(Move::Play((0, 0))), Err(MoveError::Suicide));
+        assert_eq!(game.to_move(), PlayerColor::White);
+        assert!(game.board().get((0, 0)).is_none());
+    }
+
+    // black walls off the first column and white the rest, then both pass
+    fn walled(size: usize, komi: f32, rules: Rules) -> Game {
+        let mut game = small(size, komi, rules);
+        for y in 0..size {
+            play(&mut game, &[(1, y), (2, y)]);
+        }
+        game.play(Move::Pass).unwrap();
+        game.play(Move::Pass).unwrap();
+        game
+    }
+
+    #[test]
+    fn scores_territory_or_area() {
+        let game = walled(5, 0.5, Rules::Japanese);
+        assert_eq!(game.score(), [5.0, 10.5]);
+        let game = walled(5, 0.5, Rules::Chinese);
+        assert_eq!(game.score(), [10.0, 15.5]);
+        let result = game.result().unwrap();
+        assert_eq!(result.winner(), PlayerColor::White);
+        assert_eq!(result.to_string(), "W+5.5");
+
+        let jigo = walled(4, 0.0, Rules::Japanese);
+        assert_eq!(jigo.score(), [4.0, 4.0]);
+        assert_eq!(jigo.result().unwrap().to_string(), "Draw");
+    }
+
+    fn load(text: &str) -> Result<Game, SgfError> {
+        Game::from_sgf(&sgf::parse(text)?[0])
+    }
+
+    #[test]
+    fn loads_every_board_size() {
+        for size in 2..=25 {
+            let game = load(&format!("(;SZ[{}];B[aa])", size)).unwrap();
+            assert_eq!(game.settings.size, size);
+            assert_eq!(game.board().get((0, 0)), Some(PlayerColor::Black));
+        }
+    }
+
+    #[test]
+    fn handicap_fits_the_board() {
+        for size in 2..=25 {
+            for handicap in 0..=9 {
+                let game = Game::new(GameSettings {
+                    size,
+                    handicap,
+                    ..GameSettings::default()
+                });
+                let placed = game.board().stones().count();
+                let expected = if handicap >= 2 {
+                    handicap.min(max_handicap(size))
+                } else {
+                    0
+                };
+                assert_eq!(placed, expected, "{} stones on {}x{}", handicap, size, size);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_a_handicap_the_board_cannot_hold() {
+        assert!(load("(;SZ[5]HA[2])").is_err());
+        assert!(load("(;SZ[10]HA[5])").is_err());
+        assert!(load("(;SZ[10]HA[4])").is_ok());
+        let game = load("(;SZ[9]HA[9])").unwrap();
+        assert_eq!(game.settings.handicap, 9);
+        assert_eq!(game.to_move(), PlayerColor::White);
+    }
+
+    #[test]
+    fn handicap_goes_on_the_star_points() {
+        let mut game = Game::new(GameSettings {
+            handicap: 5,
+            ..GameSettings::default()
+        });
+        for point in [(15, 3), (3, 15), (15, 15), (3, 3), (9, 9)] {
+            assert_eq!(game.board().get(point), Some(PlayerColor::Black));
+        }
+        assert!(game.play(Move::Play((9, 9))).is_err());
+    }
+
+    #[test]
+    fn markup_on_an_earlier_node_is_saved_there() {
+        let mut game = Game::default();
+        for point in [(3, 3), (15, 15), (3, 15)] {
+            game.play(Move::Play(point)).unwrap();
+        }
+        game.toggle_markup_at(1, (3, 3), Markup::Triangle);
+        game.toggle_markup((3, 15), Markup::Circle);
+
+        let tree = game.to_sgf();
+        assert_eq!(tree.nodes[1].first("TR"), Some("dd"));
+        assert_eq!(tree.nodes[2].first("TR"), None);
+        assert_eq!(tree.nodes[3].first("CR"), Some("dp"));
+
+        game.toggle_markup_at(1, (3, 3), Markup::Triangle);
+        assert_eq!(game.to_sgf().nodes[1].first("TR"), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use crate::input::Action;
+
+// how far the stick has to lean to count as a direction, and how far back to let go of it
+const STICK_PRESS: f32 = 0.5;
+const STICK_RELEASE: f32 = 0.3;
+// a held direction starts repeating after the delay, like a held key
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+const REPEAT_INTERVAL: Duration = Duration::from_millis(120);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadButton {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    South,
+    East,
+    North,
+    West,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+}
+
+/// Gamepad input with the backend taken out, so the mapping can be driven by
+/// made-up events as easily as by a real pad.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadEvent {
+    Pressed(PadButton),
+    Released(PadButton),
+    // left stick, -1.0 to 1.0, with up positive
+    StickX(f32),
+    StickY(f32),
+}
+
+fn button_action(button: PadButton) -> Option<Action> {
+    let action = match button {
+        PadButton::DPadUp => Action::CursorUp,
+        PadButton::DPadDown => Action::CursorDown,
+        PadButton::DPadLeft => Action::CursorLeft,
+        PadButton::DPadRight => Action::CursorRight,
+        PadButton::South => Action::PlaceStone,
+        PadButton::East => Action::Undo,
+        PadButton::North => Action::Pass,
+        PadButton::LeftShoulder => Action::PrevMove,
+        PadButton::RightShoulder => Action::NextMove,
+        PadButton::Select => Action::ToggleCoordinates,
+        PadButton::West => Action::ResetView,
+        PadButton::Start => return None,
+    };
+    Some(action)
+}
+
+/// Turns pad events into the same `Action`s the keyboard produces.
+#[derive(Default)]
+pub struct GamepadMapper {
+    stick: [f32; 2],
+    stick_direction: Option<Action>,
+    // the direction being held and how long until it fires again
+    held: Option<(Action, Duration)>,
+}
+impl GamepadMapper {
+    pub fn process(&mut self, event: PadEvent, actions: &mut Vec<Action>) {
+        match event {
+            PadEvent::Pressed(button) => {
+                let Some(action) = button_action(button) else {
+                    return;
+                };
+                if action.repeats() {
+                    self.held = Some((action, REPEAT_DELAY));
+                }
+                actions.push(action);
+            }
+            PadEvent::Released(button) => {
+                if self.held.map(|(action, _)| action) == button_action(button) {
+                    self.held = None;
+                }
+            }
+            PadEvent::StickX(x) => {
+                self.stick[0] = x;
+                self.update_stick(actions);
+            }
+            PadEvent::StickY(y) => {
+                self.stick[1] = y;
+                self.update_stick(actions);
+            }
+        }
+    }
+
+    fn update_stick(&mut self, actions: &mut Vec<Action>) {
+        let [x, y] = self.stick;
+        let lean = x.abs().max(y.abs());
+        let direction = if lean < STICK_RELEASE {
+            None
+        } else if lean < STICK_PRESS {
+            // in between, keep whatever the stick was already doing
+            self.stick_direction
+        } else if x.abs() > y.abs() {
+            Some(if x > 0.0 {
+                Action::CursorRight
+            } else {
+                Action::CursorLeft
+            })
+        } else {
+            Some(if y > 0.0 {
+                Action::CursorUp
+            } else {
+                Action::CursorDown
+            })
+        };
+        if direction == self.stick_direction {
+            return;
+        }
+        if self.stick_direction.is_some()
+            && self.held.map(|(action, _)| action) == self.stick_direction
+        {
+            self.held = None;
+        }
+        self.stick_direction = direction;
+        if let Some(action) = direction {
+            self.held = Some((action, REPEAT_DELAY));
+            actions.push(action);
+        }
+    }
+
+    /// Fires repeats for a direction that is still held `elapsed` after the last call.
+    pub fn tick(&mut self, elapsed: Duration, actions: &mut Vec<Action>) {
+        let Some((action, mut remaining)) = self.held else {
+            return;
+        };
+        let mut elapsed = elapsed;
+        while elapsed >= remaining {
+            actions.push(action);
+            elapsed -= remaining;
+            remaining = REPEAT_INTERVAL;
+        }
+        self.held = Some((action, remaining - elapsed));
+    }
+}
+
+fn pad_button(button: gilrs::Button) -> Option<PadButton> {
+    let button = match button {
+        gilrs::Button::DPadUp => PadButton::DPadUp,
+        gilrs::Button::DPadDown => PadButton::DPadDown,
+        gilrs::Button::DPadLeft => PadButton::DPadLeft,
+        gilrs::Button::DPadRight => PadButton::DPadRight,
+        gilrs::Button::South => PadButton::South,
+        gilrs::Button::East => PadButton::East,
+        gilrs::Button::North => PadButton::North,
+        gilrs::Button::West => PadButton::West,
+        gilrs::Button::LeftTrigger => PadButton::LeftShoulder,
+        gilrs::Button::RightTrigger => PadButton::RightShoulder,
+        gilrs::Button::Select => PadButton::Select,
+        gilrs::Button::Start => PadButton::Start,
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Every connected gamepad, read through gilrs.
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+    mapper: GamepadMapper,
+}
+impl Gamepads {
+    pub fn new() -> Option<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads {
+                gilrs,
+                mapper: GamepadMapper::default(),
+            }),
+            Err(e) => {
+                log::warn!("gamepads are unavailable: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Reads everything the pads sent since the last call and queues the resulting actions.
+    pub fn poll(&mut self, elapsed: Duration, actions: &mut Vec<Action>) {
+        while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+            let event = match event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    pad_button(button).map(PadEvent::Pressed)
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    pad_button(button).map(PadEvent::Released)
+                }
+                gilrs::EventType::AxisChanged(gilrs::Axis::LeftStickX, x, _) => {
+                    Some(PadEvent::StickX(x))
+                }
+                gilrs::EventType::AxisChanged(gilrs::Axis::LeftStickY, y, _) => {
+                    Some(PadEvent::StickY(y))
+                }
+                _ => None,
+            };
+            if let Some(event) = event {
+                self.mapper.process(event, actions);
+            }
+        }
+        self.mapper.tick(elapsed, actions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(mapper: &mut GamepadMapper, events: &[PadEvent]) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for &event in events {
+            mapper.process(event, &mut actions);
+        }
+        actions
+    }
+
+    fn tick(mapper: &mut GamepadMapper, ms: u64) -> Vec<Action> {
+        let mut actions = Vec::new();
+        mapper.tick(Duration::from_millis(ms), &mut actions);
+        actions
+    }
+
+    #[test]
+    fn buttons_map_to_actions() {
+        let mut mapper = GamepadMapper::default();
+        let pressed = [
+            (PadButton::South, Action::PlaceStone),
+            (PadButton::East, Action::Undo),
+            (PadButton::North, Action::Pass),
+            (PadButton::LeftShoulder, Action::PrevMove),
+            (PadButton::RightShoulder, Action::NextMove),
+            (PadButton::Select, Action::ToggleCoordinates),
+        ];
+        for (button, action) in pressed {
+            let events = [PadEvent::Pressed(button), PadEvent::Released(button)];
+            assert_eq!(feed(&mut mapper, &events), [action], "{:?}", button);
+        }
+        assert!(feed(&mut mapper, &[PadEvent::Pressed(PadButton::Start)]).is_empty());
+    }
+
+    #[test]
+    fn dpad_moves_the_cursor_and_repeats_while_held() {
+        let mut mapper = GamepadMapper::default();
+        let pressed = feed(&mut mapper, &[PadEvent::Pressed(PadButton::DPadRight)]);
+        assert_eq!(pressed, [Action::CursorRight]);
+
+        assert!(tick(&mut mapper, 399).is_empty());
+        assert_eq!(tick(&mut mapper, 1), [Action::CursorRight]);
+        assert_eq!(tick(&mut mapper, 240), [Action::CursorRight; 2]);
+
+        feed(&mut mapper, &[PadEvent::Released(PadButton::DPadRight)]);
+        assert!(tick(&mut mapper, 1000).is_empty());
+    }
+
+    #[test]
+    fn holding_a_button_that_doesnt_repeat_fires_once() {
+        let mut mapper = GamepadMapper::default();
+        feed(&mut mapper, &[PadEvent::Pressed(PadButton::South)]);
+        assert!(tick(&mut mapper, 1000).is_empty());
+    }
+
+    #[test]
+    fn stick_picks_the_stronger_axis() {
+        let mut mapper = GamepadMapper::default();
+        let events = [PadEvent::StickX(-0.6), PadEvent::StickY(0.8)];
+        assert_eq!(
+            feed(&mut mapper, &events),
+            [Action::CursorLeft, Action::CursorUp]
+        );
+        let events = [PadEvent::StickY(0.0), PadEvent::StickX(0.0)];
+        assert_eq!(feed(&mut mapper, &events), [Action::CursorLeft]);
+        assert_eq!(
+            feed(&mut mapper, &[PadEvent::StickY(-0.9)]),
+            [Action::CursorDown]
+        );
+        // leaning further the same way isn't another step
+        assert!(feed(&mut mapper, &[PadEvent::StickY(-1.0)]).is_empty());
+    }
+
+    #[test]
+    fn stick_has_hysteresis() {
+        let mut mapper = GamepadMapper::default();
+        // not far enough to start
+        assert!(feed(&mut mapper, &[PadEvent::StickY(-0.4)]).is_empty());
+        assert_eq!(
+            feed(&mut mapper, &[PadEvent::StickY(-0.6)]),
+            [Action::CursorDown]
+        );
+        // easing off to between the two thresholds keeps it held, without firing again
+        assert!(feed(&mut mapper, &[PadEvent::StickY(-0.4)]).is_empty());
+        assert_eq!(tick(&mut mapper, 400), [Action::CursorDown]);
+        // letting go past the release threshold stops the repeats
+        assert!(feed(&mut mapper, &[PadEvent::StickY(-0.2)]).is_empty());
+        assert!(tick(&mut mapper, 1000).is_empty());
+        // and it has to lean all the way again to fire
+        assert!(feed(&mut mapper, &[PadEvent::StickY(-0.4)]).is_empty());
+    }
+}
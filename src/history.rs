@@ -0,0 +1,160 @@
+use crate::game::Game;
+
+/// When taking a move back is allowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum UndoPolicy {
+    Always,
+    UnratedOnly,
+    Never,
+}
+impl UndoPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            UndoPolicy::Always => "Always",
+            UndoPolicy::UnratedOnly => "Unrated games only",
+            UndoPolicy::Never => "Never",
+        }
+    }
+
+    pub fn allows(self, rated: bool) -> bool {
+        match self {
+            UndoPolicy::Always => true,
+            UndoPolicy::UnratedOnly => !rated,
+            UndoPolicy::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UndoError {
+    NotAllowed,
+    NothingToUndo,
+    NothingToRedo,
+}
+impl std::fmt::Display for UndoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            UndoError::NotAllowed => "undo is not allowed in this game",
+            UndoError::NothingToUndo => "there is no move to take back",
+            UndoError::NothingToRedo => "there is no move to replay",
+        };
+        f.write_str(reason)
+    }
+}
+impl std::error::Error for UndoError {}
+
+/// Snapshots of the whole game taken around each move, so undo brings back
+/// captures, ko, the clocks and markup exactly as they were.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Game>,
+    redo: Vec<Game>,
+}
+impl History {
+    /// Call with the game as it was just before a move was played.
+    pub fn record(&mut self, before: Game) {
+        self.undo.push(before);
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, game: &mut Game) -> Result<(), UndoError> {
+        if !game.settings.undo_policy.allows(game.settings.rated) {
+            return Err(UndoError::NotAllowed);
+        }
+        let previous = self.undo.pop().ok_or(UndoError::NothingToUndo)?;
+        self.redo.push(std::mem::replace(game, previous));
+        Ok(())
+    }
+
+    pub fn redo(&mut self, game: &mut Game) -> Result<(), UndoError> {
+        if !game.settings.undo_policy.allows(game.settings.rated) {
+            return Err(UndoError::NotAllowed);
+        }
+        let next = self.redo.pop().ok_or(UndoError::NothingToRedo)?;
+        self.undo.push(std::mem::replace(game, next));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PlayerColor;
+    use crate::clock::TimeControl;
+    use crate::game::{GameSettings, Move, MoveError};
+    use std::time::Duration;
+
+    // black has just taken a ko on a game with the clocks running, each move
+    // recorded as the game window records them
+    fn ko_taken(settings: GameSettings, history: &mut History) -> Game {
+        let mut game = Game::new(GameSettings {
+            size: 9,
+            time_control: TimeControl::ByoYomi {
+                main_time: Duration::from_secs(600),
+                periods: 3,
+                period_time: Duration::from_secs(30),
+            },
+            ..settings
+        });
+        let points = [
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (1, 1),
+            (1, 2),
+            (3, 1),
+            (8, 8),
+            (2, 2),
+            (2, 1),
+        ];
+        for (seconds, point) in points.into_iter().enumerate() {
+            let before = game.clone();
+            game.play(Move::Play(point)).unwrap();
+            history.record(before);
+            game.tick(Duration::from_secs(seconds as u64));
+        }
+        game
+    }
+
+    #[test]
+    fn undo_then_redo_puts_everything_back() {
+        let mut history = History::default();
+        let mut game = ko_taken(GameSettings::default(), &mut history);
+        let board = game.board().clone();
+        let clock = game.clock.clone();
+        assert_eq!(game.check_play((1, 1)), Err(MoveError::Ko));
+
+        history.undo(&mut game).unwrap();
+        assert_eq!(game.board().get((1, 1)), Some(PlayerColor::White));
+        assert_eq!(game.captures(PlayerColor::Black), 0);
+        assert_eq!(game.to_move(), PlayerColor::Black);
+        assert_ne!(game.clock, clock);
+
+        history.redo(&mut game).unwrap();
+        assert_eq!(game.board(), &board);
+        assert_eq!(game.captures(PlayerColor::Black), 1);
+        assert_eq!(game.to_move(), PlayerColor::White);
+        assert_eq!(game.check_play((1, 1)), Err(MoveError::Ko));
+        assert_eq!(game.clock, clock);
+        assert_eq!(game.moves().len(), 9);
+        assert_eq!(history.redo(&mut game), Err(UndoError::NothingToRedo));
+    }
+
+    #[test]
+    fn redo_follows_the_undo_policy() {
+        let mut history = History::default();
+        let mut game = ko_taken(GameSettings::default(), &mut history);
+        history.undo(&mut game).unwrap();
+        // the game becomes rated after the undo, so the move can't come back either
+        game.settings.rated = true;
+        assert_eq!(history.redo(&mut game), Err(UndoError::NotAllowed));
+        assert_eq!(history.undo(&mut game), Err(UndoError::NotAllowed));
+
+        let settings = GameSettings {
+            undo_policy: UndoPolicy::Never,
+            ..GameSettings::default()
+        };
+        let mut game = ko_taken(settings, &mut history);
+        assert_eq!(history.undo(&mut game), Err(UndoError::NotAllowed));
+    }
+}
@@ -0,0 +1,66 @@
+//! Bouzy's 5/21 influence: every stone spreads its colour out over a few
+//! dilations, and then erosions wear it back wherever the two colours meet,
+//! leaving roughly the territory each side has walled off. It needs no
+//! engine at all, so it stands in for one when nothing better is known.
+
+use crate::board::{Board, PlayerColor};
+
+const DILATIONS: usize = 5;
+const EROSIONS: usize = 21;
+// what a stone starts out with; bigger than any dilation or erosion can change
+const STONE: i32 = 128;
+// an empty point this far from zero counts as wholly owned
+const SURE: f32 = 12.0;
+
+/// Who each point looks like belonging to, row by row, from -1 for White to
+/// 1 for Black. Stones count as alive, however hopeless they are.
+pub fn ownership(board: &Board) -> Vec<f32> {
+    let size = board.size();
+    let mut values = (0..size * size)
+        .map(|i| match board.get((i % size, i / size)) {
+            Some(PlayerColor::Black) => STONE,
+            Some(PlayerColor::White) => -STONE,
+            None => 0,
+        })
+        .collect::<Vec<_>>();
+    let neighbours = |i: usize| {
+        board
+            .neighbours((i % size, i / size))
+            .map(move |(x, y)| y * size + x)
+    };
+
+    for _ in 0..DILATIONS {
+        let before = values.clone();
+        for (i, value) in values.iter_mut().enumerate() {
+            let around = || neighbours(i).map(|n| before[n]);
+            // a point only grows towards a colour nothing of the other one touches
+            if before[i] >= 0 && around().all(|n| n >= 0) {
+                *value += around().filter(|&n| n > 0).count() as i32;
+            }
+            if before[i] <= 0 && around().all(|n| n <= 0) {
+                *value -= around().filter(|&n| n < 0).count() as i32;
+            }
+        }
+    }
+    for _ in 0..EROSIONS {
+        let before = values.clone();
+        for (i, value) in values.iter_mut().enumerate() {
+            let around = || neighbours(i).map(|n| before[n]);
+            if before[i] > 0 {
+                *value = (before[i] - around().filter(|&n| n <= 0).count() as i32).max(0);
+            } else if before[i] < 0 {
+                *value = (before[i] + around().filter(|&n| n >= 0).count() as i32).min(0);
+            }
+        }
+    }
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| match board.get((i % size, i / size)) {
+            Some(PlayerColor::Black) => 1.0,
+            Some(PlayerColor::White) => -1.0,
+            None => (value as f32 / SURE).clamp(-1.0, 1.0),
+        })
+        .collect()
+}
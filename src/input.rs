@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use winit::keyboard::{KeyCode, ModifiersState};
+
+/// Everything the keyboard (or anything else feeding `Controller`) can ask for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+pub enum Action {
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    PlaceStone,
+    Pass,
+    Undo,
+    Redo,
+    NextMove,
+    PrevMove,
+    ToggleCoordinates,
+    ToggleOwnership,
+    // turn the board on screen, leaving the game as it is
+    RotateBoard,
+    FlipBoard,
+    ResetView,
+    Quit,
+}
+impl Action {
+    /// Whether holding the key down should keep firing the action.
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::CursorUp
+                | Action::CursorDown
+                | Action::CursorLeft
+                | Action::CursorRight
+                | Action::NextMove
+                | Action::PrevMove
+        )
+    }
+}
+
+const DEFAULT_BINDINGS: &[(Action, &[&str])] = &[
+    (Action::CursorUp, &["ArrowUp", "W"]),
+    (Action::CursorDown, &["ArrowDown", "S"]),
+    (Action::CursorLeft, &["ArrowLeft", "A"]),
+    (Action::CursorRight, &["ArrowRight", "D"]),
+    (Action::PlaceStone, &["Enter", "NumpadEnter", "Space"]),
+    (Action::Pass, &["P"]),
+    (Action::Undo, &["Ctrl+Z"]),
+    (Action::Redo, &["Ctrl+Y", "Ctrl+Shift+Z"]),
+    (Action::NextMove, &["Period", "PageDown"]),
+    (Action::PrevMove, &["Comma", "PageUp"]),
+    (Action::ToggleCoordinates, &["C"]),
+    (Action::ToggleOwnership, &["O"]),
+    (Action::RotateBoard, &["R"]),
+    (Action::FlipBoard, &["F"]),
+    (Action::ResetView, &["Home"]),
+    (Action::Quit, &["Escape"]),
+];
+
+#[derive(Debug)]
+pub enum InputMapError {
+    Read(std::io::Error),
+    Parse(toml::de::Error),
+    BadBinding(String),
+}
+impl std::fmt::Display for InputMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputMapError::Read(e) => write!(f, "can't read the key bindings: {}", e),
+            InputMapError::Parse(e) => write!(f, "can't parse the key bindings: {}", e),
+            InputMapError::BadBinding(binding) => write!(f, "unknown key binding {:?}", binding),
+        }
+    }
+}
+impl std::error::Error for InputMapError {}
+
+/// A key plus the modifiers that must be held with it, e.g. "Ctrl+Shift+Z".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub key: KeyCode,
+    pub modifiers: ModifiersState,
+}
+impl std::str::FromStr for Binding {
+    type Err = InputMapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_binding = || InputMapError::BadBinding(s.to_string());
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().and_then(key_code).ok_or_else(bad_binding)?;
+        let mut modifiers = ModifiersState::empty();
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CONTROL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" | "option" => ModifiersState::ALT,
+                "super" | "cmd" | "meta" => ModifiersState::SUPER,
+                _ => return Err(bad_binding()),
+            };
+        }
+        Ok(Binding { key, modifiers })
+    }
+}
+
+fn key_code(name: &str) -> Option<KeyCode> {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0,
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    const FUNCTION_KEYS: [KeyCode; 12] = [
+        KeyCode::F1,
+        KeyCode::F2,
+        KeyCode::F3,
+        KeyCode::F4,
+        KeyCode::F5,
+        KeyCode::F6,
+        KeyCode::F7,
+        KeyCode::F8,
+        KeyCode::F9,
+        KeyCode::F10,
+        KeyCode::F11,
+        KeyCode::F12,
+    ];
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Some(LETTERS[(c.to_ascii_uppercase() as u8 - b'A') as usize]);
+        }
+        if let Some(d) = c.to_digit(10) {
+            return Some(DIGITS[d as usize]);
+        }
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(n.checked_sub(1)?).copied();
+    }
+    let key = match name {
+        "ArrowUp" | "Up" => KeyCode::ArrowUp,
+        "ArrowDown" | "Down" => KeyCode::ArrowDown,
+        "ArrowLeft" | "Left" => KeyCode::ArrowLeft,
+        "ArrowRight" | "Right" => KeyCode::ArrowRight,
+        "Enter" | "Return" => KeyCode::Enter,
+        "NumpadEnter" => KeyCode::NumpadEnter,
+        "Space" => KeyCode::Space,
+        "Escape" | "Esc" => KeyCode::Escape,
+        "Tab" => KeyCode::Tab,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Insert" => KeyCode::Insert,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Comma" => KeyCode::Comma,
+        "Period" => KeyCode::Period,
+        "Slash" => KeyCode::Slash,
+        "Semicolon" => KeyCode::Semicolon,
+        "Quote" => KeyCode::Quote,
+        "Minus" => KeyCode::Minus,
+        "Equal" => KeyCode::Equal,
+        "BracketLeft" => KeyCode::BracketLeft,
+        "BracketRight" => KeyCode::BracketRight,
+        "Backslash" => KeyCode::Backslash,
+        "Backquote" => KeyCode::Backquote,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// the file lists only the actions it changes; anything it leaves out keeps its defaults
+#[derive(serde::Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    bindings: HashMap<Action, Vec<String>>,
+}
+
+/// Which action each key combination triggers. Several bindings may lead to
+/// the same action.
+pub struct InputMap {
+    bindings: HashMap<Binding, Action>,
+}
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = InputMap {
+            bindings: HashMap::new(),
+        };
+        for (action, bindings) in DEFAULT_BINDINGS {
+            for binding in *bindings {
+                map.bind(binding.parse().unwrap(), *action);
+            }
+        }
+        map
+    }
+}
+impl InputMap {
+    /// Reads bindings from a TOML file such as
+    ///
+    /// ```toml
+    /// [bindings]
+    /// Undo = ["Ctrl+Z", "Backspace"]
+    /// Quit = ["Ctrl+Q"]
+    /// ```
+    ///
+    /// A missing file just means the defaults.
+    pub fn load(path: &str) -> Result<Self, InputMapError> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(InputMap::default()),
+            Err(e) => return Err(InputMapError::Read(e)),
+        };
+        let file: KeysFile = toml::from_str(&text).map_err(InputMapError::Parse)?;
+
+        let mut map = InputMap::default();
+        for (action, bindings) in file.bindings {
+            map.bindings.retain(|_, bound| *bound != action);
+            for binding in bindings {
+                map.bind(binding.parse()?, action);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn bind(&mut self, binding: Binding, action: Action) {
+        self.bindings.insert(binding, action);
+    }
+
+    /// Modifiers have to match exactly, so Ctrl+Z and Ctrl+Shift+Z can do different things.
+    pub fn action(&self, key: KeyCode, modifiers: ModifiersState) -> Option<Action> {
+        let modifiers = modifiers
+            & (ModifiersState::CONTROL
+                | ModifiersState::SHIFT
+                | ModifiersState::ALT
+                | ModifiersState::SUPER);
+        self.bindings.get(&Binding { key, modifiers }).copied()
+    }
+}
@@ -0,0 +1,216 @@
+//! Joseki and opening libraries: a big SGF tree of corner sequences, looked
+//! up corner by corner. Each corner is read in its own frame, counting lines
+//! in from its two edges, so a sequence learned in one corner is found in all
+//! four; swapping the two edges as well covers its mirror image, which makes
+//! up the board's 8 symmetries.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::board::{Board, PlayerColor, Point};
+use crate::game::{Game, Move};
+use crate::sgf::{self, SgfError};
+
+#[derive(Debug)]
+pub enum JosekiError {
+    Io(std::io::Error),
+    Sgf(SgfError),
+}
+impl std::fmt::Display for JosekiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JosekiError::Io(e) => write!(f, "{}", e),
+            JosekiError::Sgf(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl std::error::Error for JosekiError {}
+impl From<std::io::Error> for JosekiError {
+    fn from(e: std::io::Error) -> Self {
+        JosekiError::Io(e)
+    }
+}
+impl From<SgfError> for JosekiError {
+    fn from(e: SgfError) -> Self {
+        JosekiError::Sgf(e)
+    }
+}
+
+// which edges a corner sits on: whether its columns and its rows count in from the far side
+const CORNERS: [(bool, bool); 4] = [(false, false), (true, false), (false, true), (true, true)];
+
+// a corner's stones in its own frame, in order
+type CornerKey = Vec<(u8, u8, PlayerColor)>;
+
+/// A move the library knows in a matching corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Continuation {
+    pub point: Point,
+    pub color: PlayerColor,
+}
+
+/// Every corner position in a library and the moves it goes on with.
+pub struct Library {
+    size: usize,
+    positions: HashMap<CornerKey, Vec<(u8, u8, PlayerColor)>>,
+}
+impl Library {
+    pub fn open(path: impl AsRef<Path>) -> Result<Library, JosekiError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Library::from_sgf(&sgf::parse(&text)?)?)
+    }
+
+    /// Reads every line of every tree; a line with an illegal move is kept up to it.
+    pub fn from_sgf(trees: &[sgf::GameTree]) -> Result<Library, SgfError> {
+        let root = trees
+            .first()
+            .and_then(|tree| tree.nodes.first())
+            .ok_or(SgfError::NoGame)?;
+        let start = Game::from_sgf(&sgf::GameTree {
+            nodes: vec![root.clone()],
+            variations: Vec::new(),
+        })?;
+        let mut library = Library {
+            size: start.settings.size,
+            positions: HashMap::new(),
+        };
+        for tree in trees {
+            library.add_line(&start, &tree.nodes[1..], &tree.variations)?;
+        }
+        Ok(library)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// How many corner positions have known continuations.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn add_line(
+        &mut self,
+        game: &Game,
+        nodes: &[sgf::Node],
+        variations: &[sgf::GameTree],
+    ) -> Result<(), SgfError> {
+        let Some((node, rest)) = nodes.split_first() else {
+            for variation in variations {
+                self.add_line(game, &variation.nodes, &variation.variations)?;
+            }
+            return Ok(());
+        };
+        let played = [("B", PlayerColor::Black), ("W", PlayerColor::White)]
+            .into_iter()
+            .find_map(|(ident, color)| node.first(ident).map(|value| (color, value)));
+        let Some((color, value)) = played else {
+            return self.add_line(game, rest, variations);
+        };
+        // passes and tenuki leave the corner as it is
+        let Some(point) = sgf::decode_point(value, self.size) else {
+            return self.add_line(game, rest, variations);
+        };
+
+        let mut next = game.clone();
+        if next.play_as(color, Move::Play(point)).is_err() {
+            return Ok(());
+        }
+        let board = game.board();
+        for corner in CORNERS {
+            let Some((x, y)) = self.in_corner(point, corner) else {
+                continue;
+            };
+            let (key, mirrored) = self.corner_key(board, corner);
+            let known = self.positions.entry(key.clone()).or_default();
+            let mut learn = |x, y| {
+                if !known.contains(&(x, y, color)) {
+                    known.push((x, y, color));
+                }
+            };
+            if mirrored {
+                learn(y, x);
+            } else {
+                learn(x, y);
+            }
+            // a corner that is its own mirror image goes on the same way either side
+            if mirror(&key) == key {
+                learn(y, x);
+            }
+        }
+        self.add_line(&next, rest, variations)
+    }
+
+    // how far the corner reaches along each edge
+    fn reach(&self) -> usize {
+        self.size / 2
+    }
+
+    // where `point` is in the corner's frame, if it is in the corner at all
+    fn in_corner(&self, (x, y): Point, (flip_x, flip_y): (bool, bool)) -> Option<(u8, u8)> {
+        let x = if flip_x { self.size - 1 - x } else { x };
+        let y = if flip_y { self.size - 1 - y } else { y };
+        (x < self.reach() && y < self.reach()).then_some((x as u8, y as u8))
+    }
+
+    fn on_board(&self, (x, y): (u8, u8), (flip_x, flip_y): (bool, bool)) -> Point {
+        let (x, y) = (x as usize, y as usize);
+        (
+            if flip_x { self.size - 1 - x } else { x },
+            if flip_y { self.size - 1 - y } else { y },
+        )
+    }
+
+    // the stones in a corner, read whichever way round sorts first, and
+    // whether that was with the edges swapped
+    fn corner_key(&self, board: &Board, corner: (bool, bool)) -> (CornerKey, bool) {
+        let mut key = board
+            .stones()
+            .filter_map(|(point, color)| self.in_corner(point, corner).map(|(x, y)| (x, y, color)))
+            .collect::<Vec<_>>();
+        key.sort();
+        let mirrored = mirror(&key);
+        if mirrored < key {
+            (mirrored, true)
+        } else {
+            (key, false)
+        }
+    }
+
+    /// The library's moves in each corner of the board that it knows,
+    /// whoever's turn they would be. Boards of another size have none.
+    pub fn continuations(&self, board: &Board) -> Vec<Continuation> {
+        if board.size() != self.size {
+            return Vec::new();
+        }
+        let mut found = Vec::new();
+        for corner in CORNERS {
+            let (key, mirrored) = self.corner_key(board, corner);
+            let Some(known) = self.positions.get(&key) else {
+                continue;
+            };
+            for &(x, y, color) in known {
+                let at = if mirrored { (y, x) } else { (x, y) };
+                let point = self.on_board(at, corner);
+                if board.get(point).is_none() {
+                    found.push(Continuation { point, color });
+                }
+            }
+        }
+        found
+    }
+}
+
+// the same stones with the corner's two edges swapped
+fn mirror(key: &CornerKey) -> CornerKey {
+    let mut mirrored = key
+        .iter()
+        .map(|&(x, y, color)| (y, x, color))
+        .collect::<Vec<_>>();
+    mirrored.sort();
+    mirrored
+}
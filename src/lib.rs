@@ -0,0 +1,17 @@
+//! The rules, clocks, game records and database, network protocol, computer
+//! player, analysis and problems, shared by the windowed game and the headless server.
+
+pub mod ai;
+pub mod analysis;
+pub mod board;
+pub mod clock;
+pub mod database;
+pub mod game;
+pub mod history;
+pub mod influence;
+pub mod joseki;
+pub mod net;
+#[cfg(feature = "nn")]
+pub mod nn;
+pub mod sgf;
+pub mod tsumego;
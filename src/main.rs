@@ -1,27 +1,47 @@
+use board::{PlayerColor, Point, Symmetry};
 use bytemuck::bytes_of;
 use camera::Camera;
 use camera::CameraUniform;
-use cgmath::InnerSpace;
+use game::{Game, GameSettings, Markup, Move};
+#[cfg(feature = "nn")]
+use go::nn;
+use go::{
+    ai, analysis, board, clock, database, game, history, influence, joseki, net, sgf, tsumego,
+};
+use history::History;
+use input::{Action, InputMap};
+use net::{ChatLine, Message, NetError, NetEvent, Peer, Role};
+use shape::ShapeRenderer;
 use std::fs::OpenOptions;
+use std::net::TcpListener;
 use std::sync::Arc;
+use text::{Anchor, TextRenderer};
 use timer::Timer;
+use touch::{TouchGesture, TouchTracker};
+use ui::{MarkerStyle, MoveNumbers, Study, Ui, UiAction};
 use vertex::{BasicVertex, EffectVertex, Vertex};
 use wgpu::util::DeviceExt;
 use wgpu::Surface;
 use winit::application::ApplicationHandler;
-use winit::event::{KeyEvent, WindowEvent};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::keyboard::{Key, NamedKey};
 use winit::window::{Window, WindowId};
-// use game:Game;
 
 mod camera;
 mod controller;
 mod cube;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod input;
+mod shape;
+mod text;
 mod texture;
 mod timer;
+mod touch;
+mod ui;
 mod vertex;
-// mod game;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
 const BACKGROUND_QUAD: &[BasicVertex] = &[
     BasicVertex {
@@ -68,6 +88,19 @@ const BOARD_LINE_THICKNESS_PX: u16 = 5;
 const BOARD_SQUARE_SIZE_PX: u16 = 99;
 const WIDTH: u32 = 600;
 const HEIGHT: u32 = 600;
+// the board texture is drawn with a 19x19 grid, other sizes get theirs drawn on top
+const TEXTURE_BOARD_LINES: usize = 19;
+const MAX_BOARD_LINES: usize = 25;
+const COORDINATE_TEXT_PX: f32 = 11.0;
+const BOARD_COLOR: [f32; 4] = [0.85, 0.68, 0.40, 1.0];
+const GRID_LINE_COLOR: [f32; 4] = [0.1, 0.08, 0.05, 1.0];
+const KEYS_PATH: &str = "keys.toml";
+// how the player has done on each problem they have tried
+const PROGRESS_PATH: &str = "tsumego-progress.json";
+const DATABASE_PATH: &str = "games-index.json";
+const BOARD_CURSOR_COLOR: [f32; 4] = [0.85, 0.12, 0.1, 1.0];
+// how long to wait between attempts to get back into a server game
+const RECONNECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
 // ///////
 // programatically generate pixel vals for stone quads
@@ -81,30 +114,219 @@ fn game_space_to_px(x: u16, y: u16) -> (u32, u32) {
     )
 }
 
-enum PlayerColor {
-    Black,
-    White,
+// first and last line of the 19x19 grid on the board texture; every board
+// size is spread over the same span
+fn grid_span_px() -> (f32, f32) {
+    let first = MARGIN_OFFSET_PIXELS as f32 + BOARD_LINE_THICKNESS_PX as f32 / 2.0;
+    let step = (BOARD_SQUARE_SIZE_PX + BOARD_LINE_THICKNESS_PX) as f32;
+    (first, first + step * (TEXTURE_BOARD_LINES - 1) as f32)
 }
+
+fn grid_spacing_px(lines: usize) -> f32 {
+    let (first, last) = grid_span_px();
+    (last - first) / (lines - 1) as f32
+}
+
+// centre of the line crossing at `point` on the board texture
+fn intersection_px((x, y): Point, lines: usize) -> (f32, f32) {
+    let first = grid_span_px().0;
+    let step = grid_spacing_px(lines);
+    (first + x as f32 * step, first + y as f32 * step)
+}
+
+// where a point of the game is drawn, with the board turned to `view`
+fn point_px(point: Point, lines: usize, view: Symmetry) -> (f32, f32) {
+    intersection_px(view.apply(point, lines), lines)
+}
+
+fn nearest_intersection(px: (f32, f32), lines: usize) -> Option<Point> {
+    let first = grid_span_px().0;
+    let step = grid_spacing_px(lines);
+    let x = ((px.0 - first) / step).round();
+    let y = ((px.1 - first) / step).round();
+    if x < 0.0 || y < 0.0 || x >= lines as f32 || y >= lines as f32 {
+        return None;
+    }
+    let point = (x as usize, y as usize);
+    let (cx, cy) = intersection_px(point, lines);
+    // ignore clicks that land between two lines
+    if (px.0 - cx).abs() > step * 0.45 || (px.1 - cy).abs() > step * 0.45 {
+        return None;
+    }
+    Some(point)
+}
+
+// the board quad fills the board viewport, so board pixels scale straight to viewport pixels
+fn board_px_to_window(px: (f32, f32), window_size: (u32, u32)) -> [f32; 2] {
+    [
+        px.0 * window_size.0 as f32 / BOARD_PIXELS as f32,
+        px.1 * window_size.1 as f32 / BOARD_PIXELS as f32,
+    ]
+}
+
+fn board_px_to_ndc(px: (f32, f32)) -> cgmath::Vector3<f32> {
+    cgmath::Vector3::new(
+        px.0 / BOARD_PIXELS as f32 * 2.0 - 1.0,
+        1.0 - px.1 / BOARD_PIXELS as f32 * 2.0,
+        0.0,
+    )
+}
+
 struct StoneInstance {
     game_pos: [usize; 2],
     position: cgmath::Vector3<f32>,
     player_color: PlayerColor,
+    marker: Option<MarkerStyle>,
+    in_atari: bool,
+    // drawn round the stone's group, if at all
+    outline: Option<[f32; 4]>,
+    // which of the eight points around are in the same group, as the LINK_ bits in stone.wgsl
+    links: u32,
+}
+impl StoneInstance {
+    fn to_raw(&self, radius: f32) -> StoneInstanceRaw {
+        let mut color = stone_color(self.player_color);
+        if self.in_atari {
+            for (channel, red) in color.iter_mut().zip(ATARI_COLOR).take(3) {
+                *channel = *channel * (1.0 - ATARI_TINT) + red * ATARI_TINT;
+            }
+        }
+        // matches the marker constants in stone.wgsl
+        let marker = match self.marker {
+            None => 0,
+            Some(MarkerStyle::Circle) => 1,
+            Some(MarkerStyle::Dot) => 2,
+        };
+        StoneInstanceRaw {
+            position: self.position.into(),
+            radius: [radius, radius],
+            color,
+            marker,
+            outline: self.outline.unwrap_or([0.0; 4]),
+            links: self.links,
+        }
+    }
+}
+
+fn stone_color(player_color: PlayerColor) -> [f32; 4] {
+    match player_color {
+        PlayerColor::Black => [0.08, 0.08, 0.09, 1.0],
+        PlayerColor::White => [0.93, 0.93, 0.90, 1.0],
+    }
+}
+
+// text drawn on a stone has to stand out against the stone's colour
+fn contrasting_text_color(player_color: PlayerColor) -> [f32; 4] {
+    match player_color {
+        PlayerColor::Black => [0.95, 0.95, 0.95, 1.0],
+        PlayerColor::White => [0.05, 0.05, 0.05, 1.0],
+    }
+}
+const LAST_MOVE_TEXT_COLOR: [f32; 4] = [0.85, 0.12, 0.1, 1.0];
+const ATARI_COLOR: [f32; 4] = [0.9, 0.1, 0.08, 1.0];
+// how far a stone in atari is turned towards ATARI_COLOR
+const ATARI_TINT: f32 = 0.45;
+const GROUP_OUTLINE_COLOR: [f32; 4] = [0.2, 0.55, 0.95, 0.9];
+// groups with this many liberties or fewer get their count shown
+const FEW_LIBERTIES: usize = 3;
+
+// how many of the engine's candidate moves are drawn on the board at most
+const MAX_CANDIDATES: usize = 8;
+const BEST_CANDIDATE_COLOR: [f32; 4] = [0.3, 0.7, 0.95, 0.85];
+// rings on the library's known moves, in the colour of whoever plays them
+const JOSEKI_BLACK_COLOR: [f32; 4] = [0.05, 0.05, 0.05, 0.8];
+const JOSEKI_WHITE_COLOR: [f32; 4] = [0.97, 0.97, 0.95, 0.9];
+const SEARCH_REGION_COLOR: [f32; 4] = [0.95, 0.55, 0.1, 0.9];
+// how solid the discs on moves played next in the games found are
+const FOUND_ALPHA: f32 = 0.7;
+// how dark a point that surely belongs to one side is shaded
+const OWNERSHIP_ALPHA: f32 = 0.6;
+
+// green for moves about as good as the best, through yellow to red as they lose more
+fn candidate_color(winrate_loss: f32) -> [f32; 4] {
+    let t = (winrate_loss / 0.1).clamp(0.0, 1.0);
+    [0.35 + 0.6 * t, 0.85 - 0.5 * t, 0.3, 0.85]
+}
+
+// visit counts as they fit on a point, e.g. "850" or "12k"
+fn short_count(n: u32) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1000..=9999 => format!("{:.1}k", n as f32 / 1000.0),
+        _ => format!("{}k", n / 1000),
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StoneInstanceRaw {
+    position: [f32; 3],
+    radius: [f32; 2],
+    color: [f32; 4],
+    marker: u32,
+    outline: [f32; 4],
+    links: u32,
+}
+impl Vertex for StoneInstanceRaw {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<StoneInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
 }
-// impl StoneInstance {
-//     fn to_raw(&self) ->
-// }
-// }
 
 struct GameCursor {
     x: f64,
     y: f64,
 }
 
+/// The seat a server is holding for this window should the connection drop.
+struct Session {
+    address: String,
+    token: String,
+    grace: std::time::Duration,
+    pause_clocks: bool,
+}
+
 #[derive(Default)]
 struct App {
     window: Option<Arc<Window>>,
     instance: Option<wgpu::Instance>,
     surface: Option<Surface<'static>>,
+    surface_config: Option<wgpu::SurfaceConfiguration>,
     device: Option<wgpu::Device>,
     queue: Option<wgpu::Queue>,
 
@@ -115,6 +337,7 @@ struct App {
     camera: Option<Camera>,
     camera_buffer: Option<wgpu::Buffer>,
     camera_bind_group: Option<wgpu::BindGroup>,
+    camera_bind_group_layout: Option<wgpu::BindGroupLayout>,
 
     // main texture
     main_texture_render_pipeline: Option<wgpu::RenderPipeline>,
@@ -132,97 +355,214 @@ struct App {
     stone_bind_group: Option<wgpu::BindGroup>,
 
     stone_instance_buffer: Option<wgpu::Buffer>,
+    stone_instance_count: u32,
+
+    // text
+    surface_size: (u32, u32),
+    text_renderer: Option<TextRenderer>,
 
-    // game: Game,
+    // the board is drawn into a square viewport of this many pixels, left of the side panel
+    board_side: u32,
+    grid_renderer: Option<ShapeRenderer>,
+    // the ownership heatmap, between the grid and the stones
+    ownership_renderer: Option<ShapeRenderer>,
+    // markup is drawn over the stones, so it can't share the grid's batch
+    markup_renderer: Option<ShapeRenderer>,
+    ui: Option<Ui>,
+
+    game: Game,
+    history: History,
+    touch: TouchTracker,
+    // where the cursor was when the board was last moved by a middle-button drag
+    pan_drag: Option<[f64; 2]>,
+    // where a tapped stone is waiting to be confirmed by a second tap
+    touch_preview: Option<Point>,
+    // an earlier position being looked at with NextMove/PrevMove; play resumes at the end
+    review: Option<Game>,
+    // keyboard players move this instead of the mouse; hidden until a key is used
+    board_cursor: Option<Point>,
+    // the other player in a network game, and the colour played from this window
+    peer: Option<Peer>,
+    local_color: Option<PlayerColor>,
+    // while hosting: where the opponent, then any spectators, connect
+    listener: Option<TcpListener>,
+    spectators: Vec<Peer>,
+    // said during the current game; saved with it as SGF comments
+    chat: Vec<ChatLine>,
+    connected_to: Option<String>,
+    session: Option<Session>,
+    // when the connection dropped and when to try again, while getting back in
+    lost_at: Option<web_time::Instant>,
+    retry_at: Option<web_time::Instant>,
+    // the server stopped the clocks while a player is away
+    clocks_paused: bool,
+    // the built-in opponent in an offline game, and its search while it's thinking
+    computer: Option<ai::Computer>,
+    search: Option<ai::Search>,
+    // the network the computer searches with, loaded for the current board size
+    #[cfg(feature = "nn")]
+    guide: Option<Arc<nn::Network>>,
+    // the engine reviewing the game, what it has made of each position so
+    // far, and the game those positions came from
+    analyzer: Option<analysis::Analyzer>,
+    analyses: Vec<Option<analysis::Analysis>>,
+    analysed: Game,
+    // the problem collection being worked through, which answers the player's moves
+    trainer: Option<tsumego::Trainer>,
+    // the joseki library whose known moves are marked in each corner
+    joseki: Option<joseki::Library>,
+    // the game database, any indexing or search running on it, and the last search
+    database: Option<Arc<database::Database>>,
+    database_task: Option<database::Task>,
+    found: Option<database::SearchResult>,
+    // the part of the board to search for, if not all of it; while it is being
+    // picked out, the corner clicked first once there is one
+    search_region: Option<(Point, Point)>,
+    picking_region: Option<Option<Point>>,
 
     // player
     // cube_position: Option<cgmath::Vector3<f32>>,
 
     // controller
     controller: controller::Controller,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
+    // hands the GPU back to the event loop once the browser has found one
+    #[cfg(target_arch = "wasm32")]
+    proxy: Option<winit::event_loop::EventLoopProxy<Gpu>>,
 }
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        ///// window
-        self.window = Some(Arc::new(
-            event_loop
-                .create_window(
-                    Window::default_attributes()
-                        // .with_decorations(false)
-                        .with_inner_size(winit::dpi::LogicalSize::new(WIDTH, HEIGHT))
-                        // .with_position(winit::dpi::LogicalPosition::new(x, y))
-                        .with_transparent(true), // .with_window_level(WindowLevel::AlwaysOnTop),
-                )
-                .unwrap(),
-        ));
+#[cfg(not(target_arch = "wasm32"))]
+const BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+// WebGL2 for browsers without WebGPU
+#[cfg(target_arch = "wasm32")]
+const BACKENDS: wgpu::Backends = wgpu::Backends::BROWSER_WEBGPU.union(wgpu::Backends::GL);
 
-        self.instance = Some(wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            flags: wgpu::InstanceFlags::empty(),
-            ..Default::default()
-        }));
-        self.surface = Some(
-            self.instance
-                .as_ref()
-                .unwrap()
-                .create_surface(self.window.clone().unwrap())
-                .unwrap(),
-        );
+/// Everything `setup` needs that has to be waited for. Native builds block on
+/// it; in the browser it arrives later as a user event.
+struct Gpu {
+    instance: wgpu::Instance,
+    surface: Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    alpha_mode: wgpu::CompositeAlphaMode,
+    board_png: Vec<u8>,
+}
 
-        let adapter = pollster::block_on(self.instance.as_ref().unwrap().request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: self.surface.as_ref(),
-                force_fallback_adapter: false,
-            },
-        ))
+async fn request_gpu(window: Arc<Window>) -> Gpu {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: BACKENDS,
+        flags: wgpu::InstanceFlags::empty(),
+        ..Default::default()
+    });
+    let surface = instance.create_surface(window).unwrap();
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        })
+        .await
         .unwrap();
-        let device_queue = pollster::block_on(adapter.request_device(
+    let required_limits = if cfg!(target_arch = "wasm32") {
+        wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+    } else {
+        wgpu::Limits::default()
+    };
+    let (device, queue) = adapter
+        .request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("device-descriptor"),
                 required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
+                required_limits,
                 ..Default::default()
             },
             None,
-        ))
+        )
+        .await
         .unwrap();
 
-        self.device = Some(device_queue.0);
-        self.queue = Some(device_queue.1);
+    // Bgra8UnormSrgb wherever it's offered; WebGL only has RGBA
+    let capabilities = surface.get_capabilities(&adapter);
+    let format = capabilities
+        .formats
+        .iter()
+        .copied()
+        .find(|&f| f == wgpu::TextureFormat::Bgra8UnormSrgb)
+        .or_else(|| capabilities.formats.iter().copied().find(|f| f.is_srgb()))
+        .unwrap_or(capabilities.formats[0]);
+    let alpha_mode = if capabilities
+        .alpha_modes
+        .contains(&wgpu::CompositeAlphaMode::PostMultiplied)
+    {
+        wgpu::CompositeAlphaMode::PostMultiplied
+    } else {
+        capabilities.alpha_modes[0]
+    };
 
-        let texture_format = wgpu::TextureFormat::Bgra8UnormSrgb;
+    #[cfg(target_arch = "wasm32")]
+    let board_png = web::load_binary("board.png").await.unwrap();
+    #[cfg(not(target_arch = "wasm32"))]
+    let board_png = include_bytes!("../res/board.png").to_vec();
 
-        self.set_camera(Camera {
-            eye: (8.4, 25.0, -8.4).into(),
-            target: (0.0, 0.0, 0.0).into(),
-            up: (0.0, 1.0, 0.0).into(),
-            aspect: WIDTH as f32 / HEIGHT as f32,
-            fovy: 90.0,
-            znear: 0.1,
-            zfar: 100.0,
-        });
+    Gpu {
+        instance,
+        surface,
+        device,
+        queue,
+        format,
+        alpha_mode,
+        board_png,
+    }
+}
+
+impl App {
+    /// Builds the pipelines and everything else that needs the device.
+    fn setup(&mut self, gpu: Gpu) {
+        let Gpu {
+            instance,
+            surface,
+            device,
+            queue,
+            format: texture_format,
+            alpha_mode,
+            board_png,
+        } = gpu;
+        self.instance = Some(instance);
+        self.surface = Some(surface);
+        self.device = Some(device);
+        self.queue = Some(queue);
+
+        self.set_camera(Camera::default());
 
         let size = self.window.as_ref().unwrap().inner_size();
-        self.surface.as_ref().unwrap().configure(
-            &self.device.as_ref().unwrap(),
-            &wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                // not really sure what the TextureFormat is
-                format: texture_format,
-                width: size.width,
-                height: size.height,
-                present_mode: wgpu::PresentMode::Fifo,
-                desired_maximum_frame_latency: 1,
-                alpha_mode: wgpu::CompositeAlphaMode::PostMultiplied,
-                // alpha_mode: wgpu::CompositeAlphaMode::Opaque,
-                view_formats: vec![wgpu::TextureFormat::Bgra8UnormSrgb],
-            },
-        );
+        self.surface_config = Some(wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // not really sure what the TextureFormat is
+            format: texture_format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 1,
+            alpha_mode,
+            view_formats: vec![texture_format],
+        });
+        self.resize(size);
 
         ////// controller
-        self.controller.velocity = 0.5; // = controller::Controller::new(0.5);
+        self.controller.input_map = match InputMap::load(KEYS_PATH) {
+            Ok(input_map) => input_map,
+            Err(e) => {
+                println!("{}; using the default keys", e);
+                InputMap::default()
+            }
+        };
+        #[cfg(feature = "gamepad")]
+        {
+            self.gamepads = gamepad::Gamepads::new();
+        }
 
         // /////////
         // stones
@@ -297,7 +637,7 @@ impl ApplicationHandler for App {
                 .unwrap()
                 .create_shader_module(wgpu::ShaderModuleDescriptor {
                     label: Some("stone shader"),
-                    source: wgpu::ShaderSource::Wgsl(include_str!("stone.Wgsl").into()),
+                    source: wgpu::ShaderSource::Wgsl(include_str!("stone.wgsl").into()),
                 });
         let background_texture_bind_group_layout =
             &self.device.as_ref().unwrap().create_bind_group_layout(
@@ -329,7 +669,10 @@ impl ApplicationHandler for App {
                 .unwrap()
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("cube pipeline layout"),
-                    bind_group_layouts: &[background_texture_bind_group_layout],
+                    bind_group_layouts: &[
+                        background_texture_bind_group_layout,
+                        self.camera_bind_group_layout.as_ref().unwrap(),
+                    ],
                     push_constant_ranges: &[],
                 });
         self.main_texture_render_pipeline =
@@ -390,7 +733,10 @@ impl ApplicationHandler for App {
         let stone_render_pipeline_layout = &self.device.as_ref().unwrap().create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("stone pipeline layout"),
-                bind_group_layouts: &[stone_bind_group_layout],
+                bind_group_layouts: &[
+                    stone_bind_group_layout,
+                    self.camera_bind_group_layout.as_ref().unwrap(),
+                ],
                 push_constant_ranges: &[],
             },
         );
@@ -402,7 +748,7 @@ impl ApplicationHandler for App {
                     module: &stone_shader,
                     entry_point: Some("vs_main"),
                     compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    buffers: &[EffectVertex::desc()],
+                    buffers: &[EffectVertex::desc(), StoneInstanceRaw::desc()],
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &stone_shader,
@@ -449,11 +795,10 @@ impl ApplicationHandler for App {
         //     },
         // ));
 
-        let main_texture_diffuse_bytes = include_bytes!("../res/board.png");
         let main_texture = texture::Texture::from_bytes(
             &self.device.as_ref().unwrap(),
             &self.queue.as_ref().unwrap(),
-            main_texture_diffuse_bytes,
+            &board_png,
             "background image",
             false,
         )
@@ -475,39 +820,135 @@ impl ApplicationHandler for App {
             },
         ));
 
+        self.stone_instance_buffer = Some(self.device.as_ref().unwrap().create_buffer(
+            &wgpu::BufferDescriptor {
+                label: Some("stone instance buffer"),
+                size: (MAX_BOARD_LINES * MAX_BOARD_LINES * std::mem::size_of::<StoneInstanceRaw>())
+                    as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        ));
+
+        self.grid_renderer = Some(ShapeRenderer::new(
+            self.device.as_ref().unwrap(),
+            texture_format,
+            (self.board_side, self.board_side),
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        ));
+        self.ownership_renderer = Some(ShapeRenderer::new(
+            self.device.as_ref().unwrap(),
+            texture_format,
+            (self.board_side, self.board_side),
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        ));
+        self.markup_renderer = Some(ShapeRenderer::new(
+            self.device.as_ref().unwrap(),
+            texture_format,
+            (self.board_side, self.board_side),
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        ));
+        self.text_renderer = Some(TextRenderer::new(
+            self.device.as_ref().unwrap(),
+            self.queue.as_ref().unwrap(),
+            texture_format,
+            (self.board_side, self.board_side),
+            self.camera_bind_group_layout.as_ref().unwrap(),
+        ));
+        self.ui = Some(Ui::new(
+            self.window.as_ref().unwrap(),
+            self.device.as_ref().unwrap(),
+            texture_format,
+        ));
+
         // initial redraw request
         self.window.as_ref().unwrap().request_redraw();
 
         // let stone_instance_data = self.stone_instances.iter().map(Instance::to_raw)
     }
+}
+
+impl ApplicationHandler<Gpu> for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        ///// window
+        let attributes = Window::default_attributes()
+            // .with_decorations(false)
+            .with_inner_size(winit::dpi::LogicalSize::new(
+                WIDTH + ui::SIDE_PANEL_WIDTH as u32,
+                HEIGHT,
+            ))
+            // .with_position(winit::dpi::LogicalPosition::new(x, y))
+            .with_transparent(true); // .with_window_level(WindowLevel::AlwaysOnTop),
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            let canvas = web::canvas();
+            // fill whatever room the page gave the canvas
+            let size = canvas
+                .as_ref()
+                .map(|c| (c.client_width(), c.client_height()))
+                .filter(|&(w, h)| w > 0 && h > 0);
+            let attributes = match size {
+                Some((w, h)) => {
+                    attributes.with_inner_size(winit::dpi::LogicalSize::new(w as u32, h as u32))
+                }
+                None => attributes,
+            };
+            let append = canvas.is_none();
+            attributes.with_canvas(canvas).with_append(append)
+        };
+        let window = Arc::new(event_loop.create_window(attributes).unwrap());
+        self.window = Some(window.clone());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        self.setup(pollster::block_on(request_gpu(window)));
+        #[cfg(target_arch = "wasm32")]
+        {
+            let proxy = self.proxy.clone().unwrap();
+            wasm_bindgen_futures::spawn_local(async move {
+                let gpu = request_gpu(window).await;
+                if proxy.send_event(gpu).is_err() {
+                    log::warn!("the event loop closed before the GPU was ready");
+                }
+            });
+        }
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, gpu: Gpu) {
+        self.setup(gpu);
+    }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        // in the browser, events can arrive before the GPU is ready
+        if self.ui.is_none() {
+            return;
+        }
+        if self
+            .ui
+            .as_mut()
+            .unwrap()
+            .handle_event(self.window.as_ref().unwrap(), &event)
+        {
+            return;
+        }
         if self.controller.process_events(&event) {
+            if self.controller.actions.contains(&Action::Quit) {
+                println!("Quit was pressed; stopping");
+                event_loop.exit();
+            }
             return;
         }
         match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state: winit::event::ElementState::Pressed,
-                        logical_key: Key::Named(NamedKey::Escape),
-                        ..
-                    },
-                ..
-            } => {
+            WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 event_loop.exit();
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state: winit::event::ElementState::Pressed,
-                        logical_key: Key::Named(NamedKey::Space),
-                        ..
-                    },
-                ..
-            } => {} //self.add_cube(),
+            // a browser canvas follows the page's layout, so this happens there too
+            WindowEvent::Resized(size) => self.resize(size),
+            // the side panel is sized in logical pixels, so the board's share changes
+            WindowEvent::ScaleFactorChanged { .. } => {
+                self.resize(self.window.as_ref().unwrap().inner_size())
+            }
             WindowEvent::CursorMoved {
                 device_id,
                 position,
@@ -516,6 +957,62 @@ impl ApplicationHandler for App {
                 let mut c = self.cursor.as_mut().unwrap();
                 c.x = position.x;
                 c.y = position.y;
+                if let Some(last) = self.pan_drag.replace([position.x, position.y]) {
+                    self.pan_by([position.x - last[0], position.y - last[1]]);
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => self.click_board(),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => {
+                let c = self.cursor.as_ref().unwrap();
+                self.pan_drag = (state == ElementState::Pressed).then_some([c.x, c.y]);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // a notch of the wheel zooms by a tenth; trackpads scroll in pixels
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => (position.y / 50.0) as f32,
+                };
+                let c = self.cursor.as_ref().unwrap();
+                if c.x < self.board_side as f64 && c.y < self.board_side as f64 {
+                    let center = self.window_to_view([c.x, c.y]);
+                    self.camera
+                        .as_mut()
+                        .unwrap()
+                        .zoom_about(center, 1.1f32.powf(lines));
+                }
+            }
+            WindowEvent::Touch(touch) => match self.touch.handle(&touch) {
+                Some(TouchGesture::Tap(position)) => self.tap_board(position),
+                Some(TouchGesture::PanZoom {
+                    center,
+                    delta,
+                    scale,
+                }) => {
+                    self.pan_by(delta);
+                    let center = self.window_to_view(center);
+                    self.camera
+                        .as_mut()
+                        .unwrap()
+                        .zoom_about(center, scale as f32);
+                }
+                None => {}
+            },
+            // trackpad pinch, on the platforms that report it
+            WindowEvent::PinchGesture { delta, .. } => {
+                let c = self.cursor.as_ref().unwrap();
+                let center = self.window_to_view([c.x, c.y]);
+                self.camera
+                    .as_mut()
+                    .unwrap()
+                    .zoom_about(center, 1.0 + delta as f32);
             }
 
             WindowEvent::RedrawRequested => {
@@ -535,6 +1032,11 @@ impl ApplicationHandler for App {
                         label: Some("render encoder"),
                     },
                 );
+                self.ui.as_mut().unwrap().prepare(
+                    self.device.as_ref().unwrap(),
+                    self.queue.as_ref().unwrap(),
+                    &mut encoder,
+                );
 
                 {
                     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -557,6 +1059,9 @@ impl ApplicationHandler for App {
                         occlusion_query_set: None,
                     });
 
+                    let side = self.board_side as f32;
+                    render_pass.set_viewport(0.0, 0.0, side, side, 0.0, 1.0);
+
                     //////
                     // draw board
                     render_pass.set_pipeline(&self.main_texture_render_pipeline.as_ref().unwrap());
@@ -569,6 +1074,15 @@ impl ApplicationHandler for App {
                         wgpu::IndexFormat::Uint16,
                     );
                     render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..1);
+                    let camera_bind_group = self.camera_bind_group.as_ref().unwrap();
+                    self.grid_renderer
+                        .as_ref()
+                        .unwrap()
+                        .draw(&mut render_pass, camera_bind_group);
+                    self.ownership_renderer
+                        .as_ref()
+                        .unwrap()
+                        .draw(&mut render_pass, camera_bind_group);
 
                     // // draw stones
                     render_pass.set_pipeline(&self.stone_render_pipeline.as_ref().unwrap());
@@ -578,13 +1092,36 @@ impl ApplicationHandler for App {
                         &self.timer.as_ref().unwrap().timer_bind_group,
                         &[],
                     );
+                    render_pass.set_bind_group(1, camera_bind_group, &[]);
                     render_pass
                         .set_vertex_buffer(0, self.stone_vertex_buffer.as_ref().unwrap().slice(..));
+                    render_pass.set_vertex_buffer(
+                        1,
+                        self.stone_instance_buffer.as_ref().unwrap().slice(..),
+                    );
                     render_pass.set_index_buffer(
                         self.stone_index_buffer.as_ref().unwrap().slice(..),
                         wgpu::IndexFormat::Uint16,
                     );
-                    render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..1);
+                    render_pass.draw_indexed(
+                        0..QUAD_INDICES.len() as u32,
+                        0,
+                        0..self.stone_instance_count,
+                    );
+                    self.markup_renderer
+                        .as_ref()
+                        .unwrap()
+                        .draw(&mut render_pass, camera_bind_group);
+
+                    // draw text
+                    self.text_renderer
+                        .as_ref()
+                        .unwrap()
+                        .draw(&mut render_pass, camera_bind_group);
+
+                    // the UI goes on top of everything and sets its own viewport
+                    let mut render_pass = render_pass.forget_lifetime();
+                    self.ui.as_ref().unwrap().render(&mut render_pass);
 
                     //// draw pawn
                     // render_pass.set_bind_group(0, &self.main_texture_bind_group, offsets);
@@ -607,33 +1144,12 @@ impl ApplicationHandler for App {
 }
 impl App {
     fn update(&mut self) {
-        // Update the cube's position
-        let mut x = 0.0;
-        let mut y = 0.0;
-        let mut z = 0.0;
-        if self.controller.is_up_pressed {
-            z += 1.0;
-        }
-        if self.controller.is_down_pressed {
-            z -= 1.0;
-        }
-        if self.controller.is_left_pressed {
-            x -= 1.0;
-        }
-        if self.controller.is_right_pressed {
-            x += 1.0;
-        }
-        let mut move_vector = cgmath::Vector3::new(x, y, z);
-        if move_vector.magnitude() != 0.0 {
-            move_vector = move_vector.normalize();
-        }
-        move_vector *= self.controller.velocity;
-
         match self.timer.as_mut() {
             Some(timer) => {
                 let target_fps = 1.0 / 60.0 as f64;
                 timer.elapsed = timer.start.elapsed().as_secs_f64();
-                timer.acc += timer.elapsed - timer.last;
+                let delta = timer.elapsed - timer.last;
+                timer.acc += delta;
                 timer.last = timer.elapsed;
                 // framerate stuff goes here?
                 timer.timer_uniform.t = timer.elapsed as f32;
@@ -642,70 +1158,1791 @@ impl App {
                     0,
                     &timer.timer_uniform.t.to_le_bytes(),
                 );
+                if !self.clocks_paused {
+                    self.game.tick(std::time::Duration::from_secs_f64(delta));
+                }
+                // pads feed the same action queue as the keyboard
+                #[cfg(feature = "gamepad")]
+                if let Some(gamepads) = self.gamepads.as_mut() {
+                    gamepads.poll(
+                        std::time::Duration::from_secs_f64(delta),
+                        &mut self.controller.actions,
+                    );
+                }
             }
             None => {}
         };
+        self.poll_network();
+        self.poll_computer();
+        self.poll_analysis();
+        self.poll_database();
 
         ////////
 
-        let c = self.cursor.as_ref().unwrap();
-        self.queue.as_ref().unwrap().write_buffer(
-            self.stone_vertex_buffer.as_mut().unwrap(),
-            0,
-            bytemuck::cast_slice(&[c.x, c.y].as_slice()),
+        for action in std::mem::take(&mut self.controller.actions) {
+            match action {
+                Action::CursorUp => self.move_board_cursor(0, -1),
+                Action::CursorDown => self.move_board_cursor(0, 1),
+                Action::CursorLeft => self.move_board_cursor(-1, 0),
+                Action::CursorRight => self.move_board_cursor(1, 0),
+                Action::PlaceStone => match self.board_cursor {
+                    Some(point) => self.play(Move::Play(point)),
+                    // the first press only shows where the cursor is
+                    None => self.move_board_cursor_to_centre(),
+                },
+                Action::Pass => self.play(Move::Pass),
+                Action::Undo if self.peer.is_some() => self.request_undo(),
+                Action::Undo | Action::Redo if self.trainer.is_some() => self
+                    .ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message("Press Retry to start the problem again".to_string()),
+                Action::Undo => {
+                    self.review = None;
+                    match self.history.undo(&mut self.game) {
+                        // take back the computer's reply too, or it would just play it again
+                        Ok(()) if self.computer_to_move() => {
+                            let _ = self.history.undo(&mut self.game);
+                        }
+                        Ok(()) => {}
+                        Err(e) => self
+                            .ui
+                            .as_mut()
+                            .unwrap()
+                            .show_message(format!("Can't undo: {}", e)),
+                    }
+                }
+                Action::Redo if self.peer.is_some() => self
+                    .ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message("Redo isn't available in network games".to_string()),
+                Action::Redo => {
+                    self.review = None;
+                    if let Err(e) = self.history.redo(&mut self.game) {
+                        self.ui
+                            .as_mut()
+                            .unwrap()
+                            .show_message(format!("Can't redo: {}", e));
+                    }
+                }
+                Action::NextMove => self.step_review(1),
+                Action::PrevMove => self.step_review(-1),
+                Action::ToggleCoordinates => {
+                    let display = &mut self.ui.as_mut().unwrap().display;
+                    display.coordinates = !display.coordinates;
+                }
+                Action::ToggleOwnership => {
+                    let display = &mut self.ui.as_mut().unwrap().display;
+                    display.ownership = !display.ownership;
+                }
+                Action::RotateBoard | Action::FlipBoard => {
+                    let turn = match action {
+                        Action::RotateBoard => Symmetry::ROTATE,
+                        _ => Symmetry::MIRROR,
+                    };
+                    let display = &mut self.ui.as_mut().unwrap().display;
+                    display.orientation = display.orientation.then(turn);
+                }
+                Action::ResetView => *self.camera.as_mut().unwrap() = Camera::default(),
+                // handled as soon as the key arrives, in `window_event`
+                Action::Quit => {}
+            }
+        }
+
+        let actions = self.ui.as_mut().unwrap().run(
+            self.window.as_ref().unwrap(),
+            &self.game,
+            self.review.as_ref().map(|game| game.moves().len()),
+            &self.chat,
+            Study {
+                analyses: &self.analyses,
+                trainer: self.trainer.as_ref(),
+                joseki: self.joseki.as_ref(),
+                database: self.database.as_deref(),
+                database_busy: self.database_task.is_some(),
+                region: self.search_region,
+                picking_region: self.picking_region.is_some(),
+                found: self.found.as_ref(),
+            },
         );
+        for action in actions {
+            match action {
+                UiAction::NewGame(settings, _) if self.on_server() => {
+                    self.peer
+                        .as_ref()
+                        .unwrap()
+                        .send(Message::CreateGame { settings });
+                }
+                UiAction::NewGame(settings, computer) => {
+                    self.trainer = None;
+                    match self.peer.as_ref().map(|peer| peer.role) {
+                        Some(Role::Host) => self.start_network_game(settings),
+                        Some(Role::Guest) => self
+                            .ui
+                            .as_mut()
+                            .unwrap()
+                            .show_message("Only the host can start a new game".to_string()),
+                        None => {
+                            self.game = Game::new(settings);
+                            self.history = History::default();
+                            self.review = None;
+                            self.search = None;
+                            #[cfg(feature = "nn")]
+                            self.load_guide(computer.as_ref(), settings.size);
+                            self.computer = computer;
+                        }
+                    }
+                }
+                UiAction::Pass => self.play(Move::Pass),
+                UiAction::Resign => self.play(Move::Resign),
+                UiAction::SaveSgf(path) => self.save_sgf(&path),
+                UiAction::LoadSgf(_) if self.peer.is_some() => self
+                    .ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message("Disconnect before loading a game".to_string()),
+                UiAction::LoadSgf(path) => {
+                    self.load_sgf(&path, 0);
+                }
+                UiAction::Host(port) => match net::listen(port) {
+                    Ok(listener) => {
+                        self.listener = Some(listener);
+                        self.computer = None;
+                        self.search = None;
+                        self.trainer = None;
+                        self.ui.as_mut().unwrap().network =
+                            Some(format!("Waiting on port {} for an opponent", port));
+                    }
+                    Err(e) => self
+                        .ui
+                        .as_mut()
+                        .unwrap()
+                        .show_message(format!("Can't host on port {}: {}", port, e)),
+                },
+                UiAction::Connect(address) => {
+                    self.computer = None;
+                    self.search = None;
+                    self.trainer = None;
+                    self.ui.as_mut().unwrap().network = Some(format!("Connecting to {}", address));
+                    self.peer = Some(Peer::connect(address));
+                }
+                UiAction::Disconnect => self.disconnect("Disconnected".to_string()),
+                UiAction::JoinGame(id) => self.send(Message::JoinGame { id }),
+                UiAction::WatchGame(id) => self.send(Message::WatchGame { id }),
+                UiAction::LeaveGame => {
+                    self.send(Message::LeaveGame);
+                    self.local_color = None;
+                    self.session = None;
+                }
+                UiAction::SendChat(text) => self.send_chat(text),
+                UiAction::Analyze(engine) => {
+                    self.analyses = vec![None; analysis::last_position(&self.game) + 1];
+                    self.analysed = self.game.clone();
+                    self.analyzer = Some(analysis::Analyzer::start(&self.game, engine));
+                    self.ui.as_mut().unwrap().analysing = true;
+                }
+                UiAction::StopAnalysis => {
+                    self.analyzer = None;
+                    self.ui.as_mut().unwrap().analysing = false;
+                }
+                UiAction::ShowMove(n) => self.review_move(n),
+                UiAction::OpenProblems(_) if self.peer.is_some() => self
+                    .ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message("Disconnect before opening problems".to_string()),
+                UiAction::OpenProblems(path) => self.open_problems(&path),
+                UiAction::StartProblem(index) => self.start_problem(index),
+                UiAction::CloseProblems => self.trainer = None,
+                UiAction::OpenJoseki(path) => {
+                    let message = match joseki::Library::open(&path) {
+                        Ok(library) => {
+                            let message =
+                                format!("Opened {} with {} positions", path, library.len());
+                            self.joseki = Some(library);
+                            message
+                        }
+                        Err(e) => format!("Couldn't open {}: {}", path, e),
+                    };
+                    self.ui.as_mut().unwrap().show_message(message);
+                }
+                UiAction::CloseJoseki => self.joseki = None,
+                UiAction::OpenDatabase => {
+                    self.database_task = Some(database::Task::index(DATABASE_PATH.into(), None));
+                }
+                UiAction::IndexGames(folder) => {
+                    self.database_task = Some(database::Task::index(
+                        DATABASE_PATH.into(),
+                        Some(folder.into()),
+                    ));
+                }
+                UiAction::CloseDatabase => {
+                    self.database = None;
+                    self.found = None;
+                    self.search_region = None;
+                    self.picking_region = None;
+                }
+                UiAction::PickRegion => self.picking_region = Some(None),
+                UiAction::WholeBoard => self.search_region = None,
+                UiAction::SearchGames { swap_colors } => self.search_games(swap_colors),
+                UiAction::OpenFoundGame(_) if self.peer.is_some() => self
+                    .ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message("Disconnect before loading a game".to_string()),
+                UiAction::OpenFoundGame(hit) => {
+                    let entry = self.database.as_ref().and_then(|db| db.game(hit.game));
+                    if let Some((path, tree)) = entry.map(|entry| (entry.path.clone(), entry.tree))
+                    {
+                        if self.load_sgf(&path, tree) {
+                            self.review_move(hit.move_number);
+                        }
+                    }
+                }
+                UiAction::AnswerUndo(accepted) => {
+                    self.send(Message::UndoReply { accepted });
+                    if accepted {
+                        self.undo_network_move();
+                    }
+                }
+            }
+        }
+
+        self.write_camera();
+        self.write_stone_instances();
+        self.queue_grid();
+        self.queue_board_coordinates();
+        self.queue_ownership();
+        self.queue_markup();
+        self.queue_joseki();
+        self.queue_search_region();
+        self.queue_found();
+        self.queue_analysis();
+        self.queue_board_cursor();
+        self.grid_renderer
+            .as_mut()
+            .unwrap()
+            .prepare(self.queue.as_ref().unwrap());
+        self.ownership_renderer
+            .as_mut()
+            .unwrap()
+            .prepare(self.queue.as_ref().unwrap());
+        self.markup_renderer
+            .as_mut()
+            .unwrap()
+            .prepare(self.queue.as_ref().unwrap());
+        self.text_renderer
+            .as_mut()
+            .unwrap()
+            .prepare(self.queue.as_ref().unwrap());
     }
 
-    fn set_camera(&mut self, camera: Camera) {
-        let mut camera_uniform = CameraUniform::new();
-        camera_uniform.update_view_proj(&camera);
+    fn play(&mut self, mv: Move) {
+        if self.reviewing() {
+            return;
+        }
+        if self.lost_at.is_some() {
+            self.ui
+                .as_mut()
+                .unwrap()
+                .show_message("Wait until the connection is back".to_string());
+            return;
+        }
+        if self.computer_to_move() {
+            self.ui
+                .as_mut()
+                .unwrap()
+                .show_message("The computer is thinking".to_string());
+            return;
+        }
+        // a player can resign whoever's turn it is
+        if let (Move::Resign, Some(color), Some(_)) = (mv, self.local_color, &self.peer) {
+            self.resign(color);
+            return;
+        }
+        if self.peer.is_some() && self.local_color != Some(self.game.to_move()) {
+            let message = match self.local_color {
+                Some(_) => "Waiting for your opponent to move",
+                None => "You aren't playing in this game",
+            };
+            self.ui.as_mut().unwrap().show_message(message.to_string());
+            return;
+        }
+        if self.trainer.is_some() {
+            self.play_problem(mv);
+            return;
+        }
+        // the server checks the move and sends it back to everyone, this window included
+        if self.on_server() {
+            self.send(Message::Move { mv });
+            self.touch_preview = None;
+            return;
+        }
+        let before = self.game.clone();
+        match self.game.play(mv) {
+            Ok(_) => {
+                self.history.record(before);
+                self.touch_preview = None;
+                let clock = Message::Clock {
+                    players: self.game.clock.players,
+                };
+                self.send(Message::Move { mv });
+                self.send(clock.clone());
+                self.tell_spectators(Message::Move { mv });
+                self.tell_spectators(clock);
+            }
+            Err(e) => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Illegal move: {}", e)),
+        }
+    }
 
-        self.camera_buffer = Some(self.device.as_ref().unwrap().create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Camera Buffer"),
-                contents: bytemuck::cast_slice(&[camera_uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            },
-        ));
+    // resigning in a network game, which the server checks like a move
+    fn resign(&mut self, color: PlayerColor) {
+        if self.on_server() {
+            self.send(Message::Resign { color });
+            return;
+        }
+        let before = self.game.clone();
+        match self.game.resign(color) {
+            Ok(()) => {
+                self.history.record(before);
+                self.send(Message::Resign { color });
+                self.tell_spectators(Message::Resign { color });
+            }
+            Err(e) => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Can't resign: {}", e)),
+        }
+    }
 
-        let camera_bind_group_layout = self.device.as_ref().unwrap().create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("camera_bind_group_layout"),
-            },
-        );
+    // the player's move in a problem, and the collection's answer to it
+    fn play_problem(&mut self, mv: Move) {
+        let trainer = self.trainer.as_mut().unwrap();
+        if trainer.status != tsumego::Status::Playing {
+            self.ui
+                .as_mut()
+                .unwrap()
+                .show_message("Press Retry to try again, or go on to another problem".to_string());
+            return;
+        }
+        let before = self.game.clone();
+        if let Err(e) = self.game.play(mv) {
+            self.ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Illegal move: {}", e));
+            return;
+        }
+        self.history.record(before);
+        self.touch_preview = None;
+        if let Some(reply) = trainer.play(mv) {
+            let before = self.game.clone();
+            if self.game.play(reply).is_ok() {
+                self.history.record(before);
+            }
+        }
+        let message = match trainer.status {
+            tsumego::Status::Playing => return,
+            tsumego::Status::Solved => "Solved!",
+            tsumego::Status::Failed => "That's not it; press Retry to try again",
+        };
+        self.ui.as_mut().unwrap().show_message(message.to_string());
+    }
 
-        self.camera_bind_group = Some(self.device.as_ref().unwrap().create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                layout: &camera_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: self.camera_buffer.as_ref().unwrap().as_entire_binding(),
-                }],
-                label: Some("camera_bind_group"),
-            },
-        ));
+    fn open_problems(&mut self, path: &str) {
+        match tsumego::Trainer::open(path, PROGRESS_PATH) {
+            Ok(trainer) => {
+                let count = trainer.problems.len();
+                self.trainer = Some(trainer);
+                self.computer = None;
+                self.search = None;
+                self.start_problem(0);
+                self.ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message(format!("Opened {} problems from {}", count, path));
+            }
+            Err(e) => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Couldn't open {}: {}", path, e)),
+        }
+    }
 
-        self.camera = Some(camera);
+    fn start_problem(&mut self, index: usize) {
+        let Some(trainer) = &mut self.trainer else {
+            return;
+        };
+        self.game = trainer.start(index);
+        self.history = History::default();
+        self.review = None;
     }
 
-    // fn draw_board(&self) {
-    //     render_pass.set_pipeline(&self.sprite_render_pipeline)
-    // }
+    #[cfg(feature = "nn")]
+    fn load_guide(&mut self, computer: Option<&ai::Computer>, size: usize) {
+        self.guide = None;
+        let Some(path) = computer.and_then(|computer| computer.network.as_ref()) else {
+            return;
+        };
+        match nn::Network::load(path, size) {
+            Ok(network) => self.guide = Some(Arc::new(network)),
+            Err(e) => self.ui.as_mut().unwrap().show_message(format!(
+                "Couldn't load {}: {}; the computer will play without it",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    fn computer_to_move(&self) -> bool {
+        self.computer
+            .as_ref()
+            .is_some_and(|computer| computer.color == self.game.to_move())
+    }
+
+    /// Collects what the engine has found, and forgets it all once the game
+    /// it was about has changed.
+    fn poll_analysis(&mut self) {
+        let changed = self.analysed.setup() != self.game.setup()
+            || self.analysed.moves() != self.game.moves();
+        if !self.analyses.is_empty() && changed {
+            self.analyzer = None;
+            self.analyses.clear();
+            self.ui.as_mut().unwrap().analysing = false;
+        }
+        let Some(analyzer) = &self.analyzer else {
+            return;
+        };
+        for event in analyzer.poll() {
+            match event {
+                analysis::AnalysisEvent::Analysed(n, analysis) => {
+                    self.analyses[n] = Some(analysis);
+                    if n + 1 == self.analyses.len() {
+                        self.analyzer = None;
+                        self.ui.as_mut().unwrap().analysing = false;
+                    }
+                }
+                analysis::AnalysisEvent::Failed(e) => {
+                    self.analyzer = None;
+                    let ui = self.ui.as_mut().unwrap();
+                    ui.analysing = false;
+                    ui.show_message(format!("Analysis stopped: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Starts the computer thinking when it's its turn, and plays its move once it has one.
+    fn poll_computer(&mut self) {
+        let Some(budget) = self.computer.as_ref().map(|computer| computer.budget) else {
+            return;
+        };
+        if !self.computer_to_move() || self.game.is_over() {
+            self.search = None;
+            return;
+        }
+        // an undo or redo since it started makes the answer useless
+        if self
+            .search
+            .as_ref()
+            .is_some_and(|search| search.moves != self.game.moves().len())
+        {
+            self.search = None;
+        }
+        let Some(search) = &self.search else {
+            #[cfg(feature = "nn")]
+            let search = match &self.guide {
+                Some(network) => ai::Search::start_guided(&self.game, budget, network.clone()),
+                None => ai::Search::start(&self.game, budget),
+            };
+            #[cfg(not(feature = "nn"))]
+            let search = ai::Search::start(&self.game, budget);
+            self.search = Some(search);
+            return;
+        };
+        let Some(mv) = search.poll() else {
+            return;
+        };
+        self.search = None;
+        let before = self.game.clone();
+        match self.game.play(mv) {
+            Ok(_) => self.history.record(before),
+            Err(e) => {
+                log::warn!("the computer chose an illegal move, {:?}: {}", mv, e);
+                self.computer = None;
+            }
+        }
+    }
+
+    fn poll_network(&mut self) {
+        if let Some(listener) = &self.listener {
+            while let Ok((stream, _)) = listener.accept() {
+                let peer = Peer::accept(stream);
+                // the first to arrive plays; anyone after that watches
+                if self.peer.is_none() {
+                    self.peer = Some(peer);
+                } else {
+                    self.spectators.push(peer);
+                }
+            }
+        }
+        let mut heard = Vec::new();
+        self.spectators.retain(|spectator| {
+            spectator.poll().into_iter().all(|event| match event {
+                NetEvent::Connected(address) => {
+                    println!("{} is watching", address);
+                    spectator.send(Message::snapshot(&self.game, None));
+                    true
+                }
+                // spectators can talk but not play
+                NetEvent::Received(Message::Chat(line)) => {
+                    heard.push(line);
+                    true
+                }
+                NetEvent::Received(_) => true,
+                NetEvent::Disconnected(e) => {
+                    println!("a spectator left: {}", e);
+                    false
+                }
+            })
+        });
+        for line in heard {
+            self.relay_chat(ChatLine {
+                from: "Spectator".to_string(),
+                move_number: self.game.moves().len(),
+                ..line
+            });
+        }
+
+        if self
+            .retry_at
+            .is_some_and(|at| at <= web_time::Instant::now())
+        {
+            self.retry_at = None;
+            let address = self.session.as_ref().unwrap().address.clone();
+            self.peer = Some(Peer::connect(address));
+        }
+
+        let Some(peer) = &self.peer else {
+            return;
+        };
+        let role = peer.role;
+        for event in peer.poll() {
+            match event {
+                NetEvent::Connected(address) => {
+                    println!("connected to {}", address);
+                    self.ui.as_mut().unwrap().network = Some(format!("Connected to {}", address));
+                    self.connected_to = Some(address);
+                    if let (Some(session), Some(_)) = (&self.session, self.lost_at) {
+                        self.send(Message::Resume {
+                            token: session.token.clone(),
+                        });
+                    } else if role == Role::Host {
+                        // the host's current settings decide the game
+                        self.start_network_game(self.game.settings);
+                    }
+                }
+                NetEvent::Received(message) => self.receive(message),
+                NetEvent::Disconnected(e) => self.connection_lost(e),
+            }
+        }
+    }
+
+    // a server game keeps trying to get back in until the server gives up the seat
+    fn connection_lost(&mut self, e: NetError) {
+        let Some(session) = &self.session else {
+            self.disconnect(format!("Connection lost: {}", e));
+            return;
+        };
+        let lost_at = *self.lost_at.get_or_insert_with(web_time::Instant::now);
+        if self.game.is_over() || lost_at.elapsed() > session.grace {
+            self.disconnect(format!("Connection lost: {}", e));
+            return;
+        }
+        self.peer = None;
+        self.retry_at = Some(web_time::Instant::now() + RECONNECT_INTERVAL);
+        self.clocks_paused = session.pause_clocks;
+        let left = session.grace.saturating_sub(lost_at.elapsed());
+        self.ui.as_mut().unwrap().network = Some(format!(
+            "Connection lost; reconnecting for another {}s",
+            left.as_secs()
+        ));
+    }
+
+    fn receive(&mut self, message: Message) {
+        match message {
+            // only a server is sent these
+            Message::Hello { .. }
+            | Message::ListGames
+            | Message::CreateGame { .. }
+            | Message::JoinGame { .. }
+            | Message::WatchGame { .. }
+            | Message::LeaveGame
+            | Message::Resume { .. } => {}
+            Message::NewGame { settings, color } => {
+                self.game = Game::new(settings);
+                self.history = History::default();
+                self.review = None;
+                self.local_color = color;
+                self.chat.clear();
+                let message = match color {
+                    Some(color) => format!("New game; you play {}", color.name()),
+                    None => "Watching".to_string(),
+                };
+                self.ui.as_mut().unwrap().show_message(message);
+            }
+            Message::Move { mv } => {
+                if !self.on_server() && self.local_color == Some(self.game.to_move()) {
+                    self.disconnect("The opponent moved out of turn".to_string());
+                    return;
+                }
+                let before = self.game.clone();
+                match self.game.play(mv) {
+                    Ok(_) => {
+                        self.history.record(before);
+                        self.tell_spectators(Message::Move { mv });
+                    }
+                    Err(e) => self.disconnect(format!("The opponent sent an illegal move: {}", e)),
+                }
+            }
+            Message::Resign { color } => {
+                if !self.on_server() && self.local_color == Some(color) {
+                    self.disconnect("The opponent resigned for you".to_string());
+                    return;
+                }
+                let before = self.game.clone();
+                match self.game.resign(color) {
+                    Ok(()) => {
+                        self.history.record(before);
+                        self.tell_spectators(Message::Resign { color });
+                    }
+                    Err(e) => self.disconnect(format!("The opponent sent an illegal move: {}", e)),
+                }
+            }
+            Message::Clock { players } => {
+                self.game.clock.players = players;
+                self.tell_spectators(Message::Clock { players });
+            }
+            Message::Snapshot {
+                settings,
+                moves,
+                players,
+                color,
+            } => {
+                let mut game = Game::new(settings);
+                let mut history = History::default();
+                for (color, mv) in moves {
+                    let before = game.clone();
+                    let played = match mv {
+                        Move::Resign => game.resign(color),
+                        mv => game.play(mv).map(|_| ()),
+                    };
+                    if let Err(e) = played {
+                        self.disconnect(format!("The game sent over doesn't replay: {}", e));
+                        return;
+                    }
+                    history.record(before);
+                }
+                game.clock.players = players;
+                self.game = game;
+                self.history = history;
+                self.review = None;
+                // a player getting their seat back keeps the chat so far, which
+                // goes into the saved game
+                if self.lost_at.is_none() || color.is_none() || color != self.local_color {
+                    self.chat.clear();
+                }
+                self.local_color = color;
+                let message = match color {
+                    Some(color) => format!("Back in the game as {}", color.name()),
+                    None => "Watching; step back through the moves with , and .".to_string(),
+                };
+                self.ui.as_mut().unwrap().show_message(message);
+                // if this was the answer to `Resume`, we're back
+                self.lost_at = None;
+                self.clocks_paused = false;
+            }
+            Message::UndoRequest => self.ui.as_mut().unwrap().undo_requested = true,
+            Message::UndoReply { accepted: true } => self.undo_network_move(),
+            Message::UndoReply { accepted: false } => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message("The opponent refused the undo".to_string()),
+            // the host says who spoke and when, whatever the guest claimed
+            Message::Chat(line) if self.listener.is_some() => self.relay_chat(ChatLine {
+                from: self
+                    .local_color
+                    .map_or("Spectator", |color| color.opponent().name())
+                    .to_string(),
+                move_number: self.game.moves().len(),
+                ..line
+            }),
+            Message::Chat(line) => self.chat.push(line),
+            Message::Lobby { games } => self.ui.as_mut().unwrap().lobby = Some(games),
+            Message::Created { id } => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Opened game #{}; waiting for an opponent", id)),
+            Message::PlayerLeft { color } => {
+                self.local_color = None;
+                self.session = None;
+                self.clocks_paused = false;
+                self.ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message(format!("{} left the game", color.name()));
+            }
+            Message::Session {
+                token,
+                grace_secs,
+                pause_clocks,
+            } => {
+                self.session = Some(Session {
+                    address: self.connected_to.clone().unwrap_or_default(),
+                    token,
+                    grace: std::time::Duration::from_secs(grace_secs),
+                    pause_clocks,
+                })
+            }
+            Message::PlayerAway {
+                color,
+                grace_secs,
+                pause_clocks,
+            } => {
+                self.clocks_paused = pause_clocks;
+                self.ui.as_mut().unwrap().show_message(format!(
+                    "{} lost their connection; their seat is kept for {}s",
+                    color.name(),
+                    grace_secs
+                ));
+            }
+            Message::PlayerBack { color } => {
+                self.clocks_paused = false;
+                self.ui
+                    .as_mut()
+                    .unwrap()
+                    .show_message(format!("{} is back", color.name()));
+            }
+            // the server no longer has a seat for us
+            Message::Rejected { reason } if self.lost_at.is_some() => self.disconnect(reason),
+            Message::Rejected { reason } => self.ui.as_mut().unwrap().show_message(reason),
+        }
+    }
+
+    // the host plays black; the guest is told the settings and its colour
+    fn start_network_game(&mut self, settings: GameSettings) {
+        self.game = Game::new(settings);
+        self.history = History::default();
+        self.review = None;
+        self.chat.clear();
+        self.local_color = Some(PlayerColor::Black);
+        self.send(Message::NewGame {
+            settings,
+            color: Some(PlayerColor::White),
+        });
+        self.tell_spectators(Message::NewGame {
+            settings,
+            color: None,
+        });
+        self.ui
+            .as_mut()
+            .unwrap()
+            .show_message("New game; you play Black".to_string());
+    }
+
+    fn request_undo(&mut self) {
+        let message = if self.local_color.is_none() {
+            "You aren't playing in this game"
+        } else if !self
+            .game
+            .settings
+            .undo_policy
+            .allows(self.game.settings.rated)
+        {
+            "Undo isn't allowed in this game"
+        } else if self.game.moves().is_empty() {
+            "There's nothing to undo"
+        } else {
+            self.peer.as_ref().unwrap().send(Message::UndoRequest);
+            "Asked the opponent to allow an undo"
+        };
+        self.ui.as_mut().unwrap().show_message(message.to_string());
+    }
+
+    // both sides take the move back once the opponent agrees
+    fn undo_network_move(&mut self) {
+        self.review = None;
+        match self.history.undo(&mut self.game) {
+            Ok(()) => self.tell_spectators(Message::UndoReply { accepted: true }),
+            Err(e) => self
+                .ui
+                .as_mut()
+                .unwrap()
+                .show_message(format!("Can't undo: {}", e)),
+        }
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(peer) = &self.peer {
+            peer.send(message);
+        }
+    }
+
+    fn send_chat(&mut self, text: String) {
+        let line = ChatLine {
+            from: self
+                .local_color
+                .map_or("Spectator", PlayerColor::name)
+                .to_string(),
+            text,
+            move_number: self.game.moves().len(),
+        };
+        // the host or server hands it back to us along with everyone else
+        if self.listener.is_some() {
+            self.relay_chat(line);
+        } else {
+            self.send(Message::Chat(line));
+        }
+    }
+
+    // while hosting, every line goes to the opponent and all spectators
+    fn relay_chat(&mut self, line: ChatLine) {
+        self.send(Message::Chat(line.clone()));
+        self.tell_spectators(Message::Chat(line.clone()));
+        self.chat.push(line);
+    }
+
+    // a hosted game is passed on as it happens to everyone watching it
+    fn tell_spectators(&self, message: Message) {
+        for spectator in &self.spectators {
+            spectator.send(message.clone());
+        }
+    }
+
+    // a server, unlike a peer, announces itself with its lobby
+    fn on_server(&self) -> bool {
+        self.ui.as_ref().unwrap().lobby.is_some()
+    }
+
+    fn disconnect(&mut self, message: String) {
+        self.peer = None;
+        self.local_color = None;
+        self.connected_to = None;
+        self.session = None;
+        self.lost_at = None;
+        self.retry_at = None;
+        self.clocks_paused = false;
+        self.listener = None;
+        self.spectators.clear();
+        let ui = self.ui.as_mut().unwrap();
+        ui.network = None;
+        ui.undo_requested = false;
+        ui.lobby = None;
+        ui.show_message(message);
+    }
+
+    fn save_sgf(&mut self, path: &str) {
+        let mut tree = self.game.to_sgf();
+        // each node's comment is what was said while it was the latest position
+        let mut comments = vec![String::new(); tree.nodes.len()];
+        for line in &self.chat {
+            if let Some(comment) = comments.get_mut(line.move_number) {
+                comment.push_str(&format!("{}: {}\n", line.from, line.text));
+            }
+        }
+        for (node, comment) in tree.nodes.iter_mut().zip(comments) {
+            if !comment.is_empty() {
+                node.push("C", comment.trim_end().to_string());
+            }
+        }
+        let message = match std::fs::write(path, tree.to_string()) {
+            Ok(()) => format!("Saved {}", path),
+            Err(e) => format!("Couldn't save {}: {}", path, e),
+        };
+        self.ui.as_mut().unwrap().show_message(message);
+    }
+
+    // loads the `tree`th game in the file, and says whether it could
+    fn load_sgf(&mut self, path: &str, tree: usize) -> bool {
+        let loaded = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| sgf::parse(&text).map_err(|e| e.to_string()))
+            .and_then(|trees| {
+                let tree = trees
+                    .get(tree)
+                    .ok_or("the game isn't in the file any more")?;
+                Game::from_sgf(tree).map_err(|e| e.to_string())
+            });
+        let done = loaded.is_ok();
+        let message = match loaded {
+            Ok(game) => {
+                self.game = game;
+                self.history = History::default();
+                self.review = None;
+                self.computer = None;
+                self.search = None;
+                self.trainer = None;
+                format!("Loaded {}", path)
+            }
+            Err(e) => format!("Couldn't load {}: {}", path, e),
+        };
+        self.ui.as_mut().unwrap().show_message(message);
+        done
+    }
+
+    fn poll_database(&mut self) {
+        let Some(event) = self.database_task.as_ref().and_then(|task| task.poll()) else {
+            return;
+        };
+        self.database_task = None;
+        let message = match event {
+            database::DatabaseEvent::Loaded(Ok((database, report))) => {
+                let mut message = format!("{} games in the database", database.len());
+                if report.games > 0 {
+                    message += &format!(", {} just added", report.games);
+                }
+                if report.failed > 0 {
+                    message += &format!("; {} files couldn't be read", report.failed);
+                }
+                self.database = Some(Arc::new(database));
+                // the games found are numbered as they were before
+                self.found = None;
+                message
+            }
+            database::DatabaseEvent::Loaded(Err(e)) => {
+                format!("Couldn't open the game database: {}", e)
+            }
+            database::DatabaseEvent::Found(found) => {
+                let message = format!("Found in {} games", found.hits.len());
+                self.found = Some(found);
+                message
+            }
+        };
+        self.ui.as_mut().unwrap().show_message(message);
+    }
+
+    // looks for the region of the board being shown, or all of it, in the database
+    fn search_games(&mut self, swap_colors: bool) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let last = game.settings.size - 1;
+        let (a, b) = self.search_region.unwrap_or(((0, 0), (last, last)));
+        let pattern = database::Pattern::new(game.board(), a, b);
+        self.found = None;
+        self.database_task = Some(database::Task::search(
+            database.clone(),
+            pattern,
+            swap_colors,
+        ));
+    }
+
+    // a click on the board while a search region is being picked out; true if it was used
+    fn pick_region_corner(&mut self, point: Point) -> bool {
+        match self.picking_region {
+            None => return false,
+            Some(None) => self.picking_region = Some(Some(point)),
+            Some(Some(first)) => {
+                self.search_region = Some((first, point));
+                self.picking_region = None;
+            }
+        }
+        true
+    }
+
+    fn move_board_cursor_to_centre(&mut self) {
+        let centre = self.game.settings.size / 2;
+        self.board_cursor = Some((centre, centre));
+    }
+
+    // true, with a note to the player, while an earlier position is on the board
+    fn reviewing(&mut self) -> bool {
+        let Some(review) = &self.review else {
+            return false;
+        };
+        let message = format!(
+            "Showing move {} of {}; step forward to the last move to play",
+            review.moves().len(),
+            self.game.moves().len()
+        );
+        self.ui.as_mut().unwrap().show_message(message);
+        true
+    }
+
+    fn step_review(&mut self, delta: i32) {
+        let last = self.game.moves().len();
+        let current = self.review.as_ref().map_or(last, |game| game.moves().len());
+        self.review_move((current as i32 + delta).max(0) as usize);
+    }
+
+    // shows the position after `n` moves, or the game itself from the last move on
+    fn review_move(&mut self, n: usize) {
+        let last = self.game.moves().len();
+        if self.review.as_ref().map(|game| game.moves().len()) == Some(n) {
+            return;
+        }
+        self.review = (n < last).then(|| self.game.replayed(n));
+    }
+
+    fn move_board_cursor(&mut self, dx: i32, dy: i32) {
+        let Some((x, y)) = self.board_cursor else {
+            self.move_board_cursor_to_centre();
+            return;
+        };
+        // the arrow keys go the way they look on the turned board
+        let (dx, dy) = self.view().inverse().apply_offset((dx, dy));
+        let last = self.game.settings.size as i32 - 1;
+        self.board_cursor = Some((
+            (x as i32 + dx).clamp(0, last) as usize,
+            (y as i32 + dy).clamp(0, last) as usize,
+        ));
+    }
+
+    // how the board is turned on screen; the game itself is never changed
+    fn view(&self) -> Symmetry {
+        self.ui.as_ref().unwrap().display.orientation
+    }
+
+    // window pixels to clip space of the board viewport, before the camera
+    fn window_to_view(&self, [x, y]: [f64; 2]) -> cgmath::Point2<f32> {
+        let side = self.board_side as f64;
+        cgmath::Point2::new((x / side * 2.0 - 1.0) as f32, (1.0 - y / side * 2.0) as f32)
+    }
+
+    // drags the board along with a pointer that moved `delta` window pixels
+    fn pan_by(&mut self, delta: [f64; 2]) {
+        let side = self.board_side as f64;
+        self.camera.as_mut().unwrap().pan(cgmath::Vector2::new(
+            (delta[0] / side * 2.0) as f32,
+            (-delta[1] / side * 2.0) as f32,
+        ));
+    }
+
+    /// The intersection under a window position, taking zoom and pan into account.
+    fn point_at(&self, position: [f64; 2]) -> Option<Point> {
+        let side = self.board_side as f64;
+        if position[0] >= side || position[1] >= side {
+            return None;
+        }
+        let board = self
+            .camera
+            .as_ref()
+            .unwrap()
+            .to_board(self.window_to_view(position));
+        let px = (
+            (board.x + 1.0) / 2.0 * BOARD_PIXELS as f32,
+            (1.0 - board.y) / 2.0 * BOARD_PIXELS as f32,
+        );
+        let lines = self.game.settings.size;
+        nearest_intersection(px, lines).map(|point| self.view().inverse().apply(point, lines))
+    }
+
+    fn click_board(&mut self) {
+        let c = self.cursor.as_ref().unwrap();
+        let Some(point) = self.point_at([c.x, c.y]) else {
+            return;
+        };
+        if self.pick_region_corner(point) {
+            return;
+        }
+        match self.markup_for_modifiers() {
+            Some(markup) => self.annotate(point, markup),
+            None if self.reviewing() => {}
+            None => self.play(Move::Play(point)),
+        }
+    }
+
+    // markup goes on the position being shown, which may be an earlier one
+    fn annotate(&mut self, point: Point, markup: Markup) {
+        let before = self.game.clone();
+        match &mut self.review {
+            Some(review) => {
+                self.game
+                    .toggle_markup_at(review.moves().len(), point, markup.clone());
+                review.toggle_markup(point, markup);
+            }
+            None => self.game.toggle_markup(point, markup),
+        }
+        // undo in network games takes back the move both sides agreed on, so
+        // only local games can undo markup
+        if self.peer.is_none() {
+            self.history.record(before);
+        }
+    }
+
+    // a first tap only shows the stone; tapping the same point again plays it,
+    // so a finger that lands a line off doesn't cost a move
+    fn tap_board(&mut self, position: [f64; 2]) {
+        let Some(point) = self.point_at(position) else {
+            self.touch_preview = None;
+            return;
+        };
+        if self.pick_region_corner(point) {
+            return;
+        }
+        if self.touch_preview == Some(point) {
+            self.touch_preview = None;
+            self.play(Move::Play(point));
+        } else {
+            self.touch_preview = Some(point);
+        }
+    }
+
+    // clicking with modifiers held annotates the board instead of playing
+    fn markup_for_modifiers(&self) -> Option<Markup> {
+        let m = self.controller.modifiers;
+        match (m.control_key(), m.alt_key(), m.shift_key()) {
+            (false, false, false) => None,
+            (false, false, true) => Some(Markup::Triangle),
+            (true, false, false) => Some(Markup::Square),
+            (false, true, false) => Some(Markup::Circle),
+            (true, false, true) => Some(Markup::Cross),
+            (false, true, true) => Some(Markup::Label(
+                self.review.as_ref().unwrap_or(&self.game).next_label(),
+            )),
+            (true, true, false) => Some(Markup::Territory(PlayerColor::Black)),
+            (true, true, true) => Some(Markup::Territory(PlayerColor::White)),
+        }
+    }
+
+    fn write_stone_instances(&mut self) {
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let lines = game.settings.size;
+        let display = self.ui.as_ref().unwrap().display;
+        let view = display.orientation;
+        let last_play = game.last_play();
+        let move_numbers = game.move_numbers();
+        let first_numbered = match display.move_numbers {
+            MoveNumbers::Off => usize::MAX,
+            MoveNumbers::Last(n) => game.moves().len().saturating_sub(n) + 1,
+            MoveNumbers::All => 1,
+        };
+        let numbered = |point: &Point| {
+            move_numbers
+                .get(point)
+                .copied()
+                .filter(|&n| n >= first_numbered)
+        };
+
+        // each instance covers its whole point so that group outlines meet up;
+        // stone.wgsl draws the stone a little smaller, nearly touching its neighbours
+        let radius = grid_spacing_px(lines) * 0.5 / BOARD_PIXELS as f32 * 2.0;
+        let board = game.board();
+        let groups = board.groups();
+        let mut group_at = vec![None; lines * lines];
+        for (i, group) in groups.iter().enumerate() {
+            for &(x, y) in &group.stones {
+                group_at[y * lines + x] = Some(i);
+            }
+        }
+        let links = |(x, y): Point| {
+            // in the order of the LINK_ bits in stone.wgsl
+            let around = [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ];
+            let on_board = 0..lines as i32;
+            around
+                .iter()
+                .enumerate()
+                .filter(|(_, &step)| {
+                    // the links are as drawn, so each step is taken on the turned board
+                    let (dx, dy) = view.inverse().apply_offset(step);
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    on_board.contains(&nx)
+                        && on_board.contains(&ny)
+                        && group_at[ny as usize * lines + nx as usize] == group_at[y * lines + x]
+                })
+                .fold(0, |bits, (i, _)| bits | 1 << i)
+        };
+        let instance_data = board
+            .stones()
+            .map(|((x, y), player_color)| {
+                let group = &groups[group_at[y * lines + x].unwrap()];
+                let in_atari = display.atari && group.liberties.len() == 1;
+                StoneInstance {
+                    game_pos: [x, y],
+                    position: board_px_to_ndc(point_px((x, y), lines, view)),
+                    player_color,
+                    // a numbered last move is picked out by its number's colour instead
+                    marker: if last_play == Some((x, y)) && numbered(&(x, y)).is_none() {
+                        display.last_move_marker
+                    } else {
+                        None
+                    },
+                    in_atari,
+                    outline: display.outlines.then_some(if in_atari {
+                        ATARI_COLOR
+                    } else {
+                        GROUP_OUTLINE_COLOR
+                    }),
+                    links: links((x, y)),
+                }
+            })
+            .map(|stone| stone.to_raw(radius))
+            .collect::<Vec<_>>();
+        self.queue.as_ref().unwrap().write_buffer(
+            self.stone_instance_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(&instance_data),
+        );
+        self.stone_instance_count = instance_data.len() as u32;
+
+        // a tapped point waiting for its second tap shows a see-through stone
+        if let Some(point) = self
+            .touch_preview
+            .filter(|&p| self.review.is_none() && self.game.board().get(p).is_none())
+        {
+            let mut ghost = StoneInstance {
+                game_pos: [point.0, point.1],
+                position: board_px_to_ndc(point_px(point, lines, view)),
+                player_color: self.game.to_move(),
+                marker: None,
+                in_atari: false,
+                outline: None,
+                links: 0,
+            }
+            .to_raw(radius);
+            ghost.color[3] = 0.5;
+            self.queue.as_ref().unwrap().write_buffer(
+                self.stone_instance_buffer.as_ref().unwrap(),
+                instance_data.len() as wgpu::BufferAddress
+                    * std::mem::size_of::<StoneInstanceRaw>() as wgpu::BufferAddress,
+                bytemuck::bytes_of(&ghost),
+            );
+            self.stone_instance_count += 1;
+        }
+
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        for (point, player_color) in game.board().stones() {
+            let Some(number) = numbered(&point) else {
+                continue;
+            };
+            // a label replaces the move number, as in printed game records
+            if let Some(Markup::Label(_)) = game.markup().get(&point) {
+                continue;
+            }
+            let label = number.to_string();
+            let size = if label.len() > 2 { 0.36 } else { 0.45 } * spacing;
+            let color = if last_play == Some(point) && display.last_move_marker.is_some() {
+                LAST_MOVE_TEXT_COLOR
+            } else {
+                contrasting_text_color(player_color)
+            };
+            text_renderer.queue_text(
+                &label,
+                board_px_to_window(point_px(point, lines, view), window_size),
+                size,
+                color,
+                Anchor::Center,
+            );
+        }
+
+        if !display.liberties {
+            return;
+        }
+        for group in groups
+            .iter()
+            .filter(|group| group.liberties.len() <= FEW_LIBERTIES)
+        {
+            // once per group, on the free stone nearest its middle
+            let count = group.stones.len() as f32;
+            let middle = group.stones.iter().fold((0.0, 0.0), |(mx, my), &(x, y)| {
+                (mx + x as f32 / count, my + y as f32 / count)
+            });
+            let distance = |&(x, y): &Point| (x as f32 - middle.0).hypot(y as f32 - middle.1);
+            let Some(point) = group
+                .stones
+                .iter()
+                .filter(|&p| numbered(p).is_none() && !game.markup().contains_key(p))
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            else {
+                continue;
+            };
+            text_renderer.queue_text(
+                &group.liberties.len().to_string(),
+                board_px_to_window(point_px(*point, lines, view), window_size),
+                spacing * 0.45,
+                contrasting_text_color(group.color),
+                Anchor::Center,
+            );
+        }
+    }
+
+    fn queue_grid(&mut self) {
+        let lines = self.game.settings.size;
+        if lines == TEXTURE_BOARD_LINES {
+            return;
+        }
+        let grid_renderer = self.grid_renderer.as_mut().unwrap();
+        let window_size = (self.board_side, self.board_side);
+        let (first, last) = grid_span_px();
+        let half_line = BOARD_LINE_THICKNESS_PX as f32 / 2.0;
+
+        // paint over the texture's own grid before drawing this size's lines
+        let pad = grid_spacing_px(TEXTURE_BOARD_LINES) / 2.0;
+        grid_renderer.queue_rect(
+            board_px_to_window((first - pad, first - pad), window_size),
+            board_px_to_window((last + pad, last + pad), window_size),
+            BOARD_COLOR,
+        );
+        for i in 0..lines {
+            let (offset, _) = intersection_px((i, 0), lines);
+            grid_renderer.queue_rect(
+                board_px_to_window((offset - half_line, first - half_line), window_size),
+                board_px_to_window((offset + half_line, last + half_line), window_size),
+                GRID_LINE_COLOR,
+            );
+            grid_renderer.queue_rect(
+                board_px_to_window((first - half_line, offset - half_line), window_size),
+                board_px_to_window((last + half_line, offset + half_line), window_size),
+                GRID_LINE_COLOR,
+            );
+        }
+    }
+
+    fn queue_markup(&mut self) {
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let lines = game.settings.size;
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        let width = (spacing * 0.07).max(1.5);
+        let r = spacing * 0.3;
+
+        for (&point, markup) in game.markup() {
+            let [x, y] = board_px_to_window(point_px(point, lines, view), window_size);
+            let stone = game.board().get(point);
+            let color = stone.map_or(GRID_LINE_COLOR, contrasting_text_color);
+            match markup {
+                Markup::Triangle => {
+                    let corners = [
+                        [x, y - r],
+                        [x + r * 0.866, y + r * 0.5],
+                        [x - r * 0.866, y + r * 0.5],
+                    ];
+                    for i in 0..3 {
+                        markup_renderer.queue_line(corners[i], corners[(i + 1) % 3], width, color);
+                    }
+                }
+                Markup::Square => {
+                    let s = r * 0.75;
+                    let corners = [
+                        [x - s, y - s],
+                        [x + s, y - s],
+                        [x + s, y + s],
+                        [x - s, y + s],
+                    ];
+                    for i in 0..4 {
+                        markup_renderer.queue_line(corners[i], corners[(i + 1) % 4], width, color);
+                    }
+                }
+                Markup::Circle => markup_renderer.queue_ring([x, y], r * 0.85, width, color),
+                Markup::Cross => {
+                    let s = r * 0.7;
+                    markup_renderer.queue_line([x - s, y - s], [x + s, y + s], width, color);
+                    markup_renderer.queue_line([x - s, y + s], [x + s, y - s], width, color);
+                }
+                Markup::Label(text) => {
+                    // clear the lines behind a label on an empty point so it stays readable
+                    if stone.is_none() {
+                        let s = spacing * 0.3;
+                        markup_renderer.queue_rect([x - s, y - s], [x + s, y + s], BOARD_COLOR);
+                    }
+                    text_renderer.queue_text(text, [x, y], spacing * 0.5, color, Anchor::Center);
+                }
+                Markup::Territory(owner) => {
+                    let s = spacing * 0.18;
+                    markup_renderer.queue_rect([x - s, y - s], [x + s, y + s], stone_color(*owner));
+                }
+            }
+        }
+    }
+
+    // shades each point towards the colour that looks like owning it: the
+    // engine's estimate when the position has been analysed, otherwise
+    // Bouzy influence
+    fn queue_ownership(&mut self) {
+        if !self.ui.as_ref().unwrap().display.ownership {
+            return;
+        }
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let analysed = match self.analyses.get(game.moves().len()) {
+            Some(Some(analysis)) => analysis.ownership.clone(),
+            _ => None,
+        };
+        let ownership = analysed.unwrap_or_else(|| influence::ownership(game.board()));
+        let lines = game.settings.size;
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let s = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0] / 2.0;
+        let ownership_renderer = self.ownership_renderer.as_mut().unwrap();
+        for (i, owned) in ownership.iter().enumerate() {
+            let point = (i % lines, i / lines);
+            let [x, y] = board_px_to_window(point_px(point, lines, view), window_size);
+            let owner = if *owned > 0.0 {
+                PlayerColor::Black
+            } else {
+                PlayerColor::White
+            };
+            let mut color = stone_color(owner);
+            color[3] = owned.abs() * OWNERSHIP_ALPHA;
+            ownership_renderer.queue_rect([x - s, y - s], [x + s, y + s], color);
+        }
+    }
+
+    // the engine's best few moves in the position shown, with their win
+    // rates and visits
+    fn queue_analysis(&mut self) {
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let Some(Some(analysis)) = self.analyses.get(game.moves().len()) else {
+            return;
+        };
+        let Some(best) = analysis.candidates.first() else {
+            return;
+        };
+        let lines = game.settings.size;
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        let r = spacing * 0.46;
+
+        // barely looked-at moves would only be noise
+        let shown = analysis
+            .candidates
+            .iter()
+            .filter(|c| c.visits * 20 >= best.visits)
+            .take(MAX_CANDIDATES);
+        for candidate in shown {
+            let Move::Play(point) = candidate.mv else {
+                continue;
+            };
+            let [x, y] = board_px_to_window(point_px(point, lines, view), window_size);
+            let color = if std::ptr::eq(candidate, best) {
+                BEST_CANDIDATE_COLOR
+            } else {
+                candidate_color(best.winrate - candidate.winrate)
+            };
+            // a ring as wide as its radius fills the whole circle
+            markup_renderer.queue_ring([x, y], r / 2.0, r, color);
+            text_renderer.queue_text(
+                &format!("{:.0}", candidate.winrate * 100.0),
+                [x, y - spacing * 0.12],
+                spacing * 0.38,
+                GRID_LINE_COLOR,
+                Anchor::Center,
+            );
+            text_renderer.queue_text(
+                &short_count(candidate.visits),
+                [x, y + spacing * 0.2],
+                spacing * 0.26,
+                GRID_LINE_COLOR,
+                Anchor::Center,
+            );
+        }
+    }
+
+    fn queue_joseki(&mut self) {
+        if !self.ui.as_ref().unwrap().display.joseki {
+            return;
+        }
+        let Some(library) = &self.joseki else {
+            return;
+        };
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let lines = game.settings.size;
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        for continuation in library.continuations(game.board()) {
+            let [x, y] = board_px_to_window(point_px(continuation.point, lines, view), window_size);
+            let color = match continuation.color {
+                PlayerColor::Black => JOSEKI_BLACK_COLOR,
+                PlayerColor::White => JOSEKI_WHITE_COLOR,
+            };
+            markup_renderer.queue_ring([x, y], spacing * 0.28, spacing * 0.1, color);
+        }
+    }
+
+    // an outline round the region to search for in the database
+    fn queue_search_region(&mut self) {
+        if self.database.is_none() {
+            return;
+        }
+        let region = match self.picking_region {
+            Some(first) => first.map(|point| (point, point)),
+            None => self.search_region,
+        };
+        let Some((a, b)) = region else {
+            return;
+        };
+        let lines = self.game.settings.size;
+        if !self.game.board().contains(a) || !self.game.board().contains(b) {
+            return;
+        }
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let [ax, ay] = board_px_to_window(point_px(a, lines, view), window_size);
+        let [bx, by] = board_px_to_window(point_px(b, lines, view), window_size);
+        let s = spacing * 0.5;
+        let (left, right) = (ax.min(bx) - s, ax.max(bx) + s);
+        let (top, bottom) = (ay.min(by) - s, ay.max(by) + s);
+        let corners = [[left, top], [right, top], [right, bottom], [left, bottom]];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        for i in 0..4 {
+            markup_renderer.queue_line(
+                corners[i],
+                corners[(i + 1) % 4],
+                (spacing * 0.06).max(1.5),
+                SEARCH_REGION_COLOR,
+            );
+        }
+    }
+
+    // the moves played next in the games found, while the board still shows what was searched for
+    fn queue_found(&mut self) {
+        let Some(found) = &self.found else {
+            return;
+        };
+        let game = self.review.as_ref().unwrap_or(&self.game);
+        let lines = game.settings.size;
+        if found.pattern.size() != lines
+            || !found
+                .pattern
+                .matches(game.board(), board::Transform::default())
+        {
+            return;
+        }
+        let view = self.view();
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        let r = spacing * 0.4;
+        for continuation in found.continuations.iter().take(MAX_CANDIDATES) {
+            let Some(point) = continuation.point else {
+                continue;
+            };
+            let [x, y] = board_px_to_window(point_px(point, lines, view), window_size);
+            let [red, green, blue, _] = match continuation.color {
+                PlayerColor::Black => JOSEKI_BLACK_COLOR,
+                PlayerColor::White => JOSEKI_WHITE_COLOR,
+            };
+            markup_renderer.queue_ring([x, y], r / 2.0, r, [red, green, blue, FOUND_ALPHA]);
+            text_renderer.queue_text(
+                &short_count(continuation.games),
+                [x, y],
+                spacing * 0.38,
+                contrasting_text_color(continuation.color),
+                Anchor::Center,
+            );
+        }
+    }
+
+    fn queue_board_cursor(&mut self) {
+        let lines = self.game.settings.size;
+        let view = self.view();
+        // a new game may be smaller than the one the cursor was on
+        let Some(point) = self.board_cursor.filter(|&p| self.game.board().contains(p)) else {
+            self.board_cursor = None;
+            return;
+        };
+        let window_size = (self.board_side, self.board_side);
+        let spacing = board_px_to_window((grid_spacing_px(lines), 0.0), window_size)[0];
+        let [x, y] = board_px_to_window(point_px(point, lines, view), window_size);
+        let s = spacing * 0.5;
+        let corners = [
+            [x - s, y - s],
+            [x + s, y - s],
+            [x + s, y + s],
+            [x - s, y + s],
+        ];
+        let markup_renderer = self.markup_renderer.as_mut().unwrap();
+        for i in 0..4 {
+            markup_renderer.queue_line(
+                corners[i],
+                corners[(i + 1) % 4],
+                (spacing * 0.08).max(2.0),
+                BOARD_CURSOR_COLOR,
+            );
+        }
+    }
+
+    fn queue_board_coordinates(&mut self) {
+        if !self.ui.as_ref().unwrap().display.coordinates {
+            return;
+        }
+        // each line is labelled as it is in the game, however the board is turned
+        let unturn = self.view().inverse();
+        let text_renderer = self.text_renderer.as_mut().unwrap();
+        let lines = self.game.settings.size;
+        let window_size = (self.board_side, self.board_side);
+        let edge = MARGIN_OFFSET_PIXELS as f32 / 2.0;
+        let far_edge = BOARD_PIXELS as f32 - edge;
+
+        let label = |(x, y): Point, across: bool| {
+            let (x, y) = unturn.apply((x, y), lines);
+            // a column of the screen is a row of the game when the axes are swapped
+            if across != unturn.transpose {
+                (board::COLUMN_LETTERS[x] as char).to_string()
+            } else {
+                // rows count up from the bottom edge
+                (lines - y).to_string()
+            }
+        };
+        for i in 0..lines {
+            let (line_x, line_y) = intersection_px((i, i), lines);
+            let column = label((i, 0), true);
+            let row = label((0, i), false);
+            for (label, px) in [
+                (&column, (line_x, edge)),
+                (&column, (line_x, far_edge)),
+                (&row, (edge, line_y)),
+                (&row, (far_edge, line_y)),
+            ] {
+                text_renderer.queue_text(
+                    label,
+                    board_px_to_window(px, window_size),
+                    COORDINATE_TEXT_PX,
+                    GRID_LINE_COLOR,
+                    Anchor::Center,
+                );
+            }
+        }
+    }
+
+    // fits the surface to the window and the board to what the side panel leaves
+    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        // a minimised window has nothing to draw into
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.surface_size = (size.width, size.height);
+        let panel_px =
+            (ui::SIDE_PANEL_WIDTH as f64 * self.window.as_ref().unwrap().scale_factor()) as u32;
+        // a window narrower than the panel still gets a sliver of board, as
+        // wgpu won't draw into an empty viewport
+        self.board_side = size.height.min(size.width.saturating_sub(panel_px)).max(1);
+        let config = self.surface_config.as_mut().unwrap();
+        config.width = size.width;
+        config.height = size.height;
+        self.surface
+            .as_ref()
+            .unwrap()
+            .configure(self.device.as_ref().unwrap(), config);
+
+        let board = (self.board_side, self.board_side);
+        for renderer in [
+            &mut self.grid_renderer,
+            &mut self.ownership_renderer,
+            &mut self.markup_renderer,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            renderer.resize(board);
+        }
+        if let Some(text_renderer) = &mut self.text_renderer {
+            text_renderer.resize(board);
+        }
+    }
+
+    fn set_camera(&mut self, camera: Camera) {
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(&camera);
+
+        self.camera_buffer = Some(self.device.as_ref().unwrap().create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+
+        let camera_bind_group_layout = self.device.as_ref().unwrap().create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("camera_bind_group_layout"),
+            },
+        );
+
+        self.camera_bind_group = Some(self.device.as_ref().unwrap().create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: &camera_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.camera_buffer.as_ref().unwrap().as_entire_binding(),
+                }],
+                label: Some("camera_bind_group"),
+            },
+        ));
+
+        self.camera_bind_group_layout = Some(camera_bind_group_layout);
+        self.camera = Some(camera);
+    }
+
+    fn write_camera(&self) {
+        let mut camera_uniform = CameraUniform::new();
+        camera_uniform.update_view_proj(self.camera.as_ref().unwrap());
+        self.queue.as_ref().unwrap().write_buffer(
+            self.camera_buffer.as_ref().unwrap(),
+            0,
+            bytemuck::cast_slice(&[camera_uniform]),
+        );
+    }
+
+    // fn draw_board(&self) {
+    //     render_pass.set_pipeline(&self.sprite_render_pipeline)
+    // }
 }
 
 fn main() {
-    let event_loop = EventLoop::new().unwrap();
+    #[cfg(target_arch = "wasm32")]
+    web::init_logging();
+
+    let event_loop = EventLoop::with_user_event().build().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
     let mut app = App::default();
+    #[cfg(not(target_arch = "wasm32"))]
     let _ = event_loop.run_app(&mut app);
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        app.proxy = Some(event_loop.create_proxy());
+        // returns straight away; the browser drives the loop from here
+        event_loop.spawn_app(app);
+    }
 }
@@ -0,0 +1,464 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::board::PlayerColor;
+use crate::clock::PlayerClock;
+use crate::game::{Game, GameSettings, Move};
+
+/// Bumped whenever a message changes shape. Peers on different versions refuse each other.
+pub const PROTOCOL_VERSION: u32 = 5;
+pub const DEFAULT_PORT: u16 = 7645;
+// longer than any real message, even a snapshot of a long game; anyone sending
+// more is cut off rather than buffered without end
+const MAX_MESSAGE_BYTES: u64 = 1 << 20;
+
+/// What the two players send each other, one JSON object per line.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum Message {
+    // the first thing each side sends
+    Hello {
+        version: u32,
+    },
+    // from the host or server: start over with these settings, the receiver
+    // playing `color`, or only watching when it is None
+    NewGame {
+        settings: GameSettings,
+        color: Option<PlayerColor>,
+    },
+    // a stone or pass by whoever sent it
+    Move {
+        mv: Move,
+    },
+    // a player giving up, which unlike a move needn't wait for their turn
+    Resign {
+        color: PlayerColor,
+    },
+    // both clocks as the sender saw them right after its move
+    Clock {
+        players: [PlayerClock; 2],
+    },
+    // a game already under way, for someone arriving in the middle of it
+    Snapshot {
+        settings: GameSettings,
+        // with who made each, as a resignation can come on either turn
+        moves: Vec<(PlayerColor, Move)>,
+        players: [PlayerClock; 2],
+        color: Option<PlayerColor>,
+    },
+    UndoRequest,
+    UndoReply {
+        accepted: bool,
+    },
+    Chat(ChatLine),
+    // the rest only pass between a client and a server
+    ListGames,
+    Lobby {
+        games: Vec<GameListing>,
+    },
+    CreateGame {
+        settings: GameSettings,
+    },
+    Created {
+        id: u32,
+    },
+    JoinGame {
+        id: u32,
+    },
+    WatchGame {
+        id: u32,
+    },
+    LeaveGame,
+    PlayerLeft {
+        color: PlayerColor,
+    },
+    // from the server once a game starts: what to send in `Resume` to get the
+    // seat back after a dropped connection, and how long the seat is held
+    Session {
+        token: String,
+        grace_secs: u64,
+        pause_clocks: bool,
+    },
+    Resume {
+        token: String,
+    },
+    // a player's connection dropped and their seat is being held for them
+    PlayerAway {
+        color: PlayerColor,
+        grace_secs: u64,
+        pause_clocks: bool,
+    },
+    PlayerBack {
+        color: PlayerColor,
+    },
+    // the server refused the last thing the client asked for
+    Rejected {
+        reason: String,
+    },
+}
+
+impl Message {
+    /// Everything someone joining `game` part way through needs to catch up.
+    pub fn snapshot(game: &Game, color: Option<PlayerColor>) -> Message {
+        Message::Snapshot {
+            settings: game.settings,
+            moves: game.moves().to_vec(),
+            players: game.clock.players,
+            color,
+        }
+    }
+}
+
+/// Something said during a game. Whoever relays it (the host or the server)
+/// passes it to everyone, the speaker included.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChatLine {
+    // "Black", "White" or "Spectator"
+    pub from: String,
+    pub text: String,
+    // how many moves had been played when it was said
+    pub move_number: usize,
+}
+
+/// One table in a server's lobby.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GameListing {
+    pub id: u32,
+    pub settings: GameSettings,
+    // still waiting for a second player
+    pub open: bool,
+    pub moves: usize,
+    pub spectators: usize,
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(std::io::Error),
+    BadMessage(serde_json::Error),
+    Version { theirs: u32 },
+    // the other side spoke before saying hello
+    NoHello,
+    TooLong,
+    Closed,
+}
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "{}", e),
+            NetError::BadMessage(e) => write!(f, "unreadable message: {}", e),
+            NetError::Version { theirs } => write!(
+                f,
+                "the other side speaks protocol version {}, this one speaks {}",
+                theirs, PROTOCOL_VERSION
+            ),
+            NetError::NoHello => write!(f, "the other side didn't introduce itself"),
+            NetError::TooLong => write!(
+                f,
+                "the other side sent a message over {} bytes long",
+                MAX_MESSAGE_BYTES
+            ),
+            NetError::Closed => write!(f, "the other side hung up"),
+        }
+    }
+}
+impl std::error::Error for NetError {}
+impl From<std::io::Error> for NetError {
+    fn from(e: std::io::Error) -> Self {
+        NetError::Io(e)
+    }
+}
+
+pub enum NetEvent {
+    // the handshake went through; carries the other side's address
+    Connected(String),
+    Received(Message),
+    Disconnected(NetError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+enum Opener {
+    Connect(String),
+    Accepted(TcpStream),
+}
+
+/// One end of a two-player game over TCP. The socket lives on background
+/// threads; messages go out with `send` and come back through `poll`.
+/// Dropping the peer closes the connection.
+pub struct Peer {
+    pub role: Role,
+    outgoing: Sender<Message>,
+    incoming: Receiver<NetEvent>,
+}
+impl Peer {
+    /// Connects to a host at an address such as "192.168.1.20:7645".
+    pub fn connect(address: String) -> Peer {
+        Peer::spawn(Role::Guest, Opener::Connect(address))
+    }
+
+    /// Talks to someone who has already connected, e.g. to a server's listener.
+    pub fn accept(stream: TcpStream) -> Peer {
+        Peer::spawn(Role::Host, Opener::Accepted(stream))
+    }
+
+    fn spawn(role: Role, opener: Opener) -> Peer {
+        let (outgoing, outgoing_rx) = mpsc::channel();
+        let (incoming_tx, incoming) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = match open(opener) {
+                Ok(stream) => run(stream, outgoing_rx, &incoming_tx),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                let _ = incoming_tx.send(NetEvent::Disconnected(e));
+            }
+        });
+        Peer {
+            role,
+            outgoing,
+            incoming,
+        }
+    }
+
+    pub fn send(&self, message: Message) {
+        // if the connection has gone, `poll` reports it
+        let _ = self.outgoing.send(message);
+    }
+
+    /// Everything that happened since the last call.
+    pub fn poll(&self) -> Vec<NetEvent> {
+        self.incoming.try_iter().collect()
+    }
+}
+
+fn open(opener: Opener) -> std::io::Result<TcpStream> {
+    match opener {
+        Opener::Connect(address) => TcpStream::connect(address.as_str()),
+        // streams from a non-blocking listener can come out non-blocking too
+        Opener::Accepted(stream) => stream.set_nonblocking(false).map(|()| stream),
+    }
+}
+
+/// Listens on `port` without blocking; call `accept` on it every so often.
+pub fn listen(port: u16) -> Result<TcpListener, NetError> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn run(
+    stream: TcpStream,
+    outgoing: Receiver<Message>,
+    incoming: &Sender<NetEvent>,
+) -> Result<(), NetError> {
+    stream.set_nodelay(true)?;
+    let address = stream.peer_addr()?.to_string();
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    write_message(
+        &mut writer,
+        &Message::Hello {
+            version: PROTOCOL_VERSION,
+        },
+    )?;
+    match read_message(&mut reader)? {
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        } => {}
+        Message::Hello { version } => return Err(NetError::Version { theirs: version }),
+        _ => return Err(NetError::NoHello),
+    }
+    if incoming.send(NetEvent::Connected(address)).is_err() {
+        return Ok(());
+    }
+
+    std::thread::spawn(move || {
+        for message in outgoing {
+            if write_message(&mut writer, &message).is_err() {
+                break;
+            }
+        }
+        // the peer was dropped or the socket failed; either way wake the reader up
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+    loop {
+        let message = read_message(&mut reader)?;
+        if incoming.send(NetEvent::Received(message)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+fn write_message(writer: &mut TcpStream, message: &Message) -> Result<(), NetError> {
+    let mut line = serde_json::to_string(message).map_err(NetError::BadMessage)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn read_message(reader: &mut BufReader<TcpStream>) -> Result<Message, NetError> {
+    let mut line = String::new();
+    let read = reader.take(MAX_MESSAGE_BYTES).read_line(&mut line)?;
+    if read == 0 {
+        return Err(NetError::Closed);
+    }
+    if read as u64 == MAX_MESSAGE_BYTES && !line.ends_with('\n') {
+        return Err(NetError::TooLong);
+    }
+    serde_json::from_str(&line).map_err(NetError::BadMessage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use std::time::{Duration, Instant};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    // a listener on a free port of this machine, and the port
+    fn listener() -> (TcpListener, u16) {
+        let listener = listen(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (listener, port)
+    }
+
+    fn accept(listener: &TcpListener) -> TcpStream {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => return stream,
+                Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(5)),
+                Err(e) => panic!("nobody connected: {}", e),
+            }
+        }
+    }
+
+    fn next_event(peer: &Peer) -> NetEvent {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(event) = peer.poll().into_iter().next() {
+                return event;
+            }
+            assert!(Instant::now() < deadline, "nothing arrived");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn received(peer: &Peer) -> Message {
+        match next_event(peer) {
+            NetEvent::Received(message) => message,
+            NetEvent::Connected(_) => panic!("connected twice"),
+            NetEvent::Disconnected(e) => panic!("disconnected: {}", e),
+        }
+    }
+
+    fn connected(peer: &Peer) {
+        match next_event(peer) {
+            NetEvent::Connected(_) => {}
+            NetEvent::Received(message) => panic!("{:?} before the handshake", message),
+            NetEvent::Disconnected(e) => panic!("disconnected: {}", e),
+        }
+    }
+
+    // a host and a guest that have shaken hands
+    fn pair() -> (Peer, Peer) {
+        let (listener, port) = listener();
+        let guest = Peer::connect(format!("127.0.0.1:{}", port));
+        let host = Peer::accept(accept(&listener));
+        connected(&host);
+        connected(&guest);
+        (host, guest)
+    }
+
+    #[test]
+    fn peers_play_a_game() {
+        let (host, guest) = pair();
+        let mv = Message::Move {
+            mv: Move::Play((3, 3)),
+        };
+        host.send(mv.clone());
+        assert_eq!(received(&guest), mv);
+
+        let clock = Message::Clock {
+            players: Game::default().clock.players,
+        };
+        guest.send(clock.clone());
+        assert_eq!(received(&host), clock);
+
+        guest.send(Message::UndoRequest);
+        assert_eq!(received(&host), Message::UndoRequest);
+        host.send(Message::UndoReply { accepted: true });
+        assert_eq!(received(&guest), Message::UndoReply { accepted: true });
+    }
+
+    #[test]
+    fn dropping_a_peer_hangs_up() {
+        let (host, guest) = pair();
+        drop(host);
+        assert!(matches!(
+            next_event(&guest),
+            NetEvent::Disconnected(NetError::Closed | NetError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_another_protocol_version() {
+        let (listener, port) = listener();
+        let mut other = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let host = Peer::accept(accept(&listener));
+        writeln!(
+            other,
+            r#"{{"type":"Hello","version":{}}}"#,
+            PROTOCOL_VERSION + 1
+        )
+        .unwrap();
+
+        // the host still introduces itself first
+        let mut hello = String::new();
+        BufReader::new(&other).read_line(&mut hello).unwrap();
+        let hello: Message = serde_json::from_str(&hello).unwrap();
+        assert_eq!(
+            hello,
+            Message::Hello {
+                version: PROTOCOL_VERSION
+            }
+        );
+        match next_event(&host) {
+            NetEvent::Disconnected(NetError::Version { theirs }) => {
+                assert_eq!(theirs, PROTOCOL_VERSION + 1)
+            }
+            _ => panic!("expected a version mismatch"),
+        }
+    }
+
+    #[test]
+    fn hangs_up_on_an_endless_line() {
+        let (listener, port) = listener();
+        let mut other = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let host = Peer::accept(accept(&listener));
+        writeln!(
+            other,
+            r#"{{"type":"Hello","version":{}}}"#,
+            PROTOCOL_VERSION
+        )
+        .unwrap();
+        connected(&host);
+
+        // the host may hang up part way through, so how the writing ends doesn't matter
+        std::thread::spawn(move || {
+            let chunk = [b'x'; 64 * 1024];
+            while other.write_all(&chunk).is_ok() {}
+        });
+        assert!(matches!(
+            next_event(&host),
+            NetEvent::Disconnected(NetError::TooLong)
+        ));
+    }
+}
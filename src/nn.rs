@@ -0,0 +1,131 @@
+//! Policy/value networks in ONNX format, run on the CPU with tract.
+//!
+//! A network sees the position from the side of the player to move, as one
+//! `[1, 3, size, size]` float tensor: plane 0 holds that player's stones,
+//! plane 1 the opponent's and plane 2 is all ones, so the edge of the board
+//! shows through zero padding. It gives back two outputs, in this order:
+//! `[1, size * size + 1]` move logits, points row by row and then the pass,
+//! and a `[1, 1]` value between -1 (a sure loss) and 1 (a sure win).
+
+use std::path::Path;
+
+use tract_onnx::prelude::*;
+
+use crate::game::Game;
+
+pub const PLANES: usize = 3;
+
+#[derive(Debug)]
+pub enum NnError {
+    Tract(TractError),
+    // the network's outputs aren't shaped as described above
+    Outputs { expected: usize, found: usize },
+    BoardSize { network: usize, game: usize },
+}
+impl std::fmt::Display for NnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NnError::Tract(e) => write!(f, "{}", e),
+            NnError::Outputs { expected, found } => write!(
+                f,
+                "expected {} move logits and a value, found {} outputs",
+                expected, found
+            ),
+            NnError::BoardSize { network, game } => write!(
+                f,
+                "the network was loaded for {0}x{0} and the game is {1}x{1}",
+                network, game
+            ),
+        }
+    }
+}
+impl std::error::Error for NnError {}
+impl From<TractError> for NnError {
+    fn from(e: TractError) -> Self {
+        NnError::Tract(e)
+    }
+}
+
+/// What a network makes of a position.
+pub struct Evaluation {
+    // move probabilities, points row by row and then the pass; they add up to 1
+    pub policy: Vec<f32>,
+    // the player to move's prospects, from -1 to 1
+    pub value: f32,
+}
+
+/// A network loaded and optimised for one board size.
+pub struct Network {
+    size: usize,
+    model: TypedRunnableModel<TypedModel>,
+}
+impl Network {
+    pub fn load(path: impl AsRef<Path>, size: usize) -> Result<Network, NnError> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, PLANES, size, size]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(Network { size, model })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn evaluate(&self, game: &Game) -> Result<Evaluation, NnError> {
+        let board = game.board();
+        let size = board.size();
+        if size != self.size {
+            return Err(NnError::BoardSize {
+                network: self.size,
+                game: size,
+            });
+        }
+        let color = game.to_move();
+        let input: Tensor =
+            tract_ndarray::Array4::from_shape_fn((1, PLANES, size, size), |(_, plane, y, x)| {
+                let stone = board.get((x, y));
+                let on = match plane {
+                    0 => stone == Some(color),
+                    1 => stone == Some(color.opponent()),
+                    _ => true,
+                };
+                if on {
+                    1.0f32
+                } else {
+                    0.0
+                }
+            })
+            .into();
+
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let expected = size * size + 1;
+        if outputs.len() != 2 {
+            return Err(NnError::Outputs {
+                expected,
+                found: outputs.len(),
+            });
+        }
+        let logits = outputs[0].to_array_view::<f32>()?;
+        let value = outputs[1].to_array_view::<f32>()?;
+        if logits.len() != expected || value.len() != 1 {
+            return Err(NnError::Outputs {
+                expected,
+                found: logits.len(),
+            });
+        }
+
+        // softmax, shifted by the largest logit so nothing overflows
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mut policy = logits.iter().map(|l| (l - max).exp()).collect::<Vec<_>>();
+        let total = policy.iter().sum::<f32>();
+        for p in &mut policy {
+            *p /= total;
+        }
+        Ok(Evaluation {
+            policy,
+            value: value.iter().next().unwrap().clamp(-1.0, 1.0),
+        })
+    }
+}
@@ -0,0 +1,276 @@
+use crate::board::Point;
+use crate::game::MoveError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SgfError {
+    UnexpectedEnd,
+    UnexpectedChar { found: char, offset: usize },
+    NoGame,
+    BadValue { property: String, value: String },
+    IllegalMove { number: usize, error: MoveError },
+}
+impl std::fmt::Display for SgfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SgfError::UnexpectedEnd => f.write_str("the file ends in the middle of a game"),
+            SgfError::UnexpectedChar { found, offset } => {
+                write!(f, "unexpected {:?} at offset {}", found, offset)
+            }
+            SgfError::NoGame => f.write_str("the file has no game in it"),
+            SgfError::BadValue { property, value } => {
+                write!(f, "can't read {}[{}]", property, value)
+            }
+            SgfError::IllegalMove { number, error } => {
+                write!(f, "move {} is illegal: {}", number, error)
+            }
+        }
+    }
+}
+impl std::error::Error for SgfError {}
+
+/// One SGF node: its properties in the order they were read or added.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Node {
+    pub properties: Vec<(String, Vec<String>)>,
+}
+impl Node {
+    pub fn get(&self, ident: &str) -> Option<&[String]> {
+        self.properties
+            .iter()
+            .find(|(id, _)| id == ident)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    pub fn first(&self, ident: &str) -> Option<&str> {
+        self.get(ident)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Adds a value to `ident`, creating the property if the node doesn't have it yet.
+    pub fn push(&mut self, ident: &str, value: String) {
+        match self.properties.iter_mut().find(|(id, _)| id == ident) {
+            Some((_, values)) => values.push(value),
+            None => self.properties.push((ident.to_string(), vec![value])),
+        }
+    }
+}
+
+/// A sequence of nodes followed by the variations branching off its last node.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameTree {
+    pub nodes: Vec<Node>,
+    pub variations: Vec<GameTree>,
+}
+impl GameTree {
+    /// The nodes along the first variation at every branch.
+    pub fn main_line(&self) -> Vec<&Node> {
+        let mut nodes = Vec::new();
+        let mut tree = self;
+        loop {
+            nodes.extend(tree.nodes.iter());
+            match tree.variations.first() {
+                Some(next) => tree = next,
+                None => return nodes,
+            }
+        }
+    }
+}
+
+fn write_escaped(f: &mut std::fmt::Formatter<'_>, value: &str) -> std::fmt::Result {
+    for c in value.chars() {
+        if c == ']' || c == '\\' {
+            f.write_str("\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(";")?;
+        for (ident, values) in &self.properties {
+            f.write_str(ident)?;
+            for value in values {
+                f.write_str("[")?;
+                write_escaped(f, value)?;
+                f.write_str("]")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for GameTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("(")?;
+        for node in &self.nodes {
+            writeln!(f, "{}", node)?;
+        }
+        for variation in &self.variations {
+            write!(f, "{}", variation)?;
+        }
+        f.write_str(")")
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SgfError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(found) => Err(SgfError::UnexpectedChar {
+                found,
+                offset: self.pos,
+            }),
+            None => Err(SgfError::UnexpectedEnd),
+        }
+    }
+
+    fn game_tree(&mut self) -> Result<GameTree, SgfError> {
+        self.expect('(')?;
+        let mut tree = GameTree::default();
+        while self.peek() == Some(';') {
+            tree.nodes.push(self.node()?);
+        }
+        while self.peek() == Some('(') {
+            tree.variations.push(self.game_tree()?);
+        }
+        self.expect(')')?;
+        Ok(tree)
+    }
+
+    fn node(&mut self) -> Result<Node, SgfError> {
+        self.expect(';')?;
+        let mut node = Node::default();
+        while self.peek().is_some_and(|c| c.is_ascii_uppercase()) {
+            let start = self.pos;
+            // old files put lowercase letters in identifiers, e.g. "AddBlack"; only the capitals count
+            let mut ident = String::new();
+            while let Some(&c) = self.chars.get(self.pos) {
+                if !c.is_ascii_alphabetic() {
+                    break;
+                }
+                if c.is_ascii_uppercase() {
+                    ident.push(c);
+                }
+                self.pos += 1;
+            }
+            if self.peek() != Some('[') {
+                return match self.peek() {
+                    Some(found) => Err(SgfError::UnexpectedChar {
+                        found,
+                        offset: start,
+                    }),
+                    None => Err(SgfError::UnexpectedEnd),
+                };
+            }
+            while self.peek() == Some('[') {
+                self.pos += 1;
+                let value = self.value()?;
+                node.push(&ident, value);
+            }
+        }
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<String, SgfError> {
+        let mut value = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or(SgfError::UnexpectedEnd)?;
+            self.pos += 1;
+            match c {
+                ']' => return Ok(value),
+                '\\' => {
+                    let escaped = *self.chars.get(self.pos).ok_or(SgfError::UnexpectedEnd)?;
+                    self.pos += 1;
+                    // an escaped line break is a soft break and is dropped
+                    if escaped == '\n' || escaped == '\r' {
+                        if let Some(&next) = self.chars.get(self.pos) {
+                            if (next == '\n' || next == '\r') && next != escaped {
+                                self.pos += 1;
+                            }
+                        }
+                    } else {
+                        value.push(escaped);
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+/// Reads every game tree in an SGF collection.
+pub fn parse(text: &str) -> Result<Vec<GameTree>, SgfError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let mut trees = Vec::new();
+    while parser.peek().is_some() {
+        trees.push(parser.game_tree()?);
+    }
+    if trees.is_empty() {
+        return Err(SgfError::NoGame);
+    }
+    Ok(trees)
+}
+
+/// SGF coordinates are two letters, column first, both counting from "a" at the top left.
+pub fn encode_point((x, y): Point) -> String {
+    [x, y].iter().map(|&i| (b'a' + i as u8) as char).collect()
+}
+
+/// Reads a single point; `None` for the empty value or "tt", which mean a pass.
+pub fn decode_point(value: &str, size: usize) -> Option<Point> {
+    let bytes = value.as_bytes();
+    if bytes.len() != 2 || (value == "tt" && size <= 19) {
+        return None;
+    }
+    let x = bytes[0].checked_sub(b'a')? as usize;
+    let y = bytes[1].checked_sub(b'a')? as usize;
+    (x < size && y < size).then_some((x, y))
+}
+
+/// Reads a list of points, expanding compressed "aa:cc" rectangles.
+pub fn decode_points(ident: &str, values: &[String], size: usize) -> Result<Vec<Point>, SgfError> {
+    let bad_value = |value: &str| SgfError::BadValue {
+        property: ident.to_string(),
+        value: value.to_string(),
+    };
+    let mut points = Vec::new();
+    for value in values {
+        let point = |part: &str| decode_point(part, size).ok_or_else(|| bad_value(value));
+        match value.split_once(':') {
+            Some((from, to)) => {
+                let (from, to) = (point(from)?, point(to)?);
+                for y in from.1.min(to.1)..=from.1.max(to.1) {
+                    for x in from.0.min(to.0)..=from.0.max(to.0) {
+                        points.push((x, y));
+                    }
+                }
+            }
+            None => points.push(point(value)?),
+        }
+    }
+    Ok(points)
+}
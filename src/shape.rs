@@ -0,0 +1,171 @@
+use crate::vertex::{ShapeVertex, Vertex};
+
+const MAX_VERTICES: usize = 65536;
+
+/// Batches flat-coloured 2D shapes given in window pixels. Like the text
+/// renderer, shapes are queued each frame, uploaded by `prepare` and drawn by
+/// `draw`, which moves them with the board camera.
+pub struct ShapeRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertices: Vec<ShapeVertex>,
+    num_vertices: u32,
+    screen_size: [f32; 2],
+}
+impl ShapeRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        screen_size: (u32, u32),
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shape pipeline layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shape_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shape shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shape.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shape render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shape_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[ShapeVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shape_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                // shapes are built from triangles of either winding
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shape vertex buffer"),
+            size: (MAX_VERTICES * std::mem::size_of::<ShapeVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        ShapeRenderer {
+            pipeline,
+            vertex_buffer,
+            vertices: Vec::new(),
+            num_vertices: 0,
+            screen_size: [screen_size.0 as f32, screen_size.1 as f32],
+        }
+    }
+
+    fn to_ndc(&self, [x, y]: [f32; 2]) -> [f32; 3] {
+        [
+            x / self.screen_size[0] * 2.0 - 1.0,
+            1.0 - y / self.screen_size[1] * 2.0,
+            0.0,
+        ]
+    }
+
+    pub fn queue_triangle(&mut self, points: [[f32; 2]; 3], color: [f32; 4]) {
+        if self.vertices.len() + 3 > MAX_VERTICES {
+            log::warn!("shape batch is full, dropping triangle");
+            return;
+        }
+        for p in points {
+            self.vertices.push(ShapeVertex {
+                position: self.to_ndc(p),
+                color,
+            });
+        }
+    }
+
+    pub fn queue_rect(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        self.queue_triangle([min, [max[0], min[1]], max], color);
+        self.queue_triangle([min, max, [min[0], max[1]]], color);
+    }
+
+    pub fn queue_line(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: [f32; 4]) {
+        let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        // half the width, at right angles to the line
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        let a = [from[0] + nx, from[1] + ny];
+        let b = [to[0] + nx, to[1] + ny];
+        let c = [to[0] - nx, to[1] - ny];
+        let d = [from[0] - nx, from[1] - ny];
+        self.queue_triangle([a, b, c], color);
+        self.queue_triangle([a, c, d], color);
+    }
+
+    /// A circle outline `width` pixels thick, centred on `radius`.
+    pub fn queue_ring(&mut self, center: [f32; 2], radius: f32, width: f32, color: [f32; 4]) {
+        const SEGMENTS: usize = 32;
+        let point = |i: usize, r: f32| {
+            let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+            [center[0] + r * angle.cos(), center[1] + r * angle.sin()]
+        };
+        let (inner, outer) = (radius - width / 2.0, radius + width / 2.0);
+        for i in 0..SEGMENTS {
+            let (a, b) = (point(i, outer), point(i + 1, outer));
+            let (c, d) = (point(i + 1, inner), point(i, inner));
+            self.queue_triangle([a, b, c], color);
+            self.queue_triangle([a, c, d], color);
+        }
+    }
+
+    /// Call when the area drawn into changes size.
+    pub fn resize(&mut self, screen_size: (u32, u32)) {
+        self.screen_size = [screen_size.0 as f32, screen_size.1 as f32];
+    }
+
+    /// Uploads everything queued since the last call and clears the queue.
+    pub fn prepare(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_vertices = self.vertices.len() as u32;
+        self.vertices.clear();
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        if self.num_vertices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.num_vertices, 0..1);
+    }
+}
@@ -4,31 +4,114 @@ struct TimerUniform {
 @group(0) @binding(0)
 var<uniform> timer: TimerUniform;
 
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
 };
 
+struct InstanceInput {
+    @location(5) center: vec3<f32>,
+    @location(6) radius: vec2<f32>,
+    @location(7) color: vec4<f32>,
+    @location(8) marker: u32,
+    @location(9) outline: vec4<f32>,
+    @location(10) links: u32,
+};
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec3<f32>,
+    // position inside the stone, -1..1 on both axes
+    @location(0) local: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) marker: u32,
+    @location(3) outline: vec4<f32>,
+    @location(4) @interpolate(flat) links: u32,
 };
 
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.color.x = model.color.x + cos(model.position.x) - sin(timer.t);
-    out.color.y = model.color.y + sin(model.position.x) + cos(timer.t);
-    out.color.z = model.color.z + cos(model.position.x) + cos(timer.t);
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.local = model.position.xy;
+    out.color = instance.color;
+    out.marker = instance.marker;
+    out.outline = instance.outline;
+    out.links = instance.links;
+    let board_position = instance.center.xy + model.position.xy * instance.radius;
+    out.clip_position = camera.view_proj * vec4<f32>(board_position, 0.0, 1.0);
     return out;
 }
 
 // Fragment shader
+const MARKER_CIRCLE: u32 = 1u;
+const MARKER_DOT: u32 = 2u;
+
+// the stone's share of the point its quad covers
+const STONE_SIZE: f32 = 0.96;
+// where the group outline starts, towards the edge of the point
+const OUTLINE_FROM: f32 = 0.92;
+// which of the points around are in the same group; up is towards the top of the board
+const LINK_LEFT: u32 = 1u;
+const LINK_RIGHT: u32 = 2u;
+const LINK_UP: u32 = 4u;
+const LINK_DOWN: u32 = 8u;
+const LINK_UP_LEFT: u32 = 16u;
+const LINK_UP_RIGHT: u32 = 32u;
+const LINK_DOWN_LEFT: u32 = 64u;
+const LINK_DOWN_RIGHT: u32 = 128u;
+
+fn linked(links: u32, bit: u32) -> bool {
+    return (links & bit) != 0u;
+}
+
+// the edge of the point wherever the group stops, plus the inside corners
+// where it turns round an empty point
+fn on_outline(p: vec2<f32>, links: u32) -> bool {
+    let left = p.x < -OUTLINE_FROM;
+    let right = p.x > OUTLINE_FROM;
+    let up = p.y > OUTLINE_FROM;
+    let down = p.y < -OUTLINE_FROM;
+    let sides = (left && !linked(links, LINK_LEFT))
+        || (right && !linked(links, LINK_RIGHT))
+        || (up && !linked(links, LINK_UP))
+        || (down && !linked(links, LINK_DOWN));
+    let corners = (up && left && !linked(links, LINK_UP_LEFT))
+        || (up && right && !linked(links, LINK_UP_RIGHT))
+        || (down && left && !linked(links, LINK_DOWN_LEFT))
+        || (down && right && !linked(links, LINK_DOWN_RIGHT));
+    return sides || corners;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    if in.outline.a > 0.0 && on_outline(in.local, in.links) {
+        return in.outline;
+    }
+    let p = in.local / STONE_SIZE;
+    let d = length(p);
+    if d > 1.0 {
+        discard;
+    }
+    // soft rim plus a highlight towards the top left
+    let edge = 1.0 - smoothstep(0.9, 1.0, d);
+    let shine = 0.25 * max(0.0, 1.0 - length(p - vec2<f32>(-0.35, 0.35)) * 1.6);
+    var rgb = in.color.rgb * (1.0 - 0.15 * d * d) + vec3<f32>(shine);
+
+    // markers take the opposite shade of the stone so they show on both colours
+    let marker_rgb = vec3<f32>(1.0 - step(0.5, in.color.r));
+    if in.marker == MARKER_CIRCLE && d > 0.42 && d < 0.56 {
+        rgb = marker_rgb;
+    } else if in.marker == MARKER_DOT && d < 0.3 {
+        rgb = marker_rgb;
+    }
+    return vec4<f32>(rgb, in.color.a * edge);
 }
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use crate::texture;
+use crate::vertex::{TextVertex, Vertex};
+use wgpu::util::DeviceExt;
+
+const FONT_BYTES: &[u8] = include_bytes!("../res/DejaVuSansMono-Bold.ttf");
+// glyphs are rasterised once at this size and scaled down when drawn
+const RASTER_PX: f32 = 48.0;
+const ATLAS_SIZE: u32 = 1024;
+const GLYPH_PADDING: u32 = 2;
+const MAX_GLYPHS: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    Center,
+}
+
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    size: [f32; 2],
+    offset: [f32; 2],
+    advance: f32,
+}
+
+pub struct GlyphAtlas {
+    glyphs: HashMap<char, Glyph>,
+    cap_height: f32,
+    pixels: Vec<u8>,
+}
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        let font = fontdue::Font::from_bytes(FONT_BYTES, fontdue::FontSettings::default())
+            .expect("bundled font should parse");
+
+        let mut pixels = vec![0u8; (ATLAS_SIZE * ATLAS_SIZE) as usize];
+        let mut glyphs = HashMap::new();
+        let (mut pen_x, mut pen_y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+
+        for c in (' '..='~').chain(['·', '×', '±']) {
+            let (metrics, bitmap) = font.rasterize(c, RASTER_PX);
+            let (w, h) = (metrics.width as u32, metrics.height as u32);
+            if pen_x + w + GLYPH_PADDING > ATLAS_SIZE {
+                pen_x = GLYPH_PADDING;
+                pen_y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            if pen_y + h + GLYPH_PADDING > ATLAS_SIZE {
+                log::warn!("glyph atlas is full, skipping {:?}", c);
+                continue;
+            }
+            for row in 0..h {
+                let src = (row * w) as usize;
+                let dst = ((pen_y + row) * ATLAS_SIZE + pen_x) as usize;
+                pixels[dst..dst + w as usize].copy_from_slice(&bitmap[src..src + w as usize]);
+            }
+            glyphs.insert(
+                c,
+                Glyph {
+                    uv_min: [
+                        pen_x as f32 / ATLAS_SIZE as f32,
+                        pen_y as f32 / ATLAS_SIZE as f32,
+                    ],
+                    uv_max: [
+                        (pen_x + w) as f32 / ATLAS_SIZE as f32,
+                        (pen_y + h) as f32 / ATLAS_SIZE as f32,
+                    ],
+                    size: [w as f32, h as f32],
+                    offset: [metrics.xmin as f32, metrics.ymin as f32],
+                    advance: metrics.advance_width,
+                },
+            );
+            pen_x += w + GLYPH_PADDING;
+            row_height = row_height.max(h);
+        }
+
+        let cap_height = glyphs.get(&'H').map(|g| g.size[1]).unwrap_or(RASTER_PX);
+
+        GlyphAtlas {
+            glyphs,
+            cap_height,
+            pixels,
+        }
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Width and cap height in pixels of `text` drawn at `size` pixels.
+    pub fn measure(&self, text: &str, size: f32) -> [f32; 2] {
+        let scale = size / RASTER_PX;
+        let width = text
+            .chars()
+            .filter_map(|c| self.glyph(c))
+            .map(|g| g.advance * scale)
+            .sum();
+        [width, self.cap_height * scale]
+    }
+}
+
+/// Draws text from a glyph atlas, positioned in window pixels and moved with the
+/// board camera. Text is queued every frame with `queue_text`, uploaded in one
+/// batch by `prepare` and drawn by `draw`.
+pub struct TextRenderer {
+    atlas: GlyphAtlas,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    vertices: Vec<TextVertex>,
+    num_indices: u32,
+    screen_size: [f32; 2],
+}
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_format: wgpu::TextureFormat,
+        screen_size: (u32, u32),
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let atlas = GlyphAtlas::new();
+        let atlas_texture = texture::Texture::from_alpha(
+            device,
+            queue,
+            &atlas.pixels,
+            (ATLAS_SIZE, ATLAS_SIZE),
+            "glyph atlas",
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("glyph atlas bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&atlas_texture.sampler),
+                },
+            ],
+            label: Some("glyph atlas bind group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("text pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout, camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let text_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("text shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("text.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("text render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &text_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[TextVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &text_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("text vertex buffer"),
+            size: (MAX_GLYPHS * 4 * std::mem::size_of::<TextVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // every glyph is a quad, so the index buffer never changes
+        let indices = (0..MAX_GLYPHS as u16)
+            .flat_map(|i| [0, 1, 2, 0, 2, 3].map(|j| i * 4 + j))
+            .collect::<Vec<u16>>();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("text index buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        TextRenderer {
+            atlas,
+            pipeline,
+            bind_group,
+            vertex_buffer,
+            index_buffer,
+            vertices: Vec::new(),
+            num_indices: 0,
+            screen_size: [screen_size.0 as f32, screen_size.1 as f32],
+        }
+    }
+
+    /// Queues `text` at `position` in window pixels for the next `prepare`.
+    /// `size` is the nominal font size in pixels.
+    pub fn queue_text(
+        &mut self,
+        text: &str,
+        position: [f32; 2],
+        size: f32,
+        color: [f32; 4],
+        anchor: Anchor,
+    ) {
+        let scale = size / RASTER_PX;
+        let (mut pen_x, baseline) = match anchor {
+            Anchor::Center => {
+                let [width, cap_height] = self.atlas.measure(text, size);
+                (position[0] - width / 2.0, position[1] + cap_height / 2.0)
+            }
+        };
+
+        for c in text.chars() {
+            if self.vertices.len() >= MAX_GLYPHS * 4 {
+                log::warn!("text batch is full, dropping the rest of {:?}", text);
+                return;
+            }
+            let Some(glyph) = self.atlas.glyph(c) else {
+                continue;
+            };
+            let left = pen_x + glyph.offset[0] * scale;
+            let top = baseline - (glyph.offset[1] + glyph.size[1]) * scale;
+            let right = left + glyph.size[0] * scale;
+            let bottom = top + glyph.size[1] * scale;
+            pen_x += glyph.advance * scale;
+
+            if glyph.size[0] == 0.0 {
+                continue;
+            }
+            let [x0, y0] = self.to_ndc(left, top);
+            let [x1, y1] = self.to_ndc(right, bottom);
+            let [u0, v0] = glyph.uv_min;
+            let [u1, v1] = glyph.uv_max;
+            self.vertices.extend_from_slice(&[
+                TextVertex {
+                    position: [x0, y0, 0.0],
+                    tex_coords: [u0, v0],
+                    color,
+                },
+                TextVertex {
+                    position: [x1, y0, 0.0],
+                    tex_coords: [u1, v0],
+                    color,
+                },
+                TextVertex {
+                    position: [x1, y1, 0.0],
+                    tex_coords: [u1, v1],
+                    color,
+                },
+                TextVertex {
+                    position: [x0, y1, 0.0],
+                    tex_coords: [u0, v1],
+                    color,
+                },
+            ]);
+        }
+    }
+
+    fn to_ndc(&self, x: f32, y: f32) -> [f32; 2] {
+        [
+            x / self.screen_size[0] * 2.0 - 1.0,
+            1.0 - y / self.screen_size[1] * 2.0,
+        ]
+    }
+
+    /// Call when the area drawn into changes size.
+    pub fn resize(&mut self, screen_size: (u32, u32)) {
+        self.screen_size = [screen_size.0 as f32, screen_size.1 as f32];
+    }
+
+    /// Uploads everything queued since the last call and clears the queue.
+    pub fn prepare(&mut self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.num_indices = (self.vertices.len() / 4 * 6) as u32;
+        self.vertices.clear();
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        if self.num_indices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
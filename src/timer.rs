@@ -8,7 +8,7 @@ pub struct TimerUniform {
 }
 #[repr(C)]
 pub struct Timer {
-    pub start: std::time::Instant,
+    pub start: web_time::Instant,
     pub elapsed: f64,
     pub last: f64,
     pub acc: f64,
@@ -51,7 +51,7 @@ impl Timer {
             }],
         });
 
-        let start = std::time::Instant::now();
+        let start = web_time::Instant::now();
 
         Timer {
             start,
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use winit::event::{Touch, TouchPhase};
+
+// a finger that moves further than this, in window pixels, is dragging rather than tapping
+const TAP_SLOP_PX: f64 = 12.0;
+
+pub enum TouchGesture {
+    Tap([f64; 2]),
+    // two fingers: how far their midpoint moved, and how much further apart they are
+    PanZoom {
+        center: [f64; 2],
+        delta: [f64; 2],
+        scale: f64,
+    },
+}
+
+/// Follows the fingers on the screen and reports taps and two-finger pan/zoom.
+#[derive(Default)]
+pub struct TouchTracker {
+    // where each finger first landed and where it is now
+    fingers: HashMap<u64, ([f64; 2], [f64; 2])>,
+    // once a touch has moved or had company it can no longer end in a tap
+    tap_possible: bool,
+}
+impl TouchTracker {
+    pub fn handle(&mut self, touch: &Touch) -> Option<TouchGesture> {
+        let position = [touch.location.x, touch.location.y];
+        match touch.phase {
+            TouchPhase::Started => {
+                self.tap_possible = self.fingers.is_empty();
+                self.fingers.insert(touch.id, (position, position));
+                None
+            }
+            TouchPhase::Moved => {
+                let before = self.two_fingers();
+                let (start, current) = self.fingers.get_mut(&touch.id)?;
+                *current = position;
+                if (position[0] - start[0]).hypot(position[1] - start[1]) > TAP_SLOP_PX {
+                    self.tap_possible = false;
+                }
+                let (a0, b0) = before?;
+                let (a1, b1) = self.two_fingers()?;
+                let mid = |a: [f64; 2], b: [f64; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+                let distance = |a: [f64; 2], b: [f64; 2]| (a[0] - b[0]).hypot(a[1] - b[1]);
+                let (m0, m1) = (mid(a0, b0), mid(a1, b1));
+                Some(TouchGesture::PanZoom {
+                    center: m1,
+                    delta: [m1[0] - m0[0], m1[1] - m0[1]],
+                    scale: distance(a1, b1) / distance(a0, b0).max(1.0),
+                })
+            }
+            TouchPhase::Ended => {
+                let was_tap = self.tap_possible && self.fingers.len() == 1;
+                self.fingers.remove(&touch.id);
+                self.tap_possible = false;
+                was_tap.then_some(TouchGesture::Tap(position))
+            }
+            TouchPhase::Cancelled => {
+                self.fingers.remove(&touch.id);
+                self.tap_possible = false;
+                None
+            }
+        }
+    }
+
+    // the current positions of the fingers when exactly two are down
+    fn two_fingers(&self) -> Option<([f64; 2], [f64; 2])> {
+        if self.fingers.len() != 2 {
+            return None;
+        }
+        let mut positions = self.fingers.values().map(|&(_, current)| current);
+        Some((positions.next()?, positions.next()?))
+    }
+}
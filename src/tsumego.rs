@@ -0,0 +1,390 @@
+//! Life-and-death problems from SGF collections. The player to move at the
+//! start of a problem is the solver; every variation in the file is a line
+//! the solver might try, and the app answers from it. Lines are marked
+//! correct or wrong the usual ways: a comment with "RIGHT" in it or starting
+//! "Correct", or TE[], for correct; a comment starting "Wrong" or
+//! "Incorrect", or BM[], for wrong. When a file marks some lines correct,
+//! any line that ends unmarked is wrong; when it marks nothing, the main
+//! line is the solution.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::board::PlayerColor;
+use crate::game::{Game, Move};
+use crate::sgf::{self, SgfError};
+
+#[derive(Debug)]
+pub enum TsumegoError {
+    Io(std::io::Error),
+    Sgf(SgfError),
+    NoProblems,
+}
+impl std::fmt::Display for TsumegoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TsumegoError::Io(e) => write!(f, "{}", e),
+            TsumegoError::Sgf(e) => write!(f, "{}", e),
+            TsumegoError::NoProblems => write!(f, "the file has no problems in it"),
+        }
+    }
+}
+impl std::error::Error for TsumegoError {}
+impl From<std::io::Error> for TsumegoError {
+    fn from(e: std::io::Error) -> Self {
+        TsumegoError::Io(e)
+    }
+}
+impl From<SgfError> for TsumegoError {
+    fn from(e: SgfError) -> Self {
+        TsumegoError::Sgf(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mark {
+    Correct,
+    Wrong,
+    Unmarked,
+}
+
+fn mark(node: &sgf::Node) -> Mark {
+    let comment = node.first("C").unwrap_or("").trim_start();
+    let first_word = comment
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if node.get("BM").is_some() || first_word == "wrong" || first_word == "incorrect" {
+        Mark::Wrong
+    } else if node.get("TE").is_some() || comment.contains("RIGHT") || first_word == "correct" {
+        Mark::Correct
+    } else {
+        Mark::Unmarked
+    }
+}
+
+/// One move in a problem's solution tree and the lines going on from it.
+#[derive(Clone, Debug)]
+struct Branch {
+    color: PlayerColor,
+    mv: Move,
+    mark: Mark,
+    comment: Option<String>,
+    children: Vec<Branch>,
+}
+
+// the moves that can follow `nodes`, each with everything after it; nodes
+// without a move are passed over
+fn branches(
+    nodes: &[sgf::Node],
+    variations: &[sgf::GameTree],
+    size: usize,
+) -> Result<Vec<Branch>, SgfError> {
+    let Some((node, rest)) = nodes.split_first() else {
+        let mut all = Vec::new();
+        for variation in variations {
+            all.extend(branches(&variation.nodes, &variation.variations, size)?);
+        }
+        return Ok(all);
+    };
+    let played = [("B", PlayerColor::Black), ("W", PlayerColor::White)]
+        .into_iter()
+        .find_map(|(ident, color)| node.first(ident).map(|value| (color, value)));
+    let Some((color, value)) = played else {
+        return branches(rest, variations, size);
+    };
+    let mv = match sgf::decode_point(value, size) {
+        Some(point) => Move::Play(point),
+        None if value.is_empty() || value == "tt" => Move::Pass,
+        None => {
+            return Err(SgfError::BadValue {
+                property: "B/W".to_string(),
+                value: value.to_string(),
+            })
+        }
+    };
+    Ok(vec![Branch {
+        color,
+        mv,
+        mark: mark(node),
+        comment: node.first("C").map(str::to_string),
+        children: branches(rest, variations, size)?,
+    }])
+}
+
+fn has_marks(branches: &[Branch]) -> bool {
+    branches
+        .iter()
+        .any(|b| b.mark != Mark::Unmarked || has_marks(&b.children))
+}
+
+/// A position to solve and the lines the collection knows about.
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub name: String,
+    pub start: Game,
+    // what the problem asks, e.g. "Black to live"
+    pub comment: Option<String>,
+    lines: Vec<Branch>,
+    // whether any line is marked; otherwise the main line is the solution
+    marked: bool,
+}
+impl Problem {
+    pub fn from_sgf(tree: &sgf::GameTree, number: usize) -> Result<Problem, SgfError> {
+        let mut root = tree.nodes.first().ok_or(SgfError::NoGame)?.clone();
+        // the root's setup stones and settings, without anything that follows
+        let setup = |root: &sgf::Node| {
+            Game::from_sgf(&sgf::GameTree {
+                nodes: vec![root.clone()],
+                variations: Vec::new(),
+            })
+        };
+        let mut start = setup(&root)?;
+        let lines = branches(&tree.nodes[1..], &tree.variations, start.settings.size)?;
+        // collections often leave out PL, so whoever moves first is the solver
+        if let (None, Some(first)) = (root.first("PL"), lines.first()) {
+            if first.color != start.to_move() {
+                let pl = match first.color {
+                    PlayerColor::Black => "B",
+                    PlayerColor::White => "W",
+                };
+                root.push("PL", pl.to_string());
+                start = setup(&root)?;
+            }
+        }
+        Ok(Problem {
+            name: root
+                .first("GN")
+                .map_or(format!("Problem {}", number), str::to_string),
+            comment: root.first("C").map(str::to_string),
+            marked: has_marks(&lines),
+            lines,
+            start,
+        })
+    }
+
+    /// The solver's colour.
+    pub fn solver(&self) -> PlayerColor {
+        self.start.to_move()
+    }
+}
+
+/// Every problem in an SGF file, one per game tree.
+pub fn parse_collection(text: &str) -> Result<Vec<Problem>, TsumegoError> {
+    let problems = sgf::parse(text)?
+        .iter()
+        .enumerate()
+        .map(|(i, tree)| Problem::from_sgf(tree, i + 1))
+        .collect::<Result<Vec<_>, _>>()?;
+    if problems.is_empty() {
+        return Err(TsumegoError::NoProblems);
+    }
+    Ok(problems)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// How the solver has done on one problem.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    pub attempts: u32,
+    pub solved: u32,
+}
+
+/// Results kept between sessions, keyed by collection file name and problem number.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Progress {
+    #[serde(default)]
+    pub problems: BTreeMap<String, Record>,
+}
+impl Progress {
+    /// A missing file means nothing tried yet; an unreadable one is reported and ignored.
+    pub fn load(path: impl AsRef<Path>) -> Progress {
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return Progress::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            log::warn!(
+                "ignoring unreadable progress in {}: {}",
+                path.as_ref().display(),
+                e
+            );
+            Progress::default()
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let text = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, text)
+    }
+}
+
+/// Works through a collection one problem at a time, answering the solver's
+/// moves and saving each result as it comes.
+pub struct Trainer {
+    // the collection's file name, which progress is kept under
+    pub name: String,
+    pub problems: Vec<Problem>,
+    pub current: usize,
+    pub status: Status,
+    // which branch was taken at each move of the attempt so far
+    path: Vec<usize>,
+    progress: Progress,
+    progress_path: PathBuf,
+}
+impl Trainer {
+    pub fn open(
+        collection: impl AsRef<Path>,
+        progress_path: impl Into<PathBuf>,
+    ) -> Result<Trainer, TsumegoError> {
+        let collection = collection.as_ref();
+        let problems = parse_collection(&std::fs::read_to_string(collection)?)?;
+        let name = collection
+            .file_name()
+            .map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let progress_path = progress_path.into();
+        Ok(Trainer {
+            name,
+            problems,
+            current: 0,
+            status: Status::Playing,
+            path: Vec::new(),
+            progress: Progress::load(&progress_path),
+            progress_path,
+        })
+    }
+
+    pub fn problem(&self) -> &Problem {
+        &self.problems[self.current]
+    }
+
+    /// Begins a fresh attempt at problem `index` and gives its starting position.
+    pub fn start(&mut self, index: usize) -> Game {
+        self.current = index.min(self.problems.len() - 1);
+        self.status = Status::Playing;
+        self.path.clear();
+        self.problem().start.clone()
+    }
+
+    // the moves that can come next
+    fn next_branches(&self) -> &[Branch] {
+        let mut branches = self.problem().lines.as_slice();
+        for &i in &self.path {
+            branches = &branches[i].children;
+        }
+        branches
+    }
+
+    fn last_branch(&self) -> Option<&Branch> {
+        let (&last, before) = self.path.split_last()?;
+        let mut branches = self.problem().lines.as_slice();
+        for &i in before {
+            branches = &branches[i].children;
+        }
+        Some(&branches[last])
+    }
+
+    /// Something to show the solver: the comment on the last move played, or
+    /// the problem's own before anything is.
+    pub fn comment(&self) -> Option<&str> {
+        match self.last_branch() {
+            Some(branch) => branch.comment.as_deref(),
+            None => self.problem().comment.as_deref(),
+        }
+    }
+
+    /// Takes the solver's move and gives the answer to it, if the line goes on.
+    /// Moves the collection doesn't know, and resigning, fail the problem.
+    pub fn play(&mut self, mv: Move) -> Option<Move> {
+        if self.status != Status::Playing {
+            return None;
+        }
+        let Some(i) = self.next_branches().iter().position(|b| b.mv == mv) else {
+            self.finish(Status::Failed);
+            return None;
+        };
+        self.path.push(i);
+        let branch = self.last_branch().unwrap();
+        let (mark, ends) = (branch.mark, branch.children.is_empty());
+        // the collection's first answer is its main line
+        let reply = branch.children.first().map(|b| b.mv);
+        if mark == Mark::Wrong {
+            self.finish(Status::Failed);
+            // still show how it goes wrong
+            if reply.is_some() {
+                self.path.push(0);
+            }
+            return reply;
+        }
+        if ends {
+            self.finish(self.ending());
+            return None;
+        }
+
+        self.path.push(0);
+        let answer = self.last_branch().unwrap();
+        let (mark, ends) = (answer.mark, answer.children.is_empty());
+        if mark == Mark::Wrong {
+            self.finish(Status::Failed);
+        } else if ends {
+            self.finish(self.ending());
+        }
+        reply
+    }
+
+    // whether the line just played to its end solves the problem
+    fn ending(&self) -> Status {
+        let solved = match self.last_branch().map(|b| b.mark) {
+            Some(Mark::Correct) => true,
+            Some(Mark::Wrong) | None => false,
+            Some(Mark::Unmarked) => !self.problem().marked && self.path.iter().all(|&i| i == 0),
+        };
+        if solved {
+            Status::Solved
+        } else {
+            Status::Failed
+        }
+    }
+
+    fn key(&self, index: usize) -> String {
+        format!("{}#{}", self.name, index + 1)
+    }
+
+    fn finish(&mut self, status: Status) {
+        self.status = status;
+        let key = self.key(self.current);
+        let record = self.progress.problems.entry(key).or_default();
+        record.attempts += 1;
+        if status == Status::Solved {
+            record.solved += 1;
+        }
+        if let Err(e) = self.progress.save(&self.progress_path) {
+            log::warn!(
+                "couldn't save progress to {}: {}",
+                self.progress_path.display(),
+                e
+            );
+        }
+    }
+
+    pub fn record(&self, index: usize) -> Record {
+        self.progress
+            .problems
+            .get(&self.key(index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// How many problems in the collection have been solved at least once.
+    pub fn solved_count(&self) -> usize {
+        (0..self.problems.len())
+            .filter(|&i| self.record(i).solved > 0)
+            .count()
+    }
+}
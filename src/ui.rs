@@ -0,0 +1,1186 @@
+use crate::ai::{Budget, Computer};
+use crate::analysis::{Analysis, Engine};
+use crate::board::point_name;
+use crate::board::{PlayerColor, Point, Symmetry};
+use crate::clock::TimeControl;
+use crate::database::{Database, Hit, SearchResult};
+use crate::game::{max_handicap, Game, GameSettings, Move, Rules};
+use crate::history::UndoPolicy;
+use crate::joseki::Library;
+use crate::net::{ChatLine, GameListing, DEFAULT_PORT};
+use crate::tsumego::{Status, Trainer};
+use winit::window::Window;
+
+pub const SIDE_PANEL_WIDTH: f32 = 220.0;
+const GRAPH_HEIGHT: f32 = 80.0;
+// how much of a database search is listed in the side panel
+const LISTED_CONTINUATIONS: usize = 8;
+const LISTED_GAMES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkerStyle {
+    Circle,
+    Dot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveNumbers {
+    Off,
+    Last(usize),
+    All,
+}
+
+/// How the board is drawn; changed from the side panel.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayOptions {
+    pub last_move_marker: Option<MarkerStyle>,
+    pub move_numbers: MoveNumbers,
+    pub coordinates: bool,
+    // who looks like owning each point, shaded under the stones
+    pub ownership: bool,
+    // teaching aids: liberty counts on groups short of them, stones in
+    // atari picked out in red, and an outline round each group
+    pub liberties: bool,
+    pub atari: bool,
+    pub outlines: bool,
+    // the joseki library's next moves in each corner, when one is open
+    pub joseki: bool,
+    // how the board is turned on screen, for looking at it from the other side
+    pub orientation: Symmetry,
+}
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            last_move_marker: Some(MarkerStyle::Circle),
+            move_numbers: MoveNumbers::Off,
+            coordinates: true,
+            ownership: false,
+            liberties: false,
+            atari: false,
+            outlines: false,
+            joseki: true,
+            orientation: Symmetry::IDENTITY,
+        }
+    }
+}
+
+pub enum UiAction {
+    // with the computer opponent to play against, if any
+    NewGame(GameSettings, Option<Computer>),
+    Pass,
+    Resign,
+    SaveSgf(String),
+    LoadSgf(String),
+    Host(u16),
+    Connect(String),
+    Disconnect,
+    AnswerUndo(bool),
+    JoinGame(u32),
+    WatchGame(u32),
+    LeaveGame,
+    SendChat(String),
+    Analyze(Engine),
+    StopAnalysis,
+    // look at the position after this many moves
+    ShowMove(usize),
+    OpenProblems(String),
+    // a fresh attempt at the problem with this index
+    StartProblem(usize),
+    CloseProblems,
+    OpenJoseki(String),
+    CloseJoseki,
+    OpenDatabase,
+    // add the games in this folder to the database
+    IndexGames(String),
+    CloseDatabase,
+    // the next two clicks on the board are the corners of the region to search
+    PickRegion,
+    WholeBoard,
+    SearchGames { swap_colors: bool },
+    OpenFoundGame(Hit),
+}
+
+// the new-game window keeps its own copy of the settings until "Start" is pressed
+struct SetupForm {
+    settings: GameSettings,
+    timed: bool,
+    main_minutes: u32,
+    periods: u32,
+    period_seconds: u32,
+    // None when setting up a game against someone on the network
+    computer: Option<ComputerForm>,
+}
+impl SetupForm {
+    fn new(settings: GameSettings, computer: Option<ComputerForm>) -> Self {
+        let (timed, main_minutes, periods, period_seconds) = match settings.time_control {
+            TimeControl::Unlimited => (false, 10, 5, 30),
+            TimeControl::ByoYomi {
+                main_time,
+                periods,
+                period_time,
+            } => (
+                true,
+                (main_time.as_secs() / 60) as u32,
+                periods,
+                period_time.as_secs() as u32,
+            ),
+        };
+        SetupForm {
+            settings,
+            timed,
+            main_minutes,
+            periods,
+            period_seconds,
+            computer,
+        }
+    }
+
+    fn settings(&self) -> GameSettings {
+        let time_control = if self.timed {
+            TimeControl::ByoYomi {
+                main_time: std::time::Duration::from_secs(self.main_minutes as u64 * 60),
+                periods: self.periods,
+                period_time: std::time::Duration::from_secs(self.period_seconds as u64),
+            }
+        } else {
+            TimeControl::Unlimited
+        };
+        GameSettings {
+            time_control,
+            ..self.settings
+        }
+    }
+}
+
+struct ComputerForm {
+    enabled: bool,
+    color: PlayerColor,
+    timed: bool,
+    playouts: u32,
+    seconds: f32,
+    // an ONNX file; empty for plain playouts
+    network: String,
+}
+impl ComputerForm {
+    fn new(computer: Option<&Computer>) -> Self {
+        let (color, budget) = computer.map_or((PlayerColor::White, Budget::default()), |c| {
+            (c.color, c.budget)
+        });
+        let network = computer
+            .and_then(|c| c.network.as_ref())
+            .map_or(String::new(), |path| path.display().to_string());
+        let (timed, playouts, seconds) = match budget {
+            Budget::Playouts(playouts) => (false, playouts, 5.0),
+            Budget::Time(time) => (true, 2000, time.as_secs_f32()),
+        };
+        ComputerForm {
+            enabled: computer.is_some(),
+            color,
+            timed,
+            playouts,
+            seconds,
+            network,
+        }
+    }
+
+    fn computer(&self) -> Option<Computer> {
+        let budget = if self.timed {
+            Budget::Time(std::time::Duration::from_secs_f32(self.seconds))
+        } else {
+            Budget::Playouts(self.playouts)
+        };
+        let network = self.network.trim();
+        self.enabled.then(|| Computer {
+            color: self.color,
+            budget,
+            network: (!network.is_empty()).then(|| network.into()),
+        })
+    }
+}
+
+struct AnalysisForm {
+    gtp: bool,
+    playouts: u32,
+    command: String,
+    seconds: f32,
+}
+impl AnalysisForm {
+    fn engine(&self) -> Engine {
+        if self.gtp {
+            Engine::Gtp {
+                command: self.command.clone(),
+                time: std::time::Duration::from_secs_f32(self.seconds),
+            }
+        } else {
+            Engine::BuiltIn(Budget::Playouts(self.playouts))
+        }
+    }
+}
+
+/// What the study tools have to show this frame.
+pub struct Study<'a> {
+    // what the engine made of each position so far
+    pub analyses: &'a [Option<Analysis>],
+    // the problems being worked through, if any
+    pub trainer: Option<&'a Trainer>,
+    pub joseki: Option<&'a Library>,
+    pub database: Option<&'a Database>,
+    // indexing or a search is running
+    pub database_busy: bool,
+    // the region searched for, if not the whole board, and whether it is being picked out
+    pub region: Option<(Point, Point)>,
+    pub picking_region: bool,
+    pub found: Option<&'a SearchResult>,
+}
+
+/// The egui layer drawn over the board: game setup, the move list and the
+/// pass/resign controls.
+pub struct Ui {
+    ctx: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_delta: egui::TexturesDelta,
+    textures_to_free: Vec<egui::TextureId>,
+    screen_descriptor: egui_wgpu::ScreenDescriptor,
+    setup: Option<SetupForm>,
+    message: Option<String>,
+    sgf_path: String,
+    problems_path: String,
+    joseki_path: String,
+    games_folder: String,
+    either_color: bool,
+    net_port: u16,
+    net_address: String,
+    chat_draft: String,
+    pub display: DisplayOptions,
+    // what the network connection is doing, while there is one
+    pub network: Option<String>,
+    // the opponent asked to take back a move and is waiting for an answer
+    pub undo_requested: bool,
+    // the tables on the server, when connected to one
+    pub lobby: Option<Vec<GameListing>>,
+    // what the setup form last started a game against, to offer it again
+    last_computer: Option<Computer>,
+    analysis: AnalysisForm,
+    // an engine is working through the game
+    pub analysing: bool,
+}
+impl Ui {
+    pub fn new(
+        window: &Window,
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+    ) -> Self {
+        let ctx = egui::Context::default();
+        let state = egui_winit::State::new(
+            ctx.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            None,
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(device, texture_format, None, 1, false);
+        let size = window.inner_size();
+
+        Ui {
+            ctx,
+            state,
+            renderer,
+            paint_jobs: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            textures_to_free: Vec::new(),
+            screen_descriptor: egui_wgpu::ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point: window.scale_factor() as f32,
+            },
+            setup: None,
+            message: None,
+            sgf_path: "game.sgf".to_string(),
+            problems_path: "problems.sgf".to_string(),
+            joseki_path: "joseki.sgf".to_string(),
+            games_folder: "games".to_string(),
+            either_color: false,
+            net_port: DEFAULT_PORT,
+            net_address: format!("127.0.0.1:{}", DEFAULT_PORT),
+            chat_draft: String::new(),
+            display: DisplayOptions::default(),
+            network: None,
+            undo_requested: false,
+            lobby: None,
+            last_computer: None,
+            analysis: AnalysisForm {
+                gtp: false,
+                playouts: 1000,
+                command: String::new(),
+                seconds: 2.0,
+            },
+            analysing: false,
+        }
+    }
+
+    /// Returns true when egui wants the event for itself, e.g. a click on a button.
+    pub fn handle_event(&mut self, window: &Window, event: &winit::event::WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
+    }
+
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// Lays out this frame's UI and returns what the user asked for.
+    /// `review` is the move number being looked back at, if any, and
+    /// `study` what the analysis, problem and joseki tools have to show.
+    pub fn run(
+        &mut self,
+        window: &Window,
+        game: &Game,
+        review: Option<usize>,
+        chat: &[ChatLine],
+        study: Study,
+    ) -> Vec<UiAction> {
+        let mut actions = Vec::new();
+        let raw_input = self.state.take_egui_input(window);
+        let ctx = self.ctx.clone();
+        let full_output = ctx.run(raw_input, |ctx| {
+            self.side_panel(ctx, game, review, &study, &mut actions);
+            self.setup_window(ctx, &mut actions);
+            if self.network.is_some() {
+                self.chat_window(ctx, chat, &mut actions);
+            }
+        });
+
+        self.state
+            .handle_platform_output(window, full_output.platform_output);
+        self.paint_jobs = self
+            .ctx
+            .tessellate(full_output.shapes, full_output.pixels_per_point);
+        self.textures_delta.append(full_output.textures_delta);
+        let size = window.inner_size();
+        self.screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: full_output.pixels_per_point,
+        };
+        actions
+    }
+
+    fn side_panel(
+        &mut self,
+        ctx: &egui::Context,
+        game: &Game,
+        review: Option<usize>,
+        study: &Study,
+        actions: &mut Vec<UiAction>,
+    ) {
+        egui::SidePanel::right("side panel")
+            .exact_width(SIDE_PANEL_WIDTH)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(format!(
+                    "{}x{} · {} rules",
+                    game.settings.size,
+                    game.settings.size,
+                    game.settings.rules.name()
+                ));
+                ui.label(format!(
+                    "Komi {} · Handicap {}",
+                    game.settings.komi, game.settings.handicap
+                ));
+                ui.separator();
+
+                for color in [PlayerColor::Black, PlayerColor::White] {
+                    let marker = if !game.is_over() && game.to_move() == color {
+                        "▶"
+                    } else {
+                        " "
+                    };
+                    ui.label(format!(
+                        "{} {}  {}  captures {}",
+                        marker,
+                        color.name(),
+                        game.clock.display(color.index()),
+                        game.captures(color)
+                    ));
+                }
+                if let Some(result) = game.result() {
+                    ui.strong(format!("Result: {}", result));
+                }
+                if let Some(n) = review {
+                    ui.strong(format!("Reviewing move {} of {}", n, game.moves().len()));
+                }
+                if let Some(message) = &self.message {
+                    ui.colored_label(egui::Color32::LIGHT_RED, message);
+                }
+                if self.undo_requested {
+                    ui.horizontal(|ui| {
+                        ui.label("Opponent asks to undo");
+                        if ui.button("Allow").clicked() {
+                            actions.push(UiAction::AnswerUndo(true));
+                            self.undo_requested = false;
+                        }
+                        if ui.button("Refuse").clicked() {
+                            actions.push(UiAction::AnswerUndo(false));
+                            self.undo_requested = false;
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!game.is_over(), egui::Button::new("Pass"))
+                        .clicked()
+                    {
+                        actions.push(UiAction::Pass);
+                    }
+                    if ui
+                        .add_enabled(!game.is_over(), egui::Button::new("Resign"))
+                        .clicked()
+                    {
+                        actions.push(UiAction::Resign);
+                    }
+                    if ui.button("New game…").clicked() {
+                        let computer = self
+                            .network
+                            .is_none()
+                            .then(|| ComputerForm::new(self.last_computer.as_ref()));
+                        self.setup = Some(SetupForm::new(game.settings, computer));
+                    }
+                });
+                ui.separator();
+
+                self.display_options(ui);
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("SGF");
+                    ui.add(egui::TextEdit::singleline(&mut self.sgf_path).desired_width(90.0));
+                    if ui.button("Save").clicked() {
+                        actions.push(UiAction::SaveSgf(self.sgf_path.clone()));
+                    }
+                    if ui.button("Load").clicked() {
+                        actions.push(UiAction::LoadSgf(self.sgf_path.clone()));
+                    }
+                });
+                self.network_options(ui, game, actions);
+                self.problem_options(ui, study.trainer, actions);
+                self.joseki_options(ui, game, study.joseki, actions);
+                self.database_options(ui, game, study, actions);
+                let shown = review.unwrap_or(game.moves().len());
+                self.analysis_options(ui, shown, study.analyses, actions);
+                ui.collapsing("Markup", |ui| {
+                    ui.small("Click with modifiers held to mark a point:");
+                    for (keys, markup) in [
+                        ("Shift", "triangle"),
+                        ("Ctrl", "square"),
+                        ("Alt", "circle"),
+                        ("Ctrl+Shift", "cross"),
+                        ("Alt+Shift", "next label"),
+                        ("Ctrl+Alt", "black territory"),
+                        ("Ctrl+Alt+Shift", "white territory"),
+                    ] {
+                        ui.small(format!("{}: {}", keys, markup));
+                    }
+                });
+                ui.separator();
+
+                ui.label("Moves");
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, (color, mv)) in game.moves().iter().enumerate() {
+                            let what = match mv {
+                                Move::Play(point) => point_name(*point, game.settings.size),
+                                Move::Pass => "pass".to_string(),
+                                Move::Resign => "resign".to_string(),
+                            };
+                            ui.monospace(format!("{:>3}. {} {}", i + 1, color.name(), what));
+                        }
+                    });
+            });
+    }
+
+    fn network_options(&mut self, ui: &mut egui::Ui, game: &Game, actions: &mut Vec<UiAction>) {
+        ui.collapsing("Network", |ui| {
+            // browsers can't open TCP sockets
+            if cfg!(target_arch = "wasm32") {
+                ui.small("Not available in the browser");
+                return;
+            }
+            if let Some(status) = &self.network {
+                ui.label(status);
+                ui.horizontal(|ui| {
+                    if ui.button("Disconnect").clicked() {
+                        actions.push(UiAction::Disconnect);
+                    }
+                    if self.lobby.is_some() {
+                        if ui.button("Open a game…").clicked() {
+                            self.setup = Some(SetupForm::new(game.settings, None));
+                        }
+                        if ui.button("Leave").clicked() {
+                            actions.push(UiAction::LeaveGame);
+                        }
+                    }
+                });
+                if let Some(games) = &self.lobby {
+                    if games.is_empty() {
+                        ui.small("No games yet");
+                    }
+                    for listing in games {
+                        ui.horizontal(|ui| {
+                            ui.small(format!(
+                                "#{} {}x{} · {}",
+                                listing.id,
+                                listing.settings.size,
+                                listing.settings.size,
+                                if listing.open {
+                                    "waiting".to_string()
+                                } else {
+                                    format!("move {}", listing.moves)
+                                }
+                            ));
+                            if listing.open && ui.small_button("Join").clicked() {
+                                actions.push(UiAction::JoinGame(listing.id));
+                            }
+                            if ui.small_button("Watch").clicked() {
+                                actions.push(UiAction::WatchGame(listing.id));
+                            }
+                        });
+                    }
+                }
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.label("Port");
+                ui.add(egui::DragValue::new(&mut self.net_port));
+                if ui.button("Host").clicked() {
+                    actions.push(UiAction::Host(self.net_port));
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.net_address).desired_width(120.0));
+                if ui.button("Connect").clicked() {
+                    actions.push(UiAction::Connect(self.net_address.clone()));
+                }
+            });
+        });
+    }
+
+    fn problem_options(
+        &mut self,
+        ui: &mut egui::Ui,
+        trainer: Option<&Trainer>,
+        actions: &mut Vec<UiAction>,
+    ) {
+        ui.collapsing("Problems", |ui| {
+            let Some(trainer) = trainer else {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.problems_path).desired_width(120.0),
+                    );
+                    if ui.button("Open").clicked() {
+                        actions.push(UiAction::OpenProblems(self.problems_path.clone()));
+                    }
+                });
+                return;
+            };
+            let current = trainer.current;
+            let problem = trainer.problem();
+            ui.label(format!(
+                "{} of {}: {}",
+                current + 1,
+                trainer.problems.len(),
+                problem.name
+            ));
+            match trainer.status {
+                Status::Playing => {
+                    ui.label(format!("{} to play", problem.solver().name()));
+                }
+                Status::Solved => {
+                    ui.colored_label(egui::Color32::from_rgb(80, 180, 80), "Solved");
+                }
+                Status::Failed => {
+                    ui.colored_label(egui::Color32::from_rgb(220, 80, 60), "Failed");
+                }
+            }
+            if let Some(comment) = trainer.comment() {
+                ui.small(comment);
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(current > 0, egui::Button::new("◀"))
+                    .clicked()
+                {
+                    actions.push(UiAction::StartProblem(current - 1));
+                }
+                if ui.button("Retry").clicked() {
+                    actions.push(UiAction::StartProblem(current));
+                }
+                if ui
+                    .add_enabled(current + 1 < trainer.problems.len(), egui::Button::new("▶"))
+                    .clicked()
+                {
+                    actions.push(UiAction::StartProblem(current + 1));
+                }
+                if ui.button("Close").clicked() {
+                    actions.push(UiAction::CloseProblems);
+                }
+            });
+            let record = trainer.record(current);
+            ui.small(format!(
+                "Solved {} of {} tries · {} of {} problems solved",
+                record.solved,
+                record.attempts,
+                trainer.solved_count(),
+                trainer.problems.len()
+            ));
+        });
+    }
+
+    fn joseki_options(
+        &mut self,
+        ui: &mut egui::Ui,
+        game: &Game,
+        library: Option<&Library>,
+        actions: &mut Vec<UiAction>,
+    ) {
+        ui.collapsing("Joseki", |ui| {
+            let Some(library) = library else {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.joseki_path).desired_width(120.0));
+                    if ui.button("Open").clicked() {
+                        actions.push(UiAction::OpenJoseki(self.joseki_path.clone()));
+                    }
+                });
+                return;
+            };
+            ui.label(format!("{} corner positions", library.len()));
+            if library.size() != game.settings.size {
+                ui.small(format!("The library is for {0}x{0} boards", library.size()));
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.display.joseki, "Show on the board");
+                if ui.button("Close").clicked() {
+                    actions.push(UiAction::CloseJoseki);
+                }
+            });
+        });
+    }
+
+    fn database_options(
+        &mut self,
+        ui: &mut egui::Ui,
+        game: &Game,
+        study: &Study,
+        actions: &mut Vec<UiAction>,
+    ) {
+        ui.collapsing("Database", |ui| {
+            // no files to read in the browser
+            if cfg!(target_arch = "wasm32") {
+                ui.small("Not available in the browser");
+                return;
+            }
+            let idle = !study.database_busy;
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.games_folder).desired_width(90.0));
+                if ui
+                    .add_enabled(idle, egui::Button::new("Index"))
+                    .on_hover_text("Add the SGF files in this folder")
+                    .clicked()
+                {
+                    actions.push(UiAction::IndexGames(self.games_folder.clone()));
+                }
+                if study.database.is_none()
+                    && ui.add_enabled(idle, egui::Button::new("Open")).clicked()
+                {
+                    actions.push(UiAction::OpenDatabase);
+                }
+            });
+            if !idle {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.small("Working…");
+                });
+            }
+            let Some(database) = study.database else {
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.label(format!("{} games", database.len()));
+                if ui.button("Close").clicked() {
+                    actions.push(UiAction::CloseDatabase);
+                }
+            });
+
+            let size = game.settings.size;
+            ui.horizontal(|ui| {
+                ui.label("Region");
+                let region = match study.region {
+                    _ if study.picking_region => "click two corners".to_string(),
+                    Some((a, b)) => format!("{}–{}", point_name(a, size), point_name(b, size)),
+                    None => "whole board".to_string(),
+                };
+                ui.small(region);
+                if ui.small_button("Pick").clicked() {
+                    actions.push(UiAction::PickRegion);
+                }
+                if study.region.is_some() && ui.small_button("All").clicked() {
+                    actions.push(UiAction::WholeBoard);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.either_color, "Either colour");
+                if ui.add_enabled(idle, egui::Button::new("Search")).clicked() {
+                    actions.push(UiAction::SearchGames {
+                        swap_colors: self.either_color,
+                    });
+                }
+            });
+
+            let Some(found) = study.found else {
+                return;
+            };
+            ui.small(format!("Found in {} games", found.hits.len()));
+            for continuation in found.continuations.iter().take(LISTED_CONTINUATIONS) {
+                let at = continuation
+                    .point
+                    .map_or("elsewhere".to_string(), |point| point_name(point, size));
+                ui.small(format!(
+                    "{} {} · {} games · won {:.0}%",
+                    continuation.color.name(),
+                    at,
+                    continuation.games,
+                    continuation.wins as f32 / continuation.games as f32 * 100.0
+                ));
+            }
+            egui::ScrollArea::vertical()
+                .id_salt("found games")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for &hit in found.hits.iter().take(LISTED_GAMES) {
+                        let Some(entry) = database.game(hit.game) else {
+                            continue;
+                        };
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Open").clicked() {
+                                actions.push(UiAction::OpenFoundGame(hit));
+                            }
+                            ui.small(format!(
+                                "{} – {} {} · move {}",
+                                entry.black, entry.white, entry.result, hit.move_number
+                            ));
+                        });
+                    }
+                });
+        });
+    }
+
+    fn analysis_options(
+        &mut self,
+        ui: &mut egui::Ui,
+        shown: usize,
+        analyses: &[Option<Analysis>],
+        actions: &mut Vec<UiAction>,
+    ) {
+        ui.collapsing("Analysis", |ui| {
+            // no threads to think on and no engines to run
+            if cfg!(target_arch = "wasm32") {
+                ui.small("Not available in the browser");
+                return;
+            }
+            let form = &mut self.analysis;
+            if self.analysing {
+                ui.horizontal(|ui| {
+                    let done = analyses.iter().flatten().count();
+                    ui.label(format!("Analysed {} of {}", done, analyses.len()));
+                    if ui.button("Stop").clicked() {
+                        actions.push(UiAction::StopAnalysis);
+                    }
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut form.gtp, false, "Built-in");
+                    ui.radio_value(&mut form.gtp, true, "GTP engine");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Per position");
+                    if form.gtp {
+                        ui.add(
+                            egui::DragValue::new(&mut form.seconds)
+                                .speed(0.1)
+                                .range(0.1..=60.0)
+                                .suffix(" s"),
+                        );
+                    } else {
+                        ui.add(
+                            egui::DragValue::new(&mut form.playouts)
+                                .speed(50)
+                                .range(10..=100_000)
+                                .suffix(" playouts"),
+                        );
+                    }
+                });
+                if form.gtp {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut form.command)
+                            .hint_text("katago gtp -model …")
+                            .desired_width(200.0),
+                    );
+                }
+                if ui.button("Analyse game").clicked() {
+                    actions.push(UiAction::Analyze(form.engine()));
+                }
+            }
+
+            if analyses.iter().flatten().next().is_none() {
+                return;
+            }
+            if let Some(n) = winrate_graph(ui, analyses, shown) {
+                actions.push(UiAction::ShowMove(n));
+            }
+            ui.small("Blue: Black's win rate · orange: Black's lead");
+            let Some(Some(analysis)) = analyses.get(shown) else {
+                return;
+            };
+            if let Some(winrate) = analysis.black_winrate() {
+                let lead = analysis
+                    .black_score_lead()
+                    .map_or(String::new(), |lead| format!(", leads by {:.1}", lead));
+                ui.small(format!("Black {:.1}%{}", winrate * 100.0, lead));
+            }
+        });
+    }
+
+    fn chat_window(&mut self, ctx: &egui::Context, chat: &[ChatLine], actions: &mut Vec<UiAction>) {
+        egui::Window::new("Chat")
+            .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+            .default_width(260.0)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(120.0)
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for line in chat {
+                            ui.small(format!(
+                                "[{}] {}: {}",
+                                line.move_number, line.from, line.text
+                            ));
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    let input = ui
+                        .add(egui::TextEdit::singleline(&mut self.chat_draft).desired_width(190.0));
+                    let entered =
+                        input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Send").clicked() || entered)
+                        && !self.chat_draft.trim().is_empty()
+                    {
+                        actions.push(UiAction::SendChat(std::mem::take(&mut self.chat_draft)));
+                        input.request_focus();
+                    }
+                });
+            });
+    }
+
+    fn display_options(&mut self, ui: &mut egui::Ui) {
+        let display = &mut self.display;
+        ui.horizontal(|ui| {
+            ui.label("Last move");
+            ui.selectable_value(&mut display.last_move_marker, None, "off");
+            ui.selectable_value(
+                &mut display.last_move_marker,
+                Some(MarkerStyle::Circle),
+                "circle",
+            );
+            ui.selectable_value(&mut display.last_move_marker, Some(MarkerStyle::Dot), "dot");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut display.coordinates, "Coordinates");
+            ui.checkbox(&mut display.ownership, "Ownership");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Numbers");
+            ui.selectable_value(&mut display.move_numbers, MoveNumbers::Off, "off");
+            let last = match display.move_numbers {
+                MoveNumbers::Last(n) => n,
+                _ => 10,
+            };
+            ui.selectable_value(&mut display.move_numbers, MoveNumbers::Last(last), "last");
+            ui.selectable_value(&mut display.move_numbers, MoveNumbers::All, "all");
+            if let MoveNumbers::Last(n) = &mut display.move_numbers {
+                ui.add(egui::DragValue::new(n).range(1..=99));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Show");
+            ui.checkbox(&mut display.liberties, "liberties");
+            ui.checkbox(&mut display.atari, "atari");
+            ui.checkbox(&mut display.outlines, "groups");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Board");
+            if ui.button("Rotate").clicked() {
+                display.orientation = display.orientation.then(Symmetry::ROTATE);
+            }
+            if ui.button("Flip").clicked() {
+                display.orientation = display.orientation.then(Symmetry::MIRROR);
+            }
+            let turned = display.orientation != Symmetry::IDENTITY;
+            if ui.add_enabled(turned, egui::Button::new("Reset")).clicked() {
+                display.orientation = Symmetry::IDENTITY;
+            }
+        });
+    }
+
+    fn setup_window(&mut self, ctx: &egui::Context, actions: &mut Vec<UiAction>) {
+        let Some(form) = self.setup.as_mut() else {
+            return;
+        };
+        let mut close = false;
+        egui::Window::new("New game")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("setup grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Board size");
+                    ui.horizontal(|ui| {
+                        for size in [9, 13, 19] {
+                            ui.radio_value(&mut form.settings.size, size, format!("{0}x{0}", size));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Komi");
+                    ui.add(
+                        egui::DragValue::new(&mut form.settings.komi)
+                            .speed(0.5)
+                            .range(-50.0..=50.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Handicap");
+                    let most = max_handicap(form.settings.size);
+                    form.settings.handicap = form.settings.handicap.min(most);
+                    ui.add(egui::Slider::new(&mut form.settings.handicap, 0..=most));
+                    ui.end_row();
+
+                    ui.label("Rules");
+                    egui::ComboBox::from_id_salt("rules")
+                        .selected_text(form.settings.rules.name())
+                        .show_ui(ui, |ui| {
+                            for rules in [Rules::Japanese, Rules::Chinese] {
+                                ui.selectable_value(&mut form.settings.rules, rules, rules.name());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Rated");
+                    ui.checkbox(&mut form.settings.rated, "");
+                    ui.end_row();
+
+                    ui.label("Undo");
+                    egui::ComboBox::from_id_salt("undo policy")
+                        .selected_text(form.settings.undo_policy.name())
+                        .show_ui(ui, |ui| {
+                            for policy in [
+                                UndoPolicy::Always,
+                                UndoPolicy::UnratedOnly,
+                                UndoPolicy::Never,
+                            ] {
+                                ui.selectable_value(
+                                    &mut form.settings.undo_policy,
+                                    policy,
+                                    policy.name(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Time control");
+                    ui.checkbox(&mut form.timed, "Byo-yomi");
+                    ui.end_row();
+
+                    if form.timed {
+                        ui.label("Main time (min)");
+                        ui.add(egui::DragValue::new(&mut form.main_minutes).range(0..=180));
+                        ui.end_row();
+
+                        ui.label("Periods");
+                        ui.add(egui::DragValue::new(&mut form.periods).range(0..=10));
+                        ui.end_row();
+
+                        ui.label("Period (s)");
+                        ui.add(egui::DragValue::new(&mut form.period_seconds).range(5..=300));
+                        ui.end_row();
+                    }
+
+                    if let Some(computer) = form.computer.as_mut() {
+                        ui.label("Opponent");
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut computer.enabled, false, "Human");
+                            ui.radio_value(&mut computer.enabled, true, "Computer");
+                        });
+                        ui.end_row();
+
+                        if computer.enabled {
+                            ui.label("Computer plays");
+                            ui.horizontal(|ui| {
+                                for color in [PlayerColor::Black, PlayerColor::White] {
+                                    ui.radio_value(&mut computer.color, color, color.name());
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Thinks for");
+                            ui.horizontal(|ui| {
+                                if computer.timed {
+                                    ui.add(
+                                        egui::DragValue::new(&mut computer.seconds)
+                                            .speed(0.1)
+                                            .range(0.1..=60.0)
+                                            .suffix(" s"),
+                                    );
+                                } else {
+                                    ui.add(
+                                        egui::DragValue::new(&mut computer.playouts)
+                                            .speed(50)
+                                            .range(10..=100_000)
+                                            .suffix(" playouts"),
+                                    );
+                                }
+                                ui.checkbox(&mut computer.timed, "Timed");
+                            });
+                            ui.end_row();
+
+                            if cfg!(feature = "nn") {
+                                ui.label("Network (ONNX)");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut computer.network)
+                                        .hint_text("none, just playouts")
+                                        .desired_width(140.0),
+                                );
+                                ui.end_row();
+                            }
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Start").clicked() {
+                        let computer = form.computer.as_ref().and_then(ComputerForm::computer);
+                        if form.computer.is_some() {
+                            self.last_computer = computer.clone();
+                        }
+                        actions.push(UiAction::NewGame(form.settings(), computer));
+                        close = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+        if close {
+            self.setup = None;
+            self.message = None;
+        }
+    }
+
+    /// Uploads textures and vertex data for the frame laid out by `run`. Must be
+    /// called before the render pass that draws the UI begins.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        // textures egui let go of last frame are no longer drawn and can be freed now
+        for id in std::mem::take(&mut self.textures_to_free) {
+            self.renderer.free_texture(&id);
+        }
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        for (id, image_delta) in &textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        self.textures_to_free = textures_delta.free;
+        self.renderer.update_buffers(
+            device,
+            queue,
+            encoder,
+            &self.paint_jobs,
+            &self.screen_descriptor,
+        );
+    }
+
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass<'static>) {
+        self.renderer
+            .render(render_pass, &self.paint_jobs, &self.screen_descriptor);
+    }
+}
+
+/// Black's win rate after each move, with the score lead where the engine
+/// gives one, and a line at the position being shown. Gives the position
+/// under the pointer while the graph is clicked or dragged.
+fn winrate_graph(ui: &mut egui::Ui, analyses: &[Option<Analysis>], shown: usize) -> Option<usize> {
+    let size = egui::vec2(ui.available_width(), GRAPH_HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(30));
+    painter.line_segment(
+        [rect.left_center(), rect.right_center()],
+        egui::Stroke::new(1.0, egui::Color32::from_gray(80)),
+    );
+
+    let last = analyses.len().saturating_sub(1).max(1) as f32;
+    let x = |n: usize| rect.left() + rect.width() * n as f32 / last;
+    // leads are scaled so the biggest one just fits, and never less than ten points
+    let biggest = analyses
+        .iter()
+        .flatten()
+        .filter_map(Analysis::black_score_lead)
+        .fold(10.0, |biggest, lead| lead.abs().max(biggest));
+    let leads = analyses
+        .iter()
+        .enumerate()
+        .filter_map(|(n, analysis)| {
+            let lead = analysis.as_ref()?.black_score_lead()?;
+            Some(egui::pos2(
+                x(n),
+                rect.center().y - lead / biggest * rect.height() / 2.0,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let winrates = analyses
+        .iter()
+        .enumerate()
+        .filter_map(|(n, analysis)| {
+            let winrate = analysis.as_ref()?.black_winrate()?;
+            Some(egui::pos2(x(n), rect.bottom() - winrate * rect.height()))
+        })
+        .collect::<Vec<_>>();
+    painter.add(egui::Shape::line(
+        leads,
+        egui::Stroke::new(1.0, egui::Color32::from_rgb(230, 160, 60)),
+    ));
+    painter.add(egui::Shape::line(
+        winrates,
+        egui::Stroke::new(1.5, egui::Color32::from_rgb(90, 170, 255)),
+    ));
+    painter.line_segment(
+        [
+            egui::pos2(x(shown), rect.top()),
+            egui::pos2(x(shown), rect.bottom()),
+        ],
+        egui::Stroke::new(1.0, egui::Color32::LIGHT_RED),
+    );
+
+    let pointer = response.interact_pointer_pos()?;
+    let n = ((pointer.x - rect.left()) / rect.width() * last)
+        .round()
+        .max(0.0) as usize;
+    Some(n.min(analyses.len() - 1))
+}
@@ -0,0 +1,51 @@
+//! The pieces only the browser build needs: finding the page's canvas and
+//! fetching assets over HTTP instead of reading them from disk.
+
+use anyhow::anyhow;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{HtmlCanvasElement, Response};
+
+/// The id index.html gives the canvas the game draws on.
+pub const CANVAS_ID: &str = "go";
+
+pub fn init_logging() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    if let Err(e) = console_log::init_with_level(log::Level::Warn) {
+        web_sys::console::error_1(&e.to_string().into());
+    }
+}
+
+/// The page's canvas, if it has one; without it winit appends its own.
+pub fn canvas() -> Option<HtmlCanvasElement> {
+    web_sys::window()?
+        .document()?
+        .get_element_by_id(CANVAS_ID)?
+        .dyn_into()
+        .ok()
+}
+
+/// Fetches `res/<file_name>` relative to the page.
+pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
+    let window = web_sys::window().ok_or_else(|| anyhow!("no window to fetch from"))?;
+    let url = format!("res/{}", file_name);
+    let response: Response = JsFuture::from(window.fetch_with_str(&url))
+        .await
+        .map_err(|e| anyhow!("couldn't fetch {}: {:?}", url, e))?
+        .dyn_into()
+        .map_err(|e| anyhow!("{} didn't give a response: {:?}", url, e))?;
+    if !response.ok() {
+        return Err(anyhow!(
+            "couldn't fetch {}: HTTP {}",
+            url,
+            response.status()
+        ));
+    }
+    let body = response
+        .array_buffer()
+        .map_err(|e| anyhow!("couldn't read {}: {:?}", url, e))?;
+    let buffer = JsFuture::from(body)
+        .await
+        .map_err(|e| anyhow!("couldn't read {}: {:?}", url, e))?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -0,0 +1,166 @@
+//! Indexing a folder of game records and searching it.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::path::PathBuf;
+
+use go::board::{Board, PlayerColor};
+use go::database::{Continuation, Database, Pattern, SearchResult};
+use go::game::{Game, Move};
+
+// the same opening twice, the second mirrored left to right, then two games
+// in one file, a game too big for "tt" to mean a pass, and a broken file
+const FILES: &[(&str, &str)] = &[
+    ("a.sgf", "(;SZ[19]RE[B+R];B[pd];W[dp];B[pp];W[dd];B[fq])"),
+    (
+        "more/b.sgf",
+        "(;SZ[19]RE[W+2.5];B[dd];W[pp];B[dp];W[pd];B[nq])",
+    ),
+    (
+        "c.SGF",
+        "(;SZ[19]RE[W+R];B[qq];W[cc])(;SZ[9];B[ee];W[];B[cc])",
+    ),
+    ("big.sgf", "(;SZ[21];B[aa];W[];B[bb])"),
+    ("broken.sgf", "(;SZ[19];B["),
+    ("notes.txt", "not a game"),
+];
+
+// a folder of its own holding `FILES`
+fn folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("go-database-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&folder);
+    for (path, text) in FILES {
+        let path = folder.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+    folder
+}
+
+fn indexed(name: &str) -> Database {
+    let mut database = Database::default();
+    database.index_folder(folder(name)).unwrap();
+    database
+}
+
+// which files were found and after how many moves, in file name order
+fn found(database: &Database, result: &SearchResult) -> Vec<(String, usize)> {
+    let mut found = result
+        .hits
+        .iter()
+        .map(|hit| {
+            let path = PathBuf::from(&database.game(hit.game).unwrap().path);
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, hit.move_number)
+        })
+        .collect::<Vec<_>>();
+    found.sort();
+    found
+}
+
+fn after(moves: &[(usize, usize)]) -> Board {
+    let mut game = Game::default();
+    for &point in moves {
+        game.play(Move::Play(point)).unwrap();
+    }
+    game.board().clone()
+}
+
+#[test]
+fn indexes_every_game_under_a_folder() {
+    let folder = folder("index");
+    let index = folder.join("index.json");
+    let mut database = Database::default();
+    let report = database.index_folder(&folder).unwrap();
+    assert_eq!((report.games, report.failed), (5, 1));
+    assert_eq!(database.len(), 5);
+
+    database.save(&index).unwrap();
+    let mut loaded = Database::load(&index).unwrap();
+    assert_eq!(loaded.len(), 5);
+    // nothing has changed, so nothing is read again
+    assert_eq!(loaded.index_folder(&folder).unwrap().games, 0);
+
+    let first = loaded.games().find(|entry| entry.path.ends_with("a.sgf"));
+    let first = first.unwrap();
+    assert_eq!(first.result, "B+R");
+    assert_eq!(first.winner(), Some(PlayerColor::Black));
+    assert_eq!(first.moves().count(), 5);
+}
+
+#[test]
+fn keeps_passes_on_big_boards() {
+    let database = indexed("passes");
+    let big = database.games().find(|entry| entry.size == 21).unwrap();
+    assert_eq!(
+        big.moves().collect::<Vec<_>>(),
+        [
+            (PlayerColor::Black, Move::Play((0, 0))),
+            (PlayerColor::White, Move::Pass),
+            (PlayerColor::Black, Move::Play((1, 1))),
+        ]
+    );
+}
+
+#[test]
+fn finds_the_whole_board_in_any_symmetry() {
+    let database = indexed("whole");
+    let board = after(&[(15, 3), (3, 15)]);
+    let result = database.search(&Pattern::new(&board, (0, 0), (18, 18)), false);
+    assert_eq!(
+        found(&database, &result),
+        [("a.sgf".to_string(), 2), ("b.sgf".to_string(), 2)]
+    );
+    // both went on at the same point, seen the way round the pattern is
+    assert_eq!(
+        result.continuations,
+        [Continuation {
+            point: Some((15, 15)),
+            color: PlayerColor::Black,
+            games: 2,
+            wins: 1,
+        }]
+    );
+}
+
+#[test]
+fn finds_part_of_the_board() {
+    let database = indexed("region");
+    let board = after(&[(15, 3)]);
+    let result = database.search(&Pattern::new(&board, (10, 0), (18, 8)), false);
+    assert_eq!(
+        found(&database, &result),
+        [("a.sgf".to_string(), 1), ("b.sgf".to_string(), 1)]
+    );
+    // White answered away from the corner both times, and won once
+    assert_eq!(
+        result.continuations,
+        [Continuation {
+            point: None,
+            color: PlayerColor::White,
+            games: 2,
+            wins: 1,
+        }]
+    );
+}
+
+#[test]
+fn swapping_colours_finds_the_other_side() {
+    let database = indexed("swap");
+    let mut board = Board::new(19);
+    board.set((15, 3), Some(PlayerColor::White));
+    let pattern = Pattern::new(&board, (10, 0), (18, 8));
+
+    // White's first stone is on a 4-4 point as well, in another corner
+    let as_given = database.search(&pattern, false);
+    assert_eq!(
+        found(&database, &as_given),
+        [("a.sgf".to_string(), 2), ("b.sgf".to_string(), 2)]
+    );
+    let either = database.search(&pattern, true);
+    assert_eq!(
+        found(&database, &either),
+        [("a.sgf".to_string(), 1), ("b.sgf".to_string(), 1)]
+    );
+    // Black's stone, seen as White's, and White's answer as Black's
+    assert_eq!(either.continuations[0].color, PlayerColor::Black);
+}
@@ -0,0 +1,78 @@
+//! Looking up corner sequences from a joseki library.
+#![cfg(not(target_arch = "wasm32"))]
+
+use go::board::{Board, PlayerColor, Point};
+use go::joseki::{Continuation, Library};
+use go::sgf;
+
+// a line learned in the top right corner, from the 3-4 point so no position
+// along it is its own mirror image
+const LINE: &[(Point, PlayerColor)] = &[
+    ((16, 3), PlayerColor::Black),
+    ((14, 2), PlayerColor::White),
+    ((15, 4), PlayerColor::Black),
+    ((16, 2), PlayerColor::White),
+    ((17, 2), PlayerColor::Black),
+];
+
+fn library() -> Library {
+    let line = "(;SZ[19];B[qd];W[oc];B[pe];W[qc];B[rc])";
+    let library = Library::from_sgf(&sgf::parse(line).unwrap()).unwrap();
+    assert_eq!(library.size(), 19);
+    library
+}
+
+// one of the board's 8 symmetries: the axes swapped or not, then each mirrored or not
+fn image((x, y): Point, symmetry: usize) -> Point {
+    let (x, y) = if symmetry & 4 != 0 { (y, x) } else { (x, y) };
+    (
+        if symmetry & 2 != 0 { 18 - x } else { x },
+        if symmetry & 1 != 0 { 18 - y } else { y },
+    )
+}
+
+// which corner a point is in
+fn corner((x, y): Point) -> (bool, bool) {
+    (x > 9, y > 9)
+}
+
+#[test]
+fn finds_a_line_in_every_corner_and_mirrored() {
+    let library = library();
+    for symmetry in 0..8 {
+        let mut board = Board::new(19);
+        for &(point, color) in LINE {
+            let expected = Continuation {
+                point: image(point, symmetry),
+                color,
+            };
+            let found = library
+                .continuations(&board)
+                .into_iter()
+                .filter(|c| corner(c.point) == corner(expected.point))
+                .collect::<Vec<_>>();
+            if board.stones().next().is_none() {
+                // an empty corner is its own mirror image, so both of its 3-4 points are offered
+                let mirrored = Continuation {
+                    point: image((18 - point.1, 18 - point.0), symmetry),
+                    color,
+                };
+                assert_eq!(found.len(), 2, "symmetry {}", symmetry);
+                assert!(found.contains(&expected) && found.contains(&mirrored));
+            } else {
+                assert_eq!(found, [expected], "symmetry {}", symmetry);
+            }
+            board.set(expected.point, Some(color));
+        }
+    }
+}
+
+#[test]
+fn knows_nothing_on_another_board_size() {
+    let library = library();
+    assert!(library.continuations(&Board::new(13)).is_empty());
+    let mut board = Board::new(13);
+    board.set((9, 3), Some(PlayerColor::Black));
+    assert!(library.continuations(&board).is_empty());
+    assert!(!library.continuations(&Board::new(19)).is_empty());
+}
@@ -0,0 +1,156 @@
+//! The computer player guided by a tiny randomly initialised network,
+//! written out as ONNX the way a real one would be.
+#![cfg(feature = "nn")]
+
+use std::sync::atomic::AtomicBool;
+
+use go::ai::{best_move_guided, Budget};
+use go::game::{Game, GameSettings, Move};
+use go::nn::{Network, NnError, PLANES};
+use prost::Message;
+use rand::Rng;
+use tract_onnx::pb;
+
+const SIZE: usize = 9;
+
+fn weights(name: &str, dims: &[usize], rng: &mut impl Rng) -> pb::TensorProto {
+    pb::TensorProto {
+        name: name.to_string(),
+        dims: dims.iter().map(|&d| d as i64).collect(),
+        // FLOAT
+        data_type: 1,
+        float_data: (0..dims.iter().product())
+            .map(|_| rng.gen_range(-0.1..0.1))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn node(op_type: &str, input: &[&str], output: &str) -> pb::NodeProto {
+    pb::NodeProto {
+        op_type: op_type.to_string(),
+        input: input.iter().map(|i| i.to_string()).collect(),
+        output: vec![output.to_string()],
+        ..Default::default()
+    }
+}
+
+fn named(name: &str) -> pb::ValueInfoProto {
+    pb::ValueInfoProto {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+// tract wants to know what the graph's input is before anything else
+fn float_input(name: &str, dims: &[usize]) -> pb::ValueInfoProto {
+    use pb::tensor_shape_proto::{dimension, Dimension};
+    let shape = pb::TensorShapeProto {
+        dim: dims
+            .iter()
+            .map(|&d| Dimension {
+                value: Some(dimension::Value::DimValue(d as i64)),
+                ..Default::default()
+            })
+            .collect(),
+    };
+    pb::ValueInfoProto {
+        name: name.to_string(),
+        r#type: Some(pb::TypeProto {
+            value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                elem_type: 1,
+                shape: Some(shape),
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+// one dense layer to each head: flatten, then Gemm to the move logits and
+// Gemm plus Tanh to the value
+fn random_network() -> Network {
+    let mut rng = rand::thread_rng();
+    let inputs = PLANES * SIZE * SIZE;
+    let moves = SIZE * SIZE + 1;
+    let graph = pb::GraphProto {
+        name: "random".to_string(),
+        node: vec![
+            node("Flatten", &["board"], "flat"),
+            node("Gemm", &["flat", "policy_w", "policy_b"], "policy"),
+            node("Gemm", &["flat", "value_w", "value_b"], "value_raw"),
+            node("Tanh", &["value_raw"], "value"),
+        ],
+        initializer: vec![
+            weights("policy_w", &[inputs, moves], &mut rng),
+            weights("policy_b", &[moves], &mut rng),
+            weights("value_w", &[inputs, 1], &mut rng),
+            weights("value_b", &[1], &mut rng),
+        ],
+        input: vec![float_input("board", &[1, PLANES, SIZE, SIZE])],
+        output: vec![named("policy"), named("value")],
+        ..Default::default()
+    };
+    let model = pb::ModelProto {
+        ir_version: 7,
+        opset_import: vec![pb::OperatorSetIdProto {
+            domain: String::new(),
+            version: 13,
+        }],
+        graph: Some(graph),
+        ..Default::default()
+    };
+
+    let path = std::env::temp_dir().join(format!("go-random-{}.onnx", std::process::id()));
+    std::fs::write(&path, model.encode_to_vec()).unwrap();
+    let network = Network::load(&path, SIZE);
+    let _ = std::fs::remove_file(&path);
+    network.unwrap()
+}
+
+fn nine_by_nine() -> Game {
+    Game::new(GameSettings {
+        size: SIZE,
+        ..GameSettings::default()
+    })
+}
+
+#[test]
+fn evaluates_a_position() {
+    let network = random_network();
+    let mut game = nine_by_nine();
+    game.play(Move::Play((4, 4))).unwrap();
+
+    let evaluation = network.evaluate(&game).unwrap();
+    assert_eq!(evaluation.policy.len(), SIZE * SIZE + 1);
+    assert!((evaluation.policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    assert!((-1.0..=1.0).contains(&evaluation.value));
+}
+
+#[test]
+fn guided_search_plays_legal_moves() {
+    let network = random_network();
+    let stop = AtomicBool::new(false);
+    let mut game = nine_by_nine();
+    for _ in 0..10 {
+        let mv = best_move_guided(&game, Budget::Playouts(50), &network, &stop).unwrap();
+        game.play(mv).unwrap();
+    }
+    assert_eq!(game.moves().len(), 10);
+}
+
+#[test]
+fn refuses_other_board_sizes() {
+    let network = random_network();
+    let game = Game::new(GameSettings {
+        size: 13,
+        ..GameSettings::default()
+    });
+    assert!(matches!(
+        network.evaluate(&game),
+        Err(NnError::BoardSize {
+            network: 9,
+            game: 13
+        })
+    ));
+}
@@ -0,0 +1,307 @@
+//! The go-server binary on localhost, with clients talking to it the way the
+//! game's Network panel does.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use go::board::PlayerColor;
+use go::clock::TimeControl;
+use go::game::{GameSettings, Move};
+use go::net::{GameListing, Message, NetEvent, Peer};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// a server on a free port, stopped when dropped
+struct Server {
+    process: Child,
+    port: u16,
+}
+impl Server {
+    fn start(args: &[&str]) -> Server {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_go-server"))
+            .arg("0")
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut lines = BufReader::new(process.stdout.take().unwrap()).lines();
+        let first = lines.next().unwrap().unwrap();
+        let port = first
+            .strip_prefix("listening on port ")
+            .and_then(|port| port.parse().ok())
+            .unwrap_or_else(|| panic!("the server said {:?}", first));
+        // keep reading what it prints, so it never waits on a full pipe
+        std::thread::spawn(move || lines.for_each(drop));
+        Server { process, port }
+    }
+
+    // a client that has shaken hands, and the lobby it was sent
+    fn connect(&self) -> (Client, Vec<GameListing>) {
+        let mut client = Client {
+            peer: Peer::connect(format!("127.0.0.1:{}", self.port)),
+            pending: VecDeque::new(),
+        };
+        match client.next_event() {
+            NetEvent::Connected(_) => {}
+            NetEvent::Received(message) => panic!("{:?} before the handshake", message),
+            NetEvent::Disconnected(e) => panic!("disconnected: {}", e),
+        }
+        let games = client.lobby();
+        (client, games)
+    }
+}
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+// one end of a connection to the server, keeping whatever arrived but wasn't
+// looked at yet
+struct Client {
+    peer: Peer,
+    pending: VecDeque<NetEvent>,
+}
+impl Client {
+    fn send(&self, message: Message) {
+        self.peer.send(message);
+    }
+
+    fn next_event(&mut self) -> NetEvent {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            self.pending.extend(self.peer.poll());
+            if let Some(event) = self.pending.pop_front() {
+                return event;
+            }
+            assert!(Instant::now() < deadline, "nothing arrived");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn any_message(&mut self) -> Message {
+        match self.next_event() {
+            NetEvent::Received(message) => message,
+            NetEvent::Connected(_) => panic!("connected twice"),
+            NetEvent::Disconnected(e) => panic!("disconnected: {}", e),
+        }
+    }
+
+    // the next message that isn't a lobby update, which everyone is sent
+    // whenever a table opens, fills or closes
+    fn received(&mut self) -> Message {
+        loop {
+            match self.any_message() {
+                Message::Lobby { .. } => {}
+                message => return message,
+            }
+        }
+    }
+
+    fn lobby(&mut self) -> Vec<GameListing> {
+        match self.any_message() {
+            Message::Lobby { games } => games,
+            message => panic!("expected the lobby, got {:?}", message),
+        }
+    }
+
+    fn rejected(&mut self) -> String {
+        match self.received() {
+            Message::Rejected { reason } => reason,
+            message => panic!("expected a rejection, got {:?}", message),
+        }
+    }
+}
+
+// black opens a table and white joins it; both have been told the game started
+fn table(server: &Server, settings: GameSettings) -> (Client, Client) {
+    let (mut black, _) = server.connect();
+    black.send(Message::CreateGame { settings });
+    assert!(matches!(black.received(), Message::Created { .. }));
+    let (mut white, games) = server.connect();
+    white.send(Message::JoinGame { id: games[0].id });
+    for (client, color) in [
+        (&mut black, PlayerColor::Black),
+        (&mut white, PlayerColor::White),
+    ] {
+        match client.received() {
+            Message::NewGame { color: seat, .. } => assert_eq!(seat, Some(color)),
+            message => panic!("expected the game to start, got {:?}", message),
+        }
+    }
+    (black, white)
+}
+
+// the token a player is given, right after the game starts, to get their seat back
+fn session(client: &mut Client) -> String {
+    match client.received() {
+        Message::Session { token, .. } => token,
+        message => panic!("expected a session, got {:?}", message),
+    }
+}
+
+#[test]
+fn lists_and_joins_games() {
+    let server = Server::start(&[]);
+    let (mut black, games) = server.connect();
+    assert!(games.is_empty());
+
+    black.send(Message::CreateGame {
+        settings: GameSettings::default(),
+    });
+    let Message::Created { id } = black.received() else {
+        panic!("expected the table to open");
+    };
+    let (mut white, games) = server.connect();
+    assert_eq!(games.len(), 1);
+    assert_eq!((games[0].id, games[0].open), (id, true));
+
+    white.send(Message::JoinGame { id });
+    assert!(matches!(white.received(), Message::NewGame { .. }));
+    let (mut late, games) = server.connect();
+    assert!(!games[0].open);
+    late.send(Message::JoinGame { id });
+    assert_eq!(late.rejected(), "That game already has two players");
+}
+
+#[test]
+fn only_takes_legal_moves_in_turn() {
+    let server = Server::start(&[]);
+    let (mut black, mut white) = table(&server, GameSettings::default());
+    session(&mut black);
+    session(&mut white);
+
+    let mv = Message::Move {
+        mv: Move::Play((3, 3)),
+    };
+    white.send(mv.clone());
+    assert_eq!(white.rejected(), "It isn't your turn");
+    black.send(mv.clone());
+    for client in [&mut black, &mut white] {
+        assert_eq!(client.received(), mv);
+        assert!(matches!(client.received(), Message::Clock { .. }));
+    }
+    white.send(mv);
+    assert_eq!(white.rejected(), "that point is occupied");
+}
+
+#[test]
+fn resigns_on_either_turn() {
+    let server = Server::start(&[]);
+    let (mut black, mut white) = table(&server, GameSettings::default());
+    session(&mut black);
+    session(&mut white);
+    // white gives up while black is still thinking
+    white.send(Message::Resign {
+        color: PlayerColor::White,
+    });
+    for client in [&mut black, &mut white] {
+        assert_eq!(
+            client.received(),
+            Message::Resign {
+                color: PlayerColor::White
+            }
+        );
+    }
+    black.send(Message::Move {
+        mv: Move::Play((3, 3)),
+    });
+    assert_eq!(black.rejected(), "the game is over");
+}
+
+#[test]
+fn holds_a_dropped_seat_until_the_player_resumes() {
+    let server = Server::start(&[]);
+    let main_time = Duration::from_secs(600);
+    let settings = GameSettings {
+        time_control: TimeControl::ByoYomi {
+            main_time,
+            periods: 1,
+            period_time: Duration::from_secs(30),
+        },
+        ..GameSettings::default()
+    };
+    let (mut black, mut white) = table(&server, settings);
+    session(&mut black);
+    let token = session(&mut white);
+    let mv = Move::Play((3, 3));
+    black.send(Message::Move { mv });
+    for client in [&mut black, &mut white] {
+        assert_eq!(client.received(), Message::Move { mv });
+        assert!(matches!(client.received(), Message::Clock { .. }));
+    }
+
+    // white's connection drops on their turn; their clock stops while they're away
+    drop(white);
+    assert!(matches!(
+        black.received(),
+        Message::PlayerAway {
+            color: PlayerColor::White,
+            pause_clocks: true,
+            ..
+        }
+    ));
+    std::thread::sleep(Duration::from_secs(1));
+    let (mut white, _) = server.connect();
+    white.send(Message::Resume { token });
+    match white.received() {
+        Message::Snapshot { moves, color, .. } => {
+            assert_eq!(moves, [(PlayerColor::Black, mv)]);
+            assert_eq!(color, Some(PlayerColor::White));
+        }
+        message => panic!("expected a snapshot, got {:?}", message),
+    }
+    for client in [&mut black, &mut white] {
+        assert_eq!(
+            client.received(),
+            Message::PlayerBack {
+                color: PlayerColor::White
+            }
+        );
+        let Message::Clock { players } = client.received() else {
+            panic!("expected the clocks");
+        };
+        let spent = main_time - players[PlayerColor::White.index()].main_time;
+        assert!(
+            spent < Duration::from_millis(500),
+            "white was charged {:?}",
+            spent
+        );
+    }
+
+    // and carry on where they were
+    white.send(Message::Move {
+        mv: Move::Play((15, 15)),
+    });
+    assert!(matches!(black.received(), Message::Move { .. }));
+}
+
+#[test]
+fn refuses_a_wrong_token_and_an_expired_seat() {
+    let server = Server::start(&["--grace", "1"]);
+    let (mut black, mut white) = table(&server, GameSettings::default());
+    session(&mut black);
+    let token = session(&mut white);
+    drop(white);
+    assert!(matches!(black.received(), Message::PlayerAway { .. }));
+
+    let (mut other, _) = server.connect();
+    other.send(Message::Resume {
+        token: "not the token".to_string(),
+    });
+    assert_eq!(other.rejected(), "That game is no longer waiting for you");
+
+    // once the grace period is over the seat is given up, and the game with it
+    assert_eq!(
+        black.received(),
+        Message::PlayerLeft {
+            color: PlayerColor::White
+        }
+    );
+    other.send(Message::Resume { token });
+    assert_eq!(other.rejected(), "That game is no longer waiting for you");
+}
@@ -0,0 +1,151 @@
+//! Working through problem collections and keeping the results.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::path::PathBuf;
+
+use go::board::PlayerColor;
+use go::game::Move;
+use go::sgf;
+use go::tsumego::{Progress, Record, Status, Trainer};
+
+// black to kill in the corner: one line marked right, one marked wrong with a
+// bad move, one with a comment, and one left unmarked; then the same problem
+// with the right line marked TE instead
+const MARKED: &str = "\
+(;SZ[9]PL[B]AB[ca][cb][bc][ac]AW[ba][bb][ab]C[Black to kill]
+  (;B[aa];W[da];B[db]C[RIGHT])
+  (;B[da]BM[1];W[aa]C[White lives])
+  (;B[ad]C[Wrong, this gives White time];W[aa])
+  (;B[db];W[aa]))
+(;SZ[9]AB[ca][cb][bc][ac]AW[ba][bb][ab]
+  (;B[aa];W[da];B[db]TE[1])
+  (;B[da];W[aa]))";
+
+// nothing marked, so only the main line solves; white moves first without PL
+const UNMARKED: &str = "\
+(;SZ[9]AB[ba][bb][ab]AW[ca][cb][bc][ac]
+  (;W[aa];B[da];W[db])
+  (;W[da];B[aa]))";
+
+fn point(text: &str) -> Move {
+    Move::Play(sgf::decode_point(text, 9).unwrap())
+}
+
+// a collection saved in a folder of its own, and where its progress is kept
+fn collection(name: &str, text: &str) -> (PathBuf, PathBuf) {
+    let folder = std::env::temp_dir().join(format!("go-tsumego-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    let path = folder.join("corner.sgf");
+    std::fs::write(&path, text).unwrap();
+    (path, folder.join("progress.json"))
+}
+
+fn trainer(name: &str, text: &str) -> Trainer {
+    let (path, progress) = collection(name, text);
+    Trainer::open(path, progress).unwrap()
+}
+
+#[test]
+fn solves_a_line_marked_right() {
+    let mut trainer = trainer("right", MARKED);
+    for index in 0..2 {
+        trainer.start(index);
+        assert_eq!(trainer.play(point("aa")), Some(point("da")));
+        assert_eq!(trainer.status, Status::Playing);
+        assert_eq!(trainer.play(point("db")), None);
+        assert_eq!(trainer.status, Status::Solved, "problem {}", index + 1);
+    }
+}
+
+#[test]
+fn fails_a_line_marked_wrong_but_shows_the_answer() {
+    let mut trainer = trainer("wrong", MARKED);
+    trainer.start(0);
+    assert_eq!(trainer.comment(), Some("Black to kill"));
+    assert_eq!(trainer.play(point("da")), Some(point("aa")));
+    assert_eq!(trainer.status, Status::Failed);
+    assert_eq!(trainer.comment(), Some("White lives"));
+    // nothing more is taken once it's over
+    assert_eq!(trainer.play(point("db")), None);
+
+    trainer.start(0);
+    assert_eq!(trainer.play(point("ad")), Some(point("aa")));
+    assert_eq!(trainer.status, Status::Failed);
+}
+
+#[test]
+fn fails_an_unmarked_line_when_others_are_marked() {
+    let mut trainer = trainer("unmarked-line", MARKED);
+    trainer.start(0);
+    assert_eq!(trainer.play(point("db")), Some(point("aa")));
+    assert_eq!(trainer.status, Status::Failed);
+
+    // and a move the collection doesn't know at all
+    trainer.start(1);
+    assert_eq!(trainer.play(point("ee")), None);
+    assert_eq!(trainer.status, Status::Failed);
+}
+
+#[test]
+fn solves_the_main_line_when_nothing_is_marked() {
+    let mut trainer = trainer("main-line", UNMARKED);
+    trainer.start(0);
+    assert_eq!(trainer.play(point("aa")), Some(point("da")));
+    assert_eq!(trainer.play(point("db")), None);
+    assert_eq!(trainer.status, Status::Solved);
+
+    trainer.start(0);
+    assert_eq!(trainer.play(point("da")), Some(point("aa")));
+    assert_eq!(trainer.status, Status::Failed);
+}
+
+#[test]
+fn takes_the_solver_from_the_first_move_without_pl() {
+    let problems = go::tsumego::parse_collection(UNMARKED).unwrap();
+    assert_eq!(problems[0].solver(), PlayerColor::White);
+    assert_eq!(problems[0].start.to_move(), PlayerColor::White);
+    assert_eq!(problems[0].name, "Problem 1");
+
+    let problems = go::tsumego::parse_collection(MARKED).unwrap();
+    assert_eq!(problems[0].solver(), PlayerColor::Black);
+    assert_eq!(problems[1].solver(), PlayerColor::Black);
+}
+
+#[test]
+fn keeps_progress_between_sessions() {
+    let (path, progress) = collection("progress", MARKED);
+    let mut trainer = Trainer::open(&path, &progress).unwrap();
+    trainer.start(0);
+    trainer.play(point("da"));
+    trainer.start(0);
+    trainer.play(point("aa"));
+    trainer.play(point("db"));
+    assert_eq!(
+        trainer.record(0),
+        Record {
+            attempts: 2,
+            solved: 1
+        }
+    );
+
+    let trainer = Trainer::open(&path, &progress).unwrap();
+    assert_eq!(
+        trainer.record(0),
+        Record {
+            attempts: 2,
+            solved: 1
+        }
+    );
+    assert_eq!(trainer.record(1), Record::default());
+    assert_eq!(trainer.solved_count(), 1);
+
+    let saved = Progress::load(&progress);
+    assert_eq!(saved.problems.keys().collect::<Vec<_>>(), ["corner.sgf#1"]);
+    saved.save(&progress).unwrap();
+    assert_eq!(Progress::load(&progress), saved);
+
+    // a file that isn't progress counts as none
+    std::fs::write(&progress, "not json").unwrap();
+    assert_eq!(Progress::load(&progress), Progress::default());
+}
@@ -0,0 +1,41 @@
+//! The rules engine under wasm32, without a browser:
+//! `wasm-pack test --node` or `cargo test --target wasm32-unknown-unknown`
+//! with wasm-bindgen-test-runner.
+#![cfg(target_arch = "wasm32")]
+
+use go::board::PlayerColor;
+use go::game::{Game, GameSettings, Move};
+use go::net::Message;
+use go::sgf;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn captures_a_stone() {
+    let mut game = Game::default();
+    for point in [(1, 0), (0, 0), (0, 1)] {
+        game.play(Move::Play(point)).unwrap();
+    }
+    assert_eq!(game.board().get((0, 0)), None);
+    assert_eq!(game.captures(PlayerColor::Black), 1);
+}
+
+#[wasm_bindgen_test]
+fn sgf_round_trip() {
+    let mut game = Game::new(GameSettings::default());
+    for mv in [Move::Play((3, 3)), Move::Play((15, 15)), Move::Pass] {
+        game.play(mv).unwrap();
+    }
+    let text = game.to_sgf().to_string();
+    let trees = sgf::parse(&text).unwrap();
+    let loaded = Game::from_sgf(&trees[0]).unwrap();
+    assert_eq!(loaded.moves(), game.moves());
+}
+
+#[wasm_bindgen_test]
+fn messages_survive_json() {
+    let message = Message::Move {
+        mv: Move::Play((9, 9)),
+    };
+    let line = serde_json::to_string(&message).unwrap();
+    assert_eq!(serde_json::from_str::<Message>(&line).unwrap(), message);
+}